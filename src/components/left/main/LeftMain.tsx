@@ -1,4 +1,3 @@
-import type { Update } from '@tauri-apps/plugin-updater';
 import type { FC } from '../../../lib/teact/teact';
 import {
   memo, useEffect, useRef, useState,
@@ -6,14 +5,13 @@ import {
 import { getActions } from '../../../global';
 
 import type { FolderEditDispatch } from '../../../hooks/reducers/useFoldersReducer';
+import type { TauriUpdateInfo } from '../../../types/tauri';
 import { LeftColumnContent } from '../../../types';
 
-import { DEBUG } from '../../../config';
-import { IS_TAURI } from '../../../util/browser/globalEnvironment';
 import { IS_TOUCH_ENV } from '../../../util/browser/windowEnvironment';
 import buildClassName from '../../../util/buildClassName';
 
-import useInterval from '../../../hooks/schedulers/useInterval';
+import useTauriEvent from '../../../hooks/tauri/useTauriEvent';
 import useForumPanelRender from '../../../hooks/useForumPanelRender';
 import useLastCallback from '../../../hooks/useLastCallback';
 import useOldLang from '../../../hooks/useOldLang';
@@ -49,7 +47,6 @@ type OwnProps = {
 
 const TRANSITION_RENDER_COUNT = Object.keys(LeftColumnContent).length / 2;
 const BUTTON_CLOSE_DELAY_MS = 250;
-const TAURI_CHECK_UPDATE_INTERVAL = 10 * 60 * 1000;
 
 let closeTimeout: number | undefined;
 
@@ -71,7 +68,8 @@ const LeftMain: FC<OwnProps> = ({
 }) => {
   const { openLeftColumnContent } = getActions();
   const [isNewChatButtonShown, setIsNewChatButtonShown] = useState(IS_TOUCH_ENV);
-  const [tauriUpdate, setTauriUpdate] = useState<Update>();
+  const [tauriUpdate, setTauriUpdate] = useState<TauriUpdateInfo>();
+  const [isTauriUpdateReady, setIsTauriUpdateReady] = useState(false);
   const [isTauriUpdateDownloading, setIsTauriUpdateDownloading] = useState(false);
 
   const {
@@ -118,11 +116,13 @@ const LeftMain: FC<OwnProps> = ({
   const handleUpdateClick = useLastCallback(async () => {
     if (tauriUpdate) {
       try {
-        setIsTauriUpdateDownloading(true);
-        await tauriUpdate.downloadAndInstall();
-        setIsTauriUpdateDownloading(false);
+        if (!isTauriUpdateReady) {
+          setIsTauriUpdateDownloading(true);
+          await window.tauri?.downloadUpdate();
+          setIsTauriUpdateDownloading(false);
+        }
 
-        await window.tauri?.relaunch();
+        await window.tauri?.installAndRelaunch();
       } catch (e) {
         // eslint-disable-next-line no-console
         console.error('Failed to download and install Tauri update', e);
@@ -173,10 +173,11 @@ const LeftMain: FC<OwnProps> = ({
     checkTauriUpdate();
   }, []);
 
-  useInterval(
-    checkTauriUpdate,
-    (IS_TAURI && !DEBUG) ? TAURI_CHECK_UPDATE_INTERVAL : undefined,
-  );
+  // Later checks run in the background, which downloads new versions before announcing them
+  useTauriEvent<TauriUpdateInfo>('update-ready', (event) => {
+    setTauriUpdate(event.payload);
+    setIsTauriUpdateReady(true);
+  });
 
   const lang = useOldLang();
 
@@ -3,19 +3,31 @@ import { useEffect } from '../../lib/teact/teact';
 
 import { IS_TAURI } from '../../util/browser/globalEnvironment';
 
+import useLastCallback from '../useLastCallback';
+
 export default function useTauriEvent<T>(name: string, callback: (event: Event<T>) => void) {
+  // Keeps the subscription across renders, as inline callbacks change every time
+  const handleEvent = useLastCallback(callback);
+
   return useEffect(() => {
     if (!IS_TAURI) {
       return undefined;
     }
 
+    let isUnmounted = false;
     let removeListener: VoidFunction | undefined;
 
     const setUpListener = async () => {
-      const { listen } = await import('@tauri-apps/api/event');
-      removeListener = await listen<T>(name, (event) => {
-        callback(event);
-      });
+      const { getCurrentWebviewWindow } = await import('@tauri-apps/api/webviewWindow');
+      const unlisten = await getCurrentWebviewWindow().listen<T>(name, handleEvent);
+      if (isUnmounted) {
+        unlisten();
+        return;
+      }
+
+      removeListener = unlisten;
+      // Events that arrived before the listener are delivered now
+      await window.tauri.subscribeEvent(name);
     };
 
     setUpListener().catch((error) => {
@@ -24,7 +36,11 @@ export default function useTauriEvent<T>(name: string, callback: (event: Event<T
     });
 
     return () => {
-      removeListener?.();
+      isUnmounted = true;
+      if (removeListener) {
+        removeListener();
+        window.tauri.unsubscribeEvent(name);
+      }
     };
-  }, [name, callback]);
+  }, [name, handleEvent]);
 }
@@ -95,6 +95,10 @@ async function init() {
     );
 
     betterView();
+
+    if (IS_TAURI) {
+      window.tauri.frontendReady();
+    }
   });
 
   if (DEBUG) {
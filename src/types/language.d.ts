@@ -1849,6 +1849,7 @@ export interface LangPair {
   'PremiumPreviewAiTools': undefined;
   'PremiumPreviewAiToolsDescription': undefined;
   'NativeDownloadFailed': undefined;
+  'NativeDownloadDangerous': undefined;
   'DescriptionAboutGram': undefined;
   'ButtonTopUpViaFragment': undefined;
   'GramModalHint': undefined;
@@ -3882,6 +3883,13 @@ export interface LangPairWithVariables<V = LangVariable> {
   'UnofficialSecurityRisk': {
     'peer': V;
   };
+  'NativeUpdateRolledBack': {
+    'failedVersion': V;
+    'version': V;
+  };
+  'NativeShortcutConflict': {
+    'shortcut': V;
+  };
 }
 
 export interface LangPairPlural {
@@ -1,15 +1,246 @@
 import type { Window as TauriWindow } from '@tauri-apps/api/window';
-import type { Update } from '@tauri-apps/plugin-updater';
+
+export type TauriNotificationAvatar = {
+  name: string;
+  colorIndex?: number;
+  photoHash?: string;
+};
+
+export type TauriNotificationPayload = {
+  chatId: string;
+  messageId?: number;
+  title: string;
+  body: string;
+  isSilent?: boolean;
+  avatar?: TauriNotificationAvatar;
+};
+
+export type TauriQuietHours = {
+  isEnabled: boolean;
+  startMinute: number;
+  endMinute: number;
+  days: number[];
+};
+
+export type TauriDndSettings = {
+  mutedUntil?: number;
+  quietHours?: TauriQuietHours;
+  shouldFollowSystem: boolean;
+};
+
+export type TauriDndStatus = TauriDndSettings & {
+  isActive: boolean;
+  isSystemActive: boolean;
+};
+
+export type TauriDownloadState = 'requested' | 'inProgress' | 'finished' | 'failed' | 'cancelled';
+
+export type TauriDownloadRisk = 'safe' | 'suspicious' | 'dangerous';
+
+export type TauriDownload = {
+  id: string;
+  url: string;
+  windowLabel: string;
+  fileName: string;
+  destination: string;
+  state: TauriDownloadState;
+  receivedBytes: number;
+  size?: number;
+  startedAt: number;
+  endedAt?: number;
+  risk?: TauriDownloadRisk;
+  sha256?: string;
+  isMissing: boolean;
+  canRetry: boolean;
+};
+
+export type TauriDownloadSettings = {
+  directory?: string;
+  shouldAskWhereToSave: boolean;
+};
+
+export type TauriUpdateChannel = 'stable' | 'beta';
+
+export type TauriUpdaterConfig = {
+  isEnabled: boolean;
+  channel: TauriUpdateChannel;
+  endpoint?: string;
+};
+
+export type TauriUpdaterStatus = TauriUpdaterConfig & {
+  isSupported: boolean;
+  readyVersion?: string;
+};
+
+export type TauriUpdateInfo = {
+  version: string;
+  currentVersion: string;
+  body?: string;
+  date?: string;
+};
+
+export type TauriWindowSettings = {
+  width: number;
+  height: number;
+};
+
+export type TauriBadgeSettings = {
+  backgroundColor: string;
+  mutedBackgroundColor: string;
+  textColor: string;
+};
+
+export type TauriTranslationSettings = {
+  labels: Record<string, string>;
+};
+
+export type TauriWindowRole = 'main' | 'secondary';
+
+export type TauriZoomSettings = {
+  levels: Partial<Record<TauriWindowRole, number>>;
+};
+
+export type TauriProxyType = 'http' | 'socks5';
+
+export type TauriProxySettings = {
+  type: TauriProxyType;
+  host: string;
+  port: number;
+  username?: string;
+  // Write-only, settings from the shell never contain it
+  password?: string;
+  // Hosts reached directly, e.g. `localhost` or `*.example.com`
+  bypass?: string[];
+};
+
+export type TauriNetworkSettings = {
+  proxy?: TauriProxySettings;
+};
+
+export type TauriAutoplayPolicy = 'noUserGestureRequired' | 'userGestureRequired' | 'documentUserActivationRequired';
+
+export type TauriWebviewSettings = {
+  isHardwareAccelerationEnabled: boolean;
+  autoplayPolicy: TauriAutoplayPolicy;
+  isSmoothScrollingEnabled: boolean;
+  // Chromium switches on Windows, `NAME=value` environment variables on Linux
+  rawArgs: string;
+};
+
+export type TauriShortcutSettings = {
+  // Accelerators like `CommandOrControl+Shift+T`, empty when disabled
+  toggleApp: string;
+  quickCompose: string;
+};
+
+export type TauriMenuBarSettings = {
+  // Only Linux shows the menu bar inside windows and can hide it
+  isVisible: boolean;
+};
+
+// Rejection of `openNewWindow`, which only opens URLs of the app origin, relative ones resolved against it
+export type TauriOpenWindowError = {
+  kind: 'invalidUrl' | 'window';
+  reason: string;
+} | {
+  kind: 'foreignOrigin';
+  origin: string;
+} | {
+  kind: 'credentials';
+};
+
+export type TauriContextMenuContext = {
+  selectionText?: string;
+  linkUrl?: string;
+  // `data:` for images the page holds as `blob:`, which the shell cannot fetch
+  imageUrl?: string;
+  isEditable: boolean;
+  // Word under the pointer in an editable field, checked against the active dictionaries
+  misspelledWord?: string;
+};
+
+export type TauriSpellingDictionary = {
+  language: string;
+  path: string;
+};
+
+export type TauriSpellingSettings = {
+  // Dictionary names like `en_US`, spell checking is off when empty
+  languages: string[];
+};
+
+export type TauriSettings = {
+  version: number;
+  window: TauriWindowSettings;
+  badge: TauriBadgeSettings;
+  translations: TauriTranslationSettings;
+  zoom: TauriZoomSettings;
+  network: TauriNetworkSettings;
+  webview: TauriWebviewSettings;
+  shortcuts: TauriShortcutSettings;
+  menuBar: TauriMenuBarSettings;
+  spelling: TauriSpellingSettings;
+  dnd: TauriDndSettings;
+  downloads: TauriDownloadSettings;
+  updater: TauriUpdaterConfig;
+};
+
+// Merge patch: omitted fields are kept, `null` resets a field to its default
+export type TauriSettingsPatch = {
+  [K in Exclude<keyof TauriSettings, 'version'>]?: {
+    [F in keyof TauriSettings[K]]?: TauriSettings[K][F] | null;
+  } | null;
+};
+
+export type TauriMuteDuration = 'oneHour' | 'eightHours' | 'untilTomorrow';
 
 type TauriApi = {
   version: string;
   markTitleBarOverlay: (isOverlay: boolean, isMobile?: boolean) => Promise<void>;
   setNotificationsCount: (amount: number, isMuted?: boolean) => Promise<void>;
   openNewWindow: (url: string) => Promise<void>;
+  openExternalUrl: (url: string) => Promise<void>;
   relaunch: () => Promise<void>;
-  checkUpdate: () => Promise<Update | null>;
+  checkUpdate: () => Promise<TauriUpdateInfo | null>;
+  downloadUpdate: () => Promise<TauriUpdateInfo>;
+  installAndRelaunch: () => Promise<void>;
+  setCallActive: (isActive: boolean) => Promise<void>;
+  getUpdaterStatus: () => Promise<TauriUpdaterStatus>;
+  setUpdaterConfig: (config: TauriUpdaterConfig) => Promise<void>;
   getCurrentWindow: () => Promise<TauriWindow>;
   setWindowTitle: (title: string) => Promise<void>;
+  frontendReady: () => Promise<void>;
+  // Events like `deeplink` wait in the shell until the window subscribes to them
+  subscribeEvent: (event: string) => Promise<void>;
+  unsubscribeEvent: (event: string) => Promise<void>;
+  showNotification: (payload: TauriNotificationPayload) => Promise<void>;
+  cacheNotificationAvatar: (photoHash: string, photo: Uint8Array) => Promise<void>;
+  confirmNotificationReply: (id: number, success: boolean) => Promise<void>;
+  setActiveChat: (chatId?: string) => Promise<void>;
+  getDndStatus: () => Promise<TauriDndStatus>;
+  muteNotifications: (duration: TauriMuteDuration) => Promise<void>;
+  unmuteNotifications: () => Promise<void>;
+  setQuietHours: (quietHours?: TauriQuietHours) => Promise<void>;
+  setFollowSystemDnd: (shouldFollow: boolean) => Promise<void>;
+  listDownloads: () => Promise<TauriDownload[]>;
+  cancelDownload: (id: string) => Promise<void>;
+  retryDownload: (id: string) => Promise<void>;
+  openDownload: (id: string) => Promise<void>;
+  revealDownload: (id: string) => Promise<void>;
+  clearDownloadHistory: () => Promise<void>;
+  getDownloadSettings: () => Promise<TauriDownloadSettings>;
+  setDownloadSettings: (settings: TauriDownloadSettings) => Promise<void>;
+  chooseDownloadDirectory: () => Promise<TauriDownloadSettings>;
+  getSettings: () => Promise<TauriSettings>;
+  updateSettings: (patch: TauriSettingsPatch) => Promise<TauriSettings>;
+  zoomIn: () => Promise<number>;
+  zoomOut: () => Promise<number>;
+  zoomReset: () => Promise<number>;
+  setZoom: (factor: number) => Promise<number>;
+  testProxyConnection: (proxy: TauriProxySettings, target?: string) => Promise<number>;
+  showContextMenu: (context: TauriContextMenuContext) => Promise<void>;
+  listSpellingDictionaries: () => Promise<TauriSpellingDictionary[]>;
+  setSpellingLanguages: (languages: string[]) => Promise<void>;
 };
 
 declare global {
@@ -11,6 +11,7 @@ import {
   getChatAvatarHash,
   getChatTitle,
   getMessageRecentReaction,
+  getPeerColorKey,
   getUserFullName,
 } from '../global/helpers';
 import {
@@ -367,6 +368,27 @@ async function getAvatar(chat: ApiPeer) {
   return mediaData;
 }
 
+async function getTauriAvatar(chat: ApiChat, icon?: string) {
+  const global = getGlobal();
+  if (global.passcode.isScreenLocked) return undefined;
+
+  const photoHash = getChatAvatarHash(chat);
+  if (icon && photoHash) {
+    try {
+      const response = await fetch(icon);
+      await window.tauri.cacheNotificationAvatar(photoHash, new Uint8Array(await response.arrayBuffer()));
+    } catch {
+      // Fall back to initials
+    }
+  }
+
+  return {
+    name: getChatTitle(oldTranslate, chat, chat.id === global.currentUserId),
+    colorIndex: getPeerColorKey(chat, true),
+    photoHash,
+  };
+}
+
 function getReactionEmoji(reaction: ApiPeerReaction) {
   let emoji;
   if (reaction.reaction.type === 'emoji') {
@@ -473,6 +495,15 @@ export async function notifyAboutMessage({
         },
       });
     }
+  } else if (IS_TAURI) {
+    await window.tauri.showNotification({
+      chatId: chat.id,
+      messageId: message.id,
+      title,
+      body,
+      isSilent: isSilent || message.isSilent,
+      avatar: await getTauriAvatar(chat, icon),
+    });
   } else {
     const dispatch = getActions();
     const options: NotificationOptions = {
@@ -1,3 +1,9 @@
+import type {
+  TauriContextMenuContext, TauriDndStatus, TauriDownload, TauriDownloadSettings, TauriMuteDuration,
+  TauriNotificationPayload, TauriProxySettings, TauriQuietHours, TauriSettings, TauriSettingsPatch,
+  TauriSpellingDictionary, TauriUpdateInfo, TauriUpdaterConfig, TauriUpdaterStatus,
+} from '../../types/tauri';
+
 import { IS_MAC_OS } from '../browser/windowEnvironment';
 
 export default function initTauriApi() {
@@ -15,7 +21,12 @@ export default function initTauriApi() {
 
   async function openNewWindow(url: string) {
     const core = await corePromise;
-    return core.invoke<boolean>('open_new_window_cmd', { url });
+    return core.invoke<void>('open_new_window_cmd', { url });
+  }
+
+  async function openExternalUrl(url: string) {
+    const core = await corePromise;
+    return core.invoke<void>('open_external_url', { url });
   }
 
   async function setWindowTitle(title: string) {
@@ -23,15 +34,240 @@ export default function initTauriApi() {
     return core.invoke<void>('set_window_title', { title });
   }
 
+  async function frontendReady() {
+    const core = await corePromise;
+    return core.invoke<void>('frontend_ready');
+  }
+
+  async function subscribeEvent(event: string) {
+    const core = await corePromise;
+    return core.invoke<void>('subscribe_event', { event });
+  }
+
+  async function unsubscribeEvent(event: string) {
+    const core = await corePromise;
+    return core.invoke<void>('unsubscribe_event', { event });
+  }
+
+  async function showNotification(payload: TauriNotificationPayload) {
+    const core = await corePromise;
+    return core.invoke<void>('show_notification', { payload });
+  }
+
+  // The photo goes as the raw request body, which is far cheaper than a JSON array of bytes
+  async function cacheNotificationAvatar(photoHash: string, photo: Uint8Array) {
+    const core = await corePromise;
+    return core.invoke<void>('cache_notification_avatar', photo, {
+      headers: { 'Tauri-Photo-Hash': photoHash },
+    });
+  }
+
+  async function confirmNotificationReply(id: number, success: boolean) {
+    const core = await corePromise;
+    return core.invoke<void>('confirm_notification_reply', { id, success });
+  }
+
+  async function setActiveChat(chatId?: string) {
+    const core = await corePromise;
+    return core.invoke<void>('set_active_chat', { chatId });
+  }
+
+  async function getDndStatus() {
+    const core = await corePromise;
+    return core.invoke<TauriDndStatus>('get_dnd_status');
+  }
+
+  async function muteNotifications(duration: TauriMuteDuration) {
+    const core = await corePromise;
+    return core.invoke<void>('mute_notifications', { duration });
+  }
+
+  async function unmuteNotifications() {
+    const core = await corePromise;
+    return core.invoke<void>('unmute_notifications');
+  }
+
+  async function setQuietHours(quietHours?: TauriQuietHours) {
+    const core = await corePromise;
+    return core.invoke<void>('set_quiet_hours', { quietHours });
+  }
+
+  async function setFollowSystemDnd(shouldFollow: boolean) {
+    const core = await corePromise;
+    return core.invoke<void>('set_follow_system_dnd', { shouldFollow });
+  }
+
+  async function listDownloads() {
+    const core = await corePromise;
+    return core.invoke<TauriDownload[]>('list_downloads');
+  }
+
+  async function cancelDownload(id: string) {
+    const core = await corePromise;
+    return core.invoke<void>('cancel_download', { id });
+  }
+
+  async function retryDownload(id: string) {
+    const core = await corePromise;
+    return core.invoke<void>('retry_download', { id });
+  }
+
+  async function openDownload(id: string) {
+    const core = await corePromise;
+    return core.invoke<void>('open_download', { id });
+  }
+
+  async function revealDownload(id: string) {
+    const core = await corePromise;
+    return core.invoke<void>('reveal_download', { id });
+  }
+
+  async function clearDownloadHistory() {
+    const core = await corePromise;
+    return core.invoke<void>('clear_download_history');
+  }
+
+  async function getDownloadSettings() {
+    const core = await corePromise;
+    return core.invoke<TauriDownloadSettings>('get_download_settings');
+  }
+
+  async function setDownloadSettings(settings: TauriDownloadSettings) {
+    const core = await corePromise;
+    return core.invoke<void>('set_download_settings', { settings });
+  }
+
+  async function chooseDownloadDirectory() {
+    const core = await corePromise;
+    return core.invoke<TauriDownloadSettings>('choose_download_directory');
+  }
+
+  async function getSettings() {
+    const core = await corePromise;
+    return core.invoke<TauriSettings>('get_settings');
+  }
+
+  async function updateSettings(patch: TauriSettingsPatch) {
+    const core = await corePromise;
+    return core.invoke<TauriSettings>('update_settings', { patch });
+  }
+
+  async function zoomIn() {
+    const core = await corePromise;
+    return core.invoke<number>('zoom_in');
+  }
+
+  async function zoomOut() {
+    const core = await corePromise;
+    return core.invoke<number>('zoom_out');
+  }
+
+  async function zoomReset() {
+    const core = await corePromise;
+    return core.invoke<number>('zoom_reset');
+  }
+
+  async function setZoom(factor: number) {
+    const core = await corePromise;
+    return core.invoke<number>('set_zoom', { factor });
+  }
+
+  async function testProxyConnection(proxy: TauriProxySettings, target?: string) {
+    const core = await corePromise;
+    return core.invoke<number>('test_proxy_connection', { proxy, target });
+  }
+
+  async function showContextMenu(context: TauriContextMenuContext) {
+    const core = await corePromise;
+    return core.invoke<void>('show_context_menu', { context });
+  }
+
+  async function listSpellingDictionaries() {
+    const core = await corePromise;
+    return core.invoke<TauriSpellingDictionary[]>('list_spelling_dictionaries');
+  }
+
+  async function setSpellingLanguages(languages: string[]) {
+    const core = await corePromise;
+    return core.invoke<void>('set_spelling_languages', { languages });
+  }
+
+  async function checkUpdate() {
+    const core = await corePromise;
+    return core.invoke<TauriUpdateInfo | null>('check_for_update');
+  }
+
+  async function downloadUpdate() {
+    const core = await corePromise;
+    return core.invoke<TauriUpdateInfo>('download_update');
+  }
+
+  async function installAndRelaunch() {
+    const core = await corePromise;
+    return core.invoke<void>('install_and_relaunch');
+  }
+
+  async function setCallActive(isActive: boolean) {
+    const core = await corePromise;
+    return core.invoke<void>('set_call_active', { isActive });
+  }
+
+  async function getUpdaterStatus() {
+    const core = await corePromise;
+    return core.invoke<TauriUpdaterStatus>('get_updater_status');
+  }
+
+  async function setUpdaterConfig(config: TauriUpdaterConfig) {
+    const core = await corePromise;
+    return core.invoke<void>('set_updater_config', { config });
+  }
+
   // @ts-expect-error
   window.tauri ??= {};
   Object.assign(window.tauri, {
     markTitleBarOverlay,
     setNotificationsCount,
     openNewWindow,
+    openExternalUrl,
     relaunch: () => import('@tauri-apps/plugin-process').then(({ relaunch }) => relaunch()),
-    checkUpdate: () => import('@tauri-apps/plugin-updater').then(({ check }) => check()),
+    checkUpdate,
+    downloadUpdate,
+    installAndRelaunch,
+    setCallActive,
+    getUpdaterStatus,
+    setUpdaterConfig,
     getCurrentWindow: () => import('@tauri-apps/api/window').then(({ getCurrentWindow }) => getCurrentWindow()),
     setWindowTitle,
+    frontendReady,
+    subscribeEvent,
+    unsubscribeEvent,
+    showNotification,
+    cacheNotificationAvatar,
+    confirmNotificationReply,
+    setActiveChat,
+    getDndStatus,
+    muteNotifications,
+    unmuteNotifications,
+    setQuietHours,
+    setFollowSystemDnd,
+    listDownloads,
+    cancelDownload,
+    retryDownload,
+    openDownload,
+    revealDownload,
+    clearDownloadHistory,
+    getDownloadSettings,
+    setDownloadSettings,
+    chooseDownloadDirectory,
+    getSettings,
+    updateSettings,
+    zoomIn,
+    zoomOut,
+    zoomReset,
+    setZoom,
+    testProxyConnection,
+    showContextMenu,
+    listSpellingDictionaries,
+    setSpellingLanguages,
   });
 }
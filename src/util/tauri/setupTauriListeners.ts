@@ -1,34 +1,169 @@
-import { getActions } from '../../global';
+import { addCallback, removeCallback } from '../../lib/teact/teactn';
+import { getActions, getGlobal } from '../../global';
 
-import { DEBUG } from '../../config';
+import type { GlobalState } from '../../global/types';
+import type { TauriContextMenuContext, TauriDownload } from '../../types/tauri';
+import { MAIN_THREAD_ID } from '../../api/types';
+import { LeftColumnContent } from '../../types';
+
+import { DEBUG, FAQ_URL } from '../../config';
+import { selectChatMessages, selectCurrentMessageList } from '../../global/selectors';
 import { MouseButton } from '../browser/windowEnvironment';
+import { blobToDataUri } from '../files';
+import { isLocalMessageId } from '../keys/messageKey';
 
-type DownloadFinishedEvent = {
+type DownloadFinishedEvent = Partial<TauriDownload> & {
   success: boolean;
   url: string;
 };
 
+type UpdateRolledBackEvent = {
+  version: string;
+  failedVersion: string;
+};
+
+type NotificationReplyEvent = {
+  id: number;
+  chatId: string;
+  messageId?: number;
+  text: string;
+};
+
+type ShortcutRegistrationFailedEvent = {
+  action: 'toggleApp' | 'quickCompose';
+  shortcut: string;
+  reason: string;
+};
+
+type MenuActionEvent = {
+  action: 'openSettings' | 'openFaq';
+};
+
+type ContextMenuActionEvent = {
+  action: 'openLink';
+  url: string;
+} | {
+  action: 'replaceMisspelling';
+  word: string;
+  replacement: string;
+};
+
+type WordAtPointer = {
+  word: string;
+  // Selects the word, unless it has changed since
+  select: () => boolean;
+};
+
+type NotificationClickEvent = {
+  chatId: string;
+  messageId?: number;
+};
+
+const WORD_CHAR_REGEX = /[\p{L}\p{M}\p{N}'’]/u;
+const NOTIFICATION_REPLY_TIMEOUT = 30000;
+
 let isSetup = false;
+let contextMenuWord: WordAtPointer | undefined;
 export default function setupTauriListeners() {
   if (isSetup) return;
   isSetup = true;
 
-  // Disable default tauri context menu in production build
-  if (!DEBUG) {
-    document.addEventListener('contextmenu', (event) => {
-      event.preventDefault();
+  // Replace the engine's context menu with a native one wherever the app shows none of its own
+  document.addEventListener('contextmenu', handleContextMenu);
+
+  // Events emitted to a window label also reach global listeners of every other window
+  const webviewWindowPromise = import('@tauri-apps/api/webviewWindow');
+  webviewWindowPromise.then(({ getCurrentWebviewWindow }) => {
+    const currentWindow = getCurrentWebviewWindow();
+
+    currentWindow.listen<MenuActionEvent>('menu-action', (event) => {
+      switch (event.payload.action) {
+        case 'openSettings':
+          getActions().openLeftColumnContent({ contentKey: LeftColumnContent.Settings });
+          break;
+        case 'openFaq':
+          getActions().openUrl({ url: FAQ_URL, shouldSkipModal: true });
+          break;
+      }
     });
-  }
 
-  const eventPromise = import('@tauri-apps/api/event');
-  eventPromise.then(({ listen }) => {
-    listen<DownloadFinishedEvent>('download-finished', (event) => {
-      if (event.payload.success) return;
+    currentWindow.listen<ContextMenuActionEvent>('context-menu-action', (event) => {
+      const { payload } = event;
+      if (payload.action === 'openLink') {
+        openLink(payload.url);
+      } else if (payload.action === 'replaceMisspelling') {
+        replaceMisspelling(payload.word, payload.replacement);
+      }
+    });
+
+    currentWindow.listen('quick-compose', () => {
+      getActions().openLeftColumnContent({ contentKey: LeftColumnContent.Contacts });
+    });
+
+    currentWindow.listen<DownloadFinishedEvent>('download-finished', (event) => {
+      if (event.payload.success) {
+        if (event.payload.risk === 'dangerous') {
+          getActions().showNotification({
+            message: { key: 'NativeDownloadDangerous' },
+          });
+        }
+        return;
+      }
+
+      if (event.payload.state === 'cancelled') return;
 
       getActions().showNotification({
         message: { key: 'NativeDownloadFailed' },
       });
     });
+
+    currentWindow.listen<UpdateRolledBackEvent>('update-rolled-back', (event) => {
+      const { version, failedVersion } = event.payload;
+      getActions().showNotification({
+        message: { key: 'NativeUpdateRolledBack', variables: { version, failedVersion } },
+      });
+    });
+
+    currentWindow.listen<ShortcutRegistrationFailedEvent>('shortcut-registration-failed', (event) => {
+      getActions().showNotification({
+        message: { key: 'NativeShortcutConflict', variables: { shortcut: event.payload.shortcut } },
+      });
+    });
+
+    currentWindow.listen<NotificationReplyEvent>('notification-reply', async (event) => {
+      const { id, chatId, text } = event.payload;
+      const isSent = await sendNotificationReply(chatId, text);
+      window.tauri.confirmNotificationReply(id, isSent);
+    });
+
+    currentWindow.listen<NotificationClickEvent>('notification-click', (event) => {
+      const { chatId, messageId } = event.payload;
+      if (messageId) {
+        getActions().focusMessage({ chatId, messageId, shouldReplaceHistory: true });
+      } else {
+        getActions().openChat({ id: chatId, shouldReplaceHistory: true });
+      }
+    });
+  });
+
+  // Let the shell suppress notifications for the chat that is open in a focused window
+  let activeChatId: string | undefined;
+  addCallback((global: GlobalState) => {
+    const { chatId } = selectCurrentMessageList(global) || {};
+    if (chatId === activeChatId) return;
+
+    activeChatId = chatId;
+    window.tauri.setActiveChat(chatId);
+  });
+
+  // Updates are not installed while a call is in progress
+  let isCallActive = false;
+  addCallback((global: GlobalState) => {
+    const isActive = Boolean(global.phoneCall || global.groupCalls.activeGroupCallId);
+    if (isActive === isCallActive) return;
+
+    isCallActive = isActive;
+    window.tauri.setCallActive(isActive);
   });
 
   // Disable Backspace handling as back navigation
@@ -73,14 +208,151 @@ export default function setupTauriListeners() {
   document.addEventListener('auxclick', handleClick);
 }
 
+async function handleContextMenu(event: MouseEvent) {
+  // Keep the engine's menu with its devtools entries when holding Shift in development
+  if (event.defaultPrevented || (DEBUG && event.shiftKey)) return;
+
+  event.preventDefault();
+  const context = await getContextMenuContext(event);
+  window.tauri.showContextMenu(context);
+}
+
+async function getContextMenuContext(event: MouseEvent): Promise<TauriContextMenuContext> {
+  const target = event.target as HTMLElement | null;
+  const image = target instanceof HTMLImageElement ? target : undefined;
+  const isEditable = target?.tagName === 'INPUT' || target?.tagName === 'TEXTAREA'
+    || Boolean(target?.isContentEditable);
+  contextMenuWord = target && isEditable ? getWordAtPointer(event, target) : undefined;
+
+  return {
+    selectionText: window.getSelection()?.toString() || undefined,
+    linkUrl: target?.closest<HTMLAnchorElement>('a[href]')?.href,
+    imageUrl: image ? await getImageUrl(image) : undefined,
+    isEditable,
+    misspelledWord: contextMenuWord?.word,
+  };
+}
+
+function getWordBounds(text: string, offset: number) {
+  let start = offset;
+  let end = offset;
+  while (start > 0 && WORD_CHAR_REGEX.test(text[start - 1])) start--;
+  while (end < text.length && WORD_CHAR_REGEX.test(text[end])) end++;
+
+  return start < end ? { start, end } : undefined;
+}
+
+function getWordAtPointer(event: MouseEvent, target: HTMLElement): WordAtPointer | undefined {
+  if (target instanceof HTMLInputElement || target instanceof HTMLTextAreaElement) {
+    const { value, selectionStart } = target;
+    const bounds = typeof selectionStart === 'number' ? getWordBounds(value, selectionStart) : undefined;
+    if (!bounds) return undefined;
+
+    const word = value.slice(bounds.start, bounds.end);
+    return {
+      word,
+      select: () => {
+        if (target.value.slice(bounds.start, bounds.end) !== word) return false;
+        target.focus();
+        target.setSelectionRange(bounds.start, bounds.end);
+        return true;
+      },
+    };
+  }
+
+  // Fields like the message composer are `contenteditable`, where the caret follows the pointer
+  const caret = document.caretRangeFromPoint?.(event.clientX, event.clientY);
+  const node = caret?.startContainer;
+  if (!caret || !node || node.nodeType !== Node.TEXT_NODE) return undefined;
+
+  const bounds = getWordBounds(node.textContent || '', caret.startOffset);
+  if (!bounds) return undefined;
+
+  const word = node.textContent!.slice(bounds.start, bounds.end);
+  return {
+    word,
+    select: () => {
+      if (!node.isConnected || node.textContent?.slice(bounds.start, bounds.end) !== word) return false;
+      const range = document.createRange();
+      range.setStart(node, bounds.start);
+      range.setEnd(node, bounds.end);
+      const selection = window.getSelection();
+      selection?.removeAllRanges();
+      selection?.addRange(range);
+      return true;
+    },
+  };
+}
+
+function replaceMisspelling(word: string, replacement: string) {
+  if (contextMenuWord?.word !== word || !contextMenuWord.select()) return;
+
+  contextMenuWord = undefined;
+  // Goes through the editing pipeline, so the field updates its state and undo history
+  document.execCommand('insertText', false, replacement);
+}
+
+async function getImageUrl(image: HTMLImageElement) {
+  const url = image.currentSrc || image.src;
+  if (!url.startsWith('blob:')) return url;
+
+  try {
+    const blob = await fetch(url).then((response) => response.blob());
+    return await blobToDataUri(blob);
+  } catch (e) {
+    return undefined;
+  }
+}
+
+// `sendMessage` reports a failed send through the state of the local message, which is followed until it settles
+function sendNotificationReply(chatId: string, text: string) {
+  const knownIds = new Set(Object.keys(selectChatMessages(getGlobal(), chatId) || {}).map(Number));
+
+  return new Promise<boolean>((resolve) => {
+    let localId: number | undefined;
+
+    const handleGlobal = (global: GlobalState) => {
+      const messages = selectChatMessages(global, chatId) || {};
+      if (localId === undefined) {
+        const localMessage = Object.values(messages).find((message) => (
+          isLocalMessageId(message.id) && !knownIds.has(message.id) && message.content.text?.text === text
+        ));
+        if (!localMessage) return;
+        localId = localMessage.id;
+      }
+
+      // The local message is replaced by the sent one on success
+      const sendingState = messages[localId]?.sendingState;
+      if (sendingState === 'messageSendingStateFailed') {
+        finish(false);
+      } else if (!sendingState) {
+        finish(true);
+      }
+    };
+
+    const timeout = window.setTimeout(() => finish(false), NOTIFICATION_REPLY_TIMEOUT);
+
+    function finish(isSent: boolean) {
+      window.clearTimeout(timeout);
+      removeCallback(handleGlobal);
+      resolve(isSent);
+    }
+
+    addCallback(handleGlobal);
+    getActions().sendMessage({
+      messageList: { chatId, threadId: MAIN_THREAD_ID, type: 'thread' },
+      text,
+    });
+  });
+}
+
 async function openLink(url: string | URL) {
   try {
     const urlObject = url instanceof URL ? url : new URL(url, window.location.href);
     if (window.location.origin === urlObject.origin) {
       await window.tauri.openNewWindow(urlObject.toString());
     } else {
-      const shellPlugin = await import('@tauri-apps/plugin-shell');
-      await shellPlugin.open(urlObject.toString());
+      await window.tauri.openExternalUrl(urlObject.toString());
     }
   } catch (e) {
     // eslint-disable-next-line no-console
@@ -1,3 +1,9 @@
 fn main() {
+  // The proxy APIs of the Network framework only exist from macOS 14 on. Weak linking keeps the app
+  // starting on older versions, where the shell never calls them.
+  if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("macos") {
+    println!("cargo:rustc-link-arg=-Wl,-weak_framework,Network");
+  }
+
   tauri_build::build()
 }
@@ -0,0 +1,370 @@
+use std::sync::{LazyLock, Mutex};
+
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::json;
+use tauri::image::Image;
+use tauri::menu::{IsMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::{AppHandle, Emitter, Manager, WebviewWindow, Wry};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_dialog::DialogExt;
+
+use crate::tray::translated_label;
+
+// Label constants, translatable through `set_menu_translations`
+const CONTEXT_MENU_OPEN_LINK_ID: &str = "context_menu_open_link";
+const CONTEXT_MENU_OPEN_LINK_LABEL: &str = "Open Link";
+const CONTEXT_MENU_COPY_LINK_ID: &str = "context_menu_copy_link";
+const CONTEXT_MENU_COPY_LINK_LABEL: &str = "Copy Link";
+const CONTEXT_MENU_COPY_IMAGE_ID: &str = "context_menu_copy_image";
+const CONTEXT_MENU_COPY_IMAGE_LABEL: &str = "Copy Image";
+const CONTEXT_MENU_COPY_IMAGE_ADDRESS_ID: &str = "context_menu_copy_image_address";
+const CONTEXT_MENU_COPY_IMAGE_ADDRESS_LABEL: &str = "Copy Image Address";
+const CONTEXT_MENU_SAVE_IMAGE_ID: &str = "context_menu_save_image";
+const CONTEXT_MENU_SAVE_IMAGE_LABEL: &str = "Save Image As…";
+const CONTEXT_MENU_COPY_ID: &str = "context_menu_copy";
+const CONTEXT_MENU_COPY_LABEL: &str = "Copy";
+const CONTEXT_MENU_CUT_ID: &str = "context_menu_cut";
+const CONTEXT_MENU_CUT_LABEL: &str = "Cut";
+const CONTEXT_MENU_PASTE_ID: &str = "context_menu_paste";
+const CONTEXT_MENU_PASTE_LABEL: &str = "Paste";
+const CONTEXT_MENU_SELECT_ALL_ID: &str = "context_menu_select_all";
+const CONTEXT_MENU_SELECT_ALL_LABEL: &str = "Select All";
+const CONTEXT_MENU_NO_SUGGESTIONS_ID: &str = "context_menu_no_suggestions";
+const CONTEXT_MENU_NO_SUGGESTIONS_LABEL: &str = "No Spelling Suggestions";
+
+// Followed by the index of the suggestion
+const CONTEXT_MENU_SUGGESTION_ID_PREFIX: &str = "context_menu_suggestion_";
+
+const DEFAULT_IMAGE_NAME: &str = "image";
+
+/// What is under the pointer, as hit-tested by the frontend.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ContextMenuContext {
+  pub selection_text: Option<String>,
+  pub link_url: Option<String>,
+  // `data:` for images only held by the page, like `blob:` ones, which the shell cannot fetch
+  pub image_url: Option<String>,
+  pub is_editable: bool,
+  // Word under the pointer in an editable field, checked against the active dictionaries
+  pub misspelled_word: Option<String>,
+}
+
+// The context of the open menu, read when one of its items is chosen
+struct OpenContextMenu {
+  window_label: String,
+  context: ContextMenuContext,
+  suggestions: Vec<String>,
+}
+
+static OPEN_CONTEXT_MENU: LazyLock<Mutex<Option<OpenContextMenu>>> =
+  LazyLock::new(|| Mutex::new(None));
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+  value.as_deref().filter(|value| !value.trim().is_empty())
+}
+
+fn item(app: &AppHandle, id: &str, default: &str) -> tauri::Result<MenuItem<Wry>> {
+  MenuItem::with_id(app, id, translated_label(id, default), true, None::<&str>)
+}
+
+fn build(
+  app: &AppHandle,
+  context: &ContextMenuContext,
+  suggestions: Option<&[String]>,
+) -> tauri::Result<Option<Menu<Wry>>> {
+  let mut sections: Vec<Vec<Box<dyn IsMenuItem<Wry>>>> = Vec::new();
+
+  match suggestions {
+    Some([]) => sections.push(vec![Box::new(MenuItem::with_id(
+      app,
+      CONTEXT_MENU_NO_SUGGESTIONS_ID,
+      translated_label(
+        CONTEXT_MENU_NO_SUGGESTIONS_ID,
+        CONTEXT_MENU_NO_SUGGESTIONS_LABEL,
+      ),
+      false,
+      None::<&str>,
+    )?)]),
+    Some(suggestions) => {
+      let mut section: Vec<Box<dyn IsMenuItem<Wry>>> = Vec::new();
+      for (index, suggestion) in suggestions.iter().enumerate() {
+        section.push(Box::new(MenuItem::with_id(
+          app,
+          format!("{}{}", CONTEXT_MENU_SUGGESTION_ID_PREFIX, index),
+          suggestion,
+          true,
+          None::<&str>,
+        )?));
+      }
+      sections.push(section);
+    }
+    None => {}
+  }
+
+  if non_empty(&context.link_url).is_some() {
+    sections.push(vec![
+      Box::new(item(
+        app,
+        CONTEXT_MENU_OPEN_LINK_ID,
+        CONTEXT_MENU_OPEN_LINK_LABEL,
+      )?),
+      Box::new(item(
+        app,
+        CONTEXT_MENU_COPY_LINK_ID,
+        CONTEXT_MENU_COPY_LINK_LABEL,
+      )?),
+    ]);
+  }
+
+  if let Some(image_url) = non_empty(&context.image_url) {
+    let mut section: Vec<Box<dyn IsMenuItem<Wry>>> = Vec::new();
+    if image_url.starts_with("data:") {
+      section.push(Box::new(item(
+        app,
+        CONTEXT_MENU_COPY_IMAGE_ID,
+        CONTEXT_MENU_COPY_IMAGE_LABEL,
+      )?));
+    } else {
+      section.push(Box::new(item(
+        app,
+        CONTEXT_MENU_COPY_IMAGE_ADDRESS_ID,
+        CONTEXT_MENU_COPY_IMAGE_ADDRESS_LABEL,
+      )?));
+    }
+    section.push(Box::new(item(
+      app,
+      CONTEXT_MENU_SAVE_IMAGE_ID,
+      CONTEXT_MENU_SAVE_IMAGE_LABEL,
+    )?));
+    sections.push(section);
+  }
+
+  if context.is_editable {
+    // Predefined items act on the focused field natively, including the undo history
+    sections.push(vec![
+      Box::new(PredefinedMenuItem::cut(
+        app,
+        Some(&translated_label(
+          CONTEXT_MENU_CUT_ID,
+          CONTEXT_MENU_CUT_LABEL,
+        )),
+      )?),
+      Box::new(PredefinedMenuItem::copy(
+        app,
+        Some(&translated_label(
+          CONTEXT_MENU_COPY_ID,
+          CONTEXT_MENU_COPY_LABEL,
+        )),
+      )?),
+      Box::new(PredefinedMenuItem::paste(
+        app,
+        Some(&translated_label(
+          CONTEXT_MENU_PASTE_ID,
+          CONTEXT_MENU_PASTE_LABEL,
+        )),
+      )?),
+      Box::new(PredefinedMenuItem::select_all(
+        app,
+        Some(&translated_label(
+          CONTEXT_MENU_SELECT_ALL_ID,
+          CONTEXT_MENU_SELECT_ALL_LABEL,
+        )),
+      )?),
+    ]);
+  } else if non_empty(&context.selection_text).is_some() {
+    sections.push(vec![Box::new(item(
+      app,
+      CONTEXT_MENU_COPY_ID,
+      CONTEXT_MENU_COPY_LABEL,
+    )?)]);
+  }
+
+  if sections.is_empty() {
+    return Ok(None);
+  }
+
+  let menu = Menu::new(app)?;
+  for (index, section) in sections.iter().enumerate() {
+    if index > 0 {
+      menu.append(&PredefinedMenuItem::separator(app)?)?;
+    }
+    for item in section {
+      menu.append(item.as_ref())?;
+    }
+  }
+
+  Ok(Some(menu))
+}
+
+/// Shows the native context menu for what is under the pointer. Nothing is shown when no item
+/// applies, like for a right click on empty space.
+pub fn show(window: &WebviewWindow, context: ContextMenuContext) -> Result<(), String> {
+  let suggestions = non_empty(&context.misspelled_word).and_then(crate::spelling::suggestions);
+  let Some(menu) =
+    build(window.app_handle(), &context, suggestions.as_deref()).map_err(|err| err.to_string())?
+  else {
+    return Ok(());
+  };
+
+  if let Ok(mut open_menu) = OPEN_CONTEXT_MENU.lock() {
+    *open_menu = Some(OpenContextMenu {
+      window_label: window.label().to_string(),
+      context,
+      suggestions: suggestions.unwrap_or_default(),
+    });
+  }
+
+  window.popup_menu(&menu).map_err(|err| err.to_string())
+}
+
+fn decode_data_url(url: &str) -> Result<Vec<u8>, String> {
+  let (header, data) = url
+    .strip_prefix("data:")
+    .and_then(|rest| rest.split_once(','))
+    .ok_or("Not a data URL")?;
+  if !header.ends_with(";base64") {
+    return Err("Only base64 data URLs are supported".to_string());
+  }
+
+  base64::engine::general_purpose::STANDARD
+    .decode(data)
+    .map_err(|err| err.to_string())
+}
+
+fn copy_image(app: &AppHandle, url: &str) -> Result<(), String> {
+  let bytes = decode_data_url(url)?;
+  let image = image::load_from_memory(&bytes)
+    .map_err(|err| err.to_string())?
+    .to_rgba8();
+  let (width, height) = image.dimensions();
+
+  app
+    .clipboard()
+    .write_image(&Image::new_owned(image.into_raw(), width, height))
+    .map_err(|err| err.to_string())
+}
+
+fn save_image(app: &AppHandle, window_label: &str, url: &str) -> Result<(), String> {
+  let file_name = image_file_name(url);
+  if !url.starts_with("data:") {
+    crate::downloads::save_as(app, window_label, url, &file_name);
+    return Ok(());
+  }
+
+  // Written here rather than downloaded, so the history does not keep the whole image as its URL
+  let bytes = decode_data_url(url)?;
+  app
+    .dialog()
+    .file()
+    .set_file_name(&file_name)
+    .save_file(move |path| {
+      let Some(path) = path.and_then(|path| path.into_path().ok()) else {
+        return;
+      };
+      if let Err(err) = std::fs::write(&path, bytes) {
+        log::error!("Failed to save image to {:?}: {:?}", path, err);
+      }
+    });
+  Ok(())
+}
+
+fn image_file_name(url: &str) -> String {
+  let extension = url
+    .strip_prefix("data:image/")
+    .and_then(|rest| rest.split([';', ',']).next())
+    .map(|subtype| match subtype {
+      "jpeg" => "jpg",
+      "svg+xml" => "svg",
+      subtype => subtype,
+    });
+  if let Some(extension) = extension {
+    return format!("{}.{}", DEFAULT_IMAGE_NAME, extension);
+  }
+
+  url::Url::parse(url)
+    .ok()
+    .and_then(|url| {
+      url
+        .path_segments()?
+        .next_back()
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+    })
+    .unwrap_or_else(|| DEFAULT_IMAGE_NAME.to_string())
+}
+
+fn copy_text(app: &AppHandle, text: &str) -> Result<(), String> {
+  app
+    .clipboard()
+    .write_text(text)
+    .map_err(|err| err.to_string())
+}
+
+// Choices the frontend handles itself, sent with the `context-menu-action` event
+fn emit_action(app: &AppHandle, window_label: &str, payload: serde_json::Value) {
+  app
+    .emit_to(window_label, "context-menu-action", payload)
+    .unwrap_or_default();
+}
+
+/// Runs the action of a chosen context menu item. Other menu events come here too and are ignored.
+pub fn handle(app: &AppHandle, event: &MenuEvent) {
+  let id = event.id().as_ref();
+  if !id.starts_with("context_menu_") {
+    return;
+  }
+
+  let Some(OpenContextMenu {
+    window_label,
+    context,
+    suggestions,
+  }) = OPEN_CONTEXT_MENU
+    .lock()
+    .ok()
+    .and_then(|mut open_menu| open_menu.take())
+  else {
+    return;
+  };
+  let link_url = non_empty(&context.link_url).unwrap_or_default();
+  let image_url = non_empty(&context.image_url).unwrap_or_default();
+
+  let suggestion = id
+    .strip_prefix(CONTEXT_MENU_SUGGESTION_ID_PREFIX)
+    .and_then(|index| index.parse::<usize>().ok())
+    .and_then(|index| suggestions.get(index));
+  if let Some(suggestion) = suggestion {
+    emit_action(
+      app,
+      &window_label,
+      json!({
+        "action": "replaceMisspelling",
+        "word": context.misspelled_word,
+        "replacement": suggestion,
+      }),
+    );
+    return;
+  }
+
+  let result = match id {
+    CONTEXT_MENU_OPEN_LINK_ID => {
+      emit_action(
+        app,
+        &window_label,
+        json!({ "action": "openLink", "url": link_url }),
+      );
+      Ok(())
+    }
+    CONTEXT_MENU_COPY_LINK_ID => copy_text(app, link_url),
+    CONTEXT_MENU_COPY_IMAGE_ID => copy_image(app, image_url),
+    CONTEXT_MENU_COPY_IMAGE_ADDRESS_ID => copy_text(app, image_url),
+    CONTEXT_MENU_SAVE_IMAGE_ID => save_image(app, &window_label, image_url),
+    CONTEXT_MENU_COPY_ID => copy_text(app, context.selection_text.as_deref().unwrap_or_default()),
+    _ => Ok(()),
+  };
+
+  if let Err(err) = result {
+    log::error!("Failed to run context menu action {}: {}", id, err);
+  }
+}
@@ -1,5 +1,5 @@
 use log::info;
-use tauri::{Emitter, Manager, UserAttentionType};
+use tauri::UserAttentionType;
 use tauri_plugin_deep_link::DeepLinkExt;
 
 pub struct Deeplink;
@@ -19,13 +19,31 @@ impl Deeplink {
       let urls = event.urls();
       info!("Deep link received: {:?}", urls);
 
-      // Get the main window
-      if let Some(window) = app_handle.get_webview_window("main") {
-        // Emit the deep link event to the frontend
-        if let Err(err) = window.emit("deeplink", &urls) {
-          info!("Error emitting deeplink event: {:?}", err);
+      // Proxy links change how the shell itself connects, so they are not forwarded
+      let mut forwarded_urls = Vec::new();
+      for url in urls {
+        match crate::proxy::link::parse(&url) {
+          Some(proxy_link) => crate::proxy::link::handle(&app_handle, proxy_link),
+          None => forwarded_urls.push(url),
         }
+      }
+      if forwarded_urls.is_empty() {
+        return;
+      }
+
+      let window = crate::window::primary_window(&app_handle);
+
+      // Emit the deep link events to the frontend, which takes one URL at a time, once it is ready
+      for url in forwarded_urls {
+        crate::window::emit_when_ready(
+          &app_handle,
+          window.as_ref().map(|window| window.label()),
+          "deeplink",
+          serde_json::json!(url.to_string()),
+        );
+      }
 
+      if let Some(window) = window {
         // Request user attention and focus the window
         if let Err(err) = window.request_user_attention(Some(UserAttentionType::Informational)) {
           info!("Error requesting user attention: {:?}", err);
@@ -0,0 +1,313 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tauri::AppHandle;
+use zbus::blocking::Connection;
+use zbus::zvariant::Value;
+
+// GNOME keeps its do-not-disturb switch in GSettings, read through the settings portal
+const GNOME_NOTIFICATIONS_NAMESPACE: &str = "org.gnome.desktop.notifications";
+const GNOME_KEY_SHOW_BANNERS: &str = "show-banners";
+
+// KDE (and other servers following the spec draft) expose do-not-disturb as a property
+#[zbus::proxy(
+  interface = "org.freedesktop.Notifications",
+  default_service = "org.freedesktop.Notifications",
+  default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+  #[zbus(property)]
+  fn inhibited(&self) -> zbus::Result<bool>;
+}
+
+#[zbus::proxy(
+  interface = "org.freedesktop.portal.Settings",
+  default_service = "org.freedesktop.portal.Desktop",
+  default_path = "/org/freedesktop/portal/desktop"
+)]
+trait PortalSettings {
+  fn read_one(&self, namespace: &str, key: &str) -> zbus::Result<zbus::zvariant::OwnedValue>;
+
+  // Older portals only have `Read`, which wraps the value in one more variant
+  fn read(&self, namespace: &str, key: &str) -> zbus::Result<zbus::zvariant::OwnedValue>;
+
+  #[zbus(signal)]
+  fn setting_changed(&self, namespace: &str, key: &str, value: Value<'_>) -> zbus::Result<()>;
+}
+
+/// Combines both desktop sources and reports the result after every change.
+struct Watcher {
+  is_inhibited: AtomicBool,
+  are_banners_hidden: AtomicBool,
+  on_change: Box<dyn Fn(bool) + Send + Sync>,
+}
+
+impl Watcher {
+  fn apply(&self) {
+    (self.on_change)(
+      self.is_inhibited.load(Ordering::Relaxed) || self.are_banners_hidden.load(Ordering::Relaxed),
+    );
+  }
+}
+
+pub fn init(app: &AppHandle) {
+  let connection = match Connection::session() {
+    Ok(connection) => connection,
+    Err(err) => {
+      log::warn!(
+        "Failed to connect to session bus, system do-not-disturb is ignored: {:?}",
+        err
+      );
+      return;
+    }
+  };
+
+  let app = app.clone();
+  watch(connection, move |is_active| {
+    super::set_system_active(&app, is_active)
+  });
+}
+
+/// Starts following both desktop sources on the given connection, which is a private one in tests.
+fn watch(connection: Connection, on_change: impl Fn(bool) + Send + Sync + 'static) {
+  let watcher = Arc::new(Watcher {
+    is_inhibited: AtomicBool::new(false),
+    are_banners_hidden: AtomicBool::new(false),
+    on_change: Box::new(on_change),
+  });
+
+  {
+    let watcher = watcher.clone();
+    let connection = connection.clone();
+    std::thread::spawn(move || {
+      if let Err(err) = watch_gnome(&watcher, &connection) {
+        log::info!("GNOME do-not-disturb is not available: {:?}", err);
+      }
+    });
+  }
+
+  std::thread::spawn(move || {
+    if let Err(err) = watch_inhibited(&watcher, &connection) {
+      log::info!(
+        "Notification server does not report do-not-disturb: {:?}",
+        err
+      );
+    }
+  });
+}
+
+fn watch_inhibited(watcher: &Watcher, connection: &Connection) -> zbus::Result<()> {
+  let proxy = NotificationsProxyBlocking::new(connection)?;
+  let changes = proxy.receive_inhibited_changed();
+  // Servers without the property fail here, leaving the state untouched
+  watcher
+    .is_inhibited
+    .store(proxy.inhibited()?, Ordering::Relaxed);
+  watcher.apply();
+
+  for change in changes {
+    match change.get() {
+      Ok(is_inhibited) => {
+        watcher.is_inhibited.store(is_inhibited, Ordering::Relaxed);
+        watcher.apply();
+      }
+      Err(err) => log::warn!("Failed to read notification inhibition: {:?}", err),
+    }
+  }
+
+  Ok(())
+}
+
+fn watch_gnome(watcher: &Watcher, connection: &Connection) -> zbus::Result<()> {
+  let proxy = PortalSettingsProxyBlocking::new(connection)?;
+  // Subscribe first, so no change slips in between the read and the subscription
+  let changes = proxy.receive_setting_changed()?;
+
+  // Outside GNOME the portal has no such setting, and the state stays untouched
+  let value = proxy
+    .read_one(GNOME_NOTIFICATIONS_NAMESPACE, GNOME_KEY_SHOW_BANNERS)
+    .or_else(|_| proxy.read(GNOME_NOTIFICATIONS_NAMESPACE, GNOME_KEY_SHOW_BANNERS))?;
+  let show_banners = as_bool(&value)
+    .ok_or_else(|| zbus::Error::Failure(format!("Unexpected {} value", GNOME_KEY_SHOW_BANNERS)))?;
+  watcher
+    .are_banners_hidden
+    .store(!show_banners, Ordering::Relaxed);
+  watcher.apply();
+
+  for change in changes {
+    let Ok(args) = change.args() else {
+      continue;
+    };
+    if *args.namespace() != GNOME_NOTIFICATIONS_NAMESPACE || *args.key() != GNOME_KEY_SHOW_BANNERS {
+      continue;
+    }
+
+    if let Some(show_banners) = as_bool(args.value()) {
+      watcher
+        .are_banners_hidden
+        .store(!show_banners, Ordering::Relaxed);
+      watcher.apply();
+    }
+  }
+
+  Ok(())
+}
+
+fn as_bool(value: &Value<'_>) -> Option<bool> {
+  match value {
+    Value::Bool(value) => Some(*value),
+    Value::Value(inner) => as_bool(inner),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::os::unix::net::UnixStream;
+  use std::sync::mpsc::{self, Receiver};
+  use std::time::Duration;
+
+  use zbus::blocking::connection::Builder;
+  use zbus::object_server::SignalEmitter;
+  use zbus::zvariant::OwnedValue;
+
+  use super::*;
+
+  const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+  const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+  const TIMEOUT: Duration = Duration::from_secs(5);
+
+  struct FakeNotifications {
+    is_inhibited: bool,
+  }
+
+  #[zbus::interface(name = "org.freedesktop.Notifications")]
+  impl FakeNotifications {
+    #[zbus(property)]
+    fn inhibited(&self) -> bool {
+      self.is_inhibited
+    }
+  }
+
+  struct FakePortalSettings {
+    show_banners: bool,
+  }
+
+  #[zbus::interface(name = "org.freedesktop.portal.Settings")]
+  impl FakePortalSettings {
+    fn read_one(&self, namespace: &str, key: &str) -> zbus::fdo::Result<OwnedValue> {
+      if namespace != GNOME_NOTIFICATIONS_NAMESPACE || key != GNOME_KEY_SHOW_BANNERS {
+        return Err(zbus::fdo::Error::Failed(
+          "Requested setting not found".to_string(),
+        ));
+      }
+      Ok(OwnedValue::from(self.show_banners))
+    }
+
+    #[zbus(signal)]
+    async fn setting_changed(
+      emitter: &SignalEmitter<'_>,
+      namespace: &str,
+      key: &str,
+      value: Value<'_>,
+    ) -> zbus::Result<()>;
+  }
+
+  /// A server and a client connected to each other directly, without a bus.
+  fn connect(is_inhibited: bool, show_banners: bool) -> (Connection, Connection) {
+    let (server_stream, client_stream) = UnixStream::pair().unwrap();
+    let server = std::thread::spawn(move || {
+      Builder::unix_stream(server_stream)
+        .server(zbus::Guid::generate())?
+        .p2p()
+        .serve_at(NOTIFICATIONS_PATH, FakeNotifications { is_inhibited })?
+        .serve_at(PORTAL_PATH, FakePortalSettings { show_banners })?
+        .build()
+    });
+    let client = Builder::unix_stream(client_stream).p2p().build().unwrap();
+
+    (server.join().unwrap().unwrap(), client)
+  }
+
+  fn set_inhibited(server: &Connection, is_inhibited: bool) {
+    let iface = server
+      .object_server()
+      .interface::<_, FakeNotifications>(NOTIFICATIONS_PATH)
+      .unwrap();
+    iface.get_mut().is_inhibited = is_inhibited;
+    zbus::block_on(iface.get().inhibited_changed(iface.signal_emitter())).unwrap();
+  }
+
+  fn set_show_banners(server: &Connection, namespace: &str, show_banners: bool) {
+    let iface = server
+      .object_server()
+      .interface::<_, FakePortalSettings>(PORTAL_PATH)
+      .unwrap();
+    iface.get_mut().show_banners = show_banners;
+    zbus::block_on(FakePortalSettings::setting_changed(
+      iface.signal_emitter(),
+      namespace,
+      GNOME_KEY_SHOW_BANNERS,
+      Value::from(show_banners),
+    ))
+    .unwrap();
+  }
+
+  /// Skips reports until the expected one, as each source reports its initial state on its own.
+  fn wait_for(receiver: &Receiver<bool>, expected: bool) {
+    loop {
+      let is_active = receiver
+        .recv_timeout(TIMEOUT)
+        .unwrap_or_else(|_| panic!("No report of {}", expected));
+      if is_active == expected {
+        return;
+      }
+    }
+  }
+
+  fn watch_connection(client: Connection) -> Receiver<bool> {
+    let (sender, receiver) = mpsc::channel();
+    watch(client, move |is_active| {
+      sender.send(is_active).ok();
+    });
+    receiver
+  }
+
+  #[test]
+  fn reports_initial_state() {
+    let (_server, client) = connect(false, false);
+    let receiver = watch_connection(client);
+    wait_for(&receiver, true);
+  }
+
+  #[test]
+  fn follows_both_sources() {
+    let (server, client) = connect(false, true);
+    let receiver = watch_connection(client);
+    // Both sources have subscribed once they reported
+    wait_for(&receiver, false);
+    wait_for(&receiver, false);
+
+    set_inhibited(&server, true);
+    wait_for(&receiver, true);
+    set_inhibited(&server, false);
+    wait_for(&receiver, false);
+
+    // Other GSettings namespaces are ignored
+    set_show_banners(&server, "org.gnome.desktop.interface", false);
+    set_show_banners(&server, GNOME_NOTIFICATIONS_NAMESPACE, false);
+    wait_for(&receiver, true);
+    set_show_banners(&server, GNOME_NOTIFICATIONS_NAMESPACE, true);
+    wait_for(&receiver, false);
+  }
+
+  #[test]
+  fn wrapped_values_of_older_portals() {
+    assert_eq!(as_bool(&Value::from(true)), Some(true));
+    assert_eq!(
+      as_bool(&Value::Value(Box::new(Value::from(false)))),
+      Some(false)
+    );
+    assert_eq!(as_bool(&Value::from("true")), None);
+  }
+}
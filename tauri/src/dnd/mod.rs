@@ -0,0 +1,307 @@
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use chrono::{Datelike, Days, Local, TimeZone, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Emitter};
+
+#[cfg(target_os = "linux")]
+mod linux;
+
+const DND_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+// "Until tomorrow" ends at this local hour of the next day
+const DND_TOMORROW_HOUR: u32 = 8;
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuietHours {
+  pub is_enabled: bool,
+  // Minutes since local midnight; `end_minute` below `start_minute` wraps past midnight
+  pub start_minute: u16,
+  pub end_minute: u16,
+  // Days the quiet period starts on, 0 = Monday .. 6 = Sunday
+  pub days: Vec<u8>,
+}
+
+impl QuietHours {
+  pub fn validate(&self) -> Result<(), String> {
+    if self.start_minute >= MINUTES_PER_DAY || self.end_minute >= MINUTES_PER_DAY {
+      return Err(format!(
+        "Quiet hours must start and end within a day, got {} and {}",
+        self.start_minute, self.end_minute
+      ));
+    }
+    if let Some(day) = self.days.iter().find(|day| **day > 6) {
+      return Err(format!("Invalid quiet hours day {}", day));
+    }
+
+    Ok(())
+  }
+
+  fn starts_on(&self, weekday: Weekday) -> bool {
+    self.days.contains(&(weekday.num_days_from_monday() as u8))
+  }
+
+  pub fn is_quiet_at(&self, weekday: Weekday, minute: u16) -> bool {
+    if !self.is_enabled {
+      return false;
+    }
+
+    if self.start_minute == self.end_minute {
+      return self.starts_on(weekday);
+    }
+
+    if self.start_minute < self.end_minute {
+      return self.starts_on(weekday) && minute >= self.start_minute && minute < self.end_minute;
+    }
+
+    // Overnight period: the evening part belongs to today, the morning part to yesterday
+    (self.starts_on(weekday) && minute >= self.start_minute)
+      || (self.starts_on(weekday.pred()) && minute < self.end_minute)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DndConfig {
+  // Unix timestamp in milliseconds
+  pub muted_until: Option<i64>,
+  pub quiet_hours: Option<QuietHours>,
+  // Whether the desktop's own do-not-disturb also suppresses notifications and badges
+  pub should_follow_system: bool,
+}
+
+impl Default for DndConfig {
+  fn default() -> Self {
+    Self {
+      muted_until: None,
+      quiet_hours: None,
+      should_follow_system: true,
+    }
+  }
+}
+
+impl DndConfig {
+  pub fn validate(&self) -> Result<(), String> {
+    match &self.quiet_hours {
+      Some(quiet_hours) => quiet_hours.validate(),
+      None => Ok(()),
+    }
+  }
+
+  pub fn is_active_at<Tz: TimeZone>(&self, now: &chrono::DateTime<Tz>) -> bool {
+    if self
+      .muted_until
+      .is_some_and(|muted_until| muted_until > now.timestamp_millis())
+    {
+      return true;
+    }
+
+    self.quiet_hours.as_ref().is_some_and(|quiet_hours| {
+      quiet_hours.is_quiet_at(now.weekday(), (now.hour() * 60 + now.minute()) as u16)
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MuteDuration {
+  OneHour,
+  EightHours,
+  UntilTomorrow,
+}
+
+impl MuteDuration {
+  fn until(self) -> i64 {
+    let now = Local::now();
+    let until = match self {
+      MuteDuration::OneHour => now + chrono::Duration::hours(1),
+      MuteDuration::EightHours => now + chrono::Duration::hours(8),
+      MuteDuration::UntilTomorrow => now
+        .date_naive()
+        .checked_add_days(Days::new(1))
+        .and_then(|date| date.and_hms_opt(DND_TOMORROW_HOUR, 0, 0))
+        .and_then(|date| date.and_local_timezone(Local).earliest())
+        .unwrap_or(now + chrono::Duration::hours(24)),
+    };
+    until.timestamp_millis()
+  }
+}
+
+struct DndState {
+  is_active: bool,
+  is_system_active: bool,
+}
+
+impl DndState {
+  fn compute_is_active(&self, config: &DndConfig) -> bool {
+    (config.should_follow_system && self.is_system_active) || config.is_active_at(&Local::now())
+  }
+}
+
+static DND_STATE: LazyLock<Mutex<DndState>> = LazyLock::new(|| {
+  Mutex::new(DndState {
+    is_active: false,
+    is_system_active: false,
+  })
+});
+
+pub fn init(app: &AppHandle) {
+  let config = get_config();
+  if let Ok(mut state) = DND_STATE.lock() {
+    state.is_active = state.compute_is_active(&config);
+  }
+
+  #[cfg(target_os = "linux")]
+  linux::init(app);
+
+  // Timed mutes and quiet hours start and end on their own, so re-check periodically
+  let app = app.clone();
+  std::thread::spawn(move || {
+    loop {
+      std::thread::sleep(DND_CHECK_INTERVAL);
+      refresh(&app, false);
+    }
+  });
+}
+
+pub fn is_active() -> bool {
+  DND_STATE
+    .lock()
+    .map(|state| state.is_active)
+    .unwrap_or(false)
+}
+
+fn get_config() -> DndConfig {
+  crate::settings::get().dnd
+}
+
+/// Stores the change, which the settings apply through `apply`.
+fn update_config(app: &AppHandle, update: impl FnOnce(&mut DndConfig)) {
+  crate::settings::modify(app, |settings| update(&mut settings.dnd));
+}
+
+/// Applies changed settings.
+pub fn apply(app: &AppHandle) {
+  refresh(app, true);
+}
+
+pub fn mute(app: &AppHandle, duration: MuteDuration) {
+  update_config(app, |config| config.muted_until = Some(duration.until()));
+}
+
+pub fn unmute(app: &AppHandle) {
+  update_config(app, |config| config.muted_until = None);
+}
+
+pub fn set_quiet_hours(app: &AppHandle, quiet_hours: Option<QuietHours>) -> Result<(), String> {
+  if let Some(quiet_hours) = &quiet_hours {
+    quiet_hours.validate()?;
+  }
+
+  update_config(app, |config| config.quiet_hours = quiet_hours);
+  Ok(())
+}
+
+pub fn set_follow_system(app: &AppHandle, should_follow_system: bool) {
+  update_config(app, |config| {
+    config.should_follow_system = should_follow_system
+  });
+}
+
+/// Records the desktop's do-not-disturb state reported by the platform watcher.
+#[cfg(target_os = "linux")]
+fn set_system_active(app: &AppHandle, is_system_active: bool) {
+  let is_changed = DND_STATE
+    .lock()
+    .map(|mut state| {
+      std::mem::replace(&mut state.is_system_active, is_system_active) != is_system_active
+    })
+    .unwrap_or(false);
+
+  if !is_changed {
+    return;
+  }
+
+  app
+    .emit(
+      "system-dnd-changed",
+      json!({ "isActive": is_system_active }),
+    )
+    .unwrap_or_default();
+  refresh(app, false);
+}
+
+/// Re-evaluates the schedule and applies the result if it changed.
+fn refresh(app: &AppHandle, should_emit: bool) {
+  let config = get_config();
+  let is_changed = DND_STATE
+    .lock()
+    .map(|mut state| {
+      let is_active = state.compute_is_active(&config);
+      std::mem::replace(&mut state.is_active, is_active) != is_active
+    })
+    .unwrap_or(false);
+
+  if is_changed {
+    crate::tray::refresh_notifications_count(app);
+  }
+
+  if is_changed || should_emit {
+    emit_changed(app);
+  }
+}
+
+pub fn get_status() -> serde_json::Value {
+  let config = get_config();
+  let is_system_active = DND_STATE
+    .lock()
+    .map(|state| state.is_system_active)
+    .unwrap_or(false);
+
+  json!({
+    "isActive": is_active(),
+    "mutedUntil": config.muted_until,
+    "quietHours": config.quiet_hours,
+    "isSystemActive": is_system_active,
+    "shouldFollowSystem": config.should_follow_system,
+  })
+}
+
+fn emit_changed(app: &AppHandle) {
+  app.emit("dnd-changed", get_status()).unwrap_or_default();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn quiet_hours(start_minute: u16, end_minute: u16, days: Vec<u8>) -> QuietHours {
+    QuietHours {
+      is_enabled: true,
+      start_minute,
+      end_minute,
+      days,
+    }
+  }
+
+  #[test]
+  fn validate_rejects_out_of_range_values() {
+    assert!(quiet_hours(22 * 60, 7 * 60, vec![0, 6]).validate().is_ok());
+    assert!(quiet_hours(0, 1439, vec![]).validate().is_ok());
+    assert!(quiet_hours(1440, 60, vec![0]).validate().is_err());
+    assert!(quiet_hours(60, 1440, vec![0]).validate().is_err());
+    assert!(quiet_hours(60, 120, vec![7]).validate().is_err());
+  }
+
+  #[test]
+  fn overnight_quiet_hours_belong_to_their_start_day() {
+    let hours = quiet_hours(22 * 60, 7 * 60, vec![4]);
+    assert!(hours.is_quiet_at(Weekday::Fri, 23 * 60));
+    assert!(hours.is_quiet_at(Weekday::Sat, 6 * 60));
+    assert!(!hours.is_quiet_at(Weekday::Sat, 23 * 60));
+    assert!(!hours.is_quiet_at(Weekday::Fri, 6 * 60));
+  }
+}
@@ -0,0 +1,170 @@
+use std::path::Path;
+
+use super::manager::{Download, DownloadState};
+
+pub const HISTORY_FILE: &str = "downloads-history.json";
+const MAX_ENTRIES: usize = 500;
+const MAX_AGE_MS: i64 = 90 * 24 * 60 * 60 * 1000;
+
+/// Reads the stored history. Unreadable files and broken records are skipped, never fatal.
+pub fn load(path: &Path) -> Vec<Download> {
+  let Ok(contents) = std::fs::read_to_string(path) else {
+    return Vec::new();
+  };
+
+  let records: Vec<serde_json::Value> = match serde_json::from_str(&contents) {
+    Ok(records) => records,
+    Err(err) => {
+      log::warn!("Ignoring broken download history: {:?}", err);
+      return Vec::new();
+    }
+  };
+
+  records
+    .into_iter()
+    .filter_map(|record| {
+      serde_json::from_value::<Download>(record)
+        .inspect_err(|err| log::warn!("Skipping broken download record: {:?}", err))
+        .ok()
+    })
+    .map(interrupt)
+    .collect()
+}
+
+/// Downloads still running when the app quit can never finish.
+fn interrupt(mut download: Download) -> Download {
+  if download.ended_at.is_none() {
+    if download.state.is_active() {
+      download.state = DownloadState::Failed;
+    }
+    download.ended_at = Some(download.started_at);
+  }
+  download
+}
+
+pub fn save(path: &Path, downloads: &[Download]) {
+  let result = serde_json::to_string(downloads)
+    .map_err(std::io::Error::other)
+    .and_then(|contents| crate::settings::write_atomically(path, &contents));
+
+  if let Err(err) = result {
+    log::error!("Failed to save download history: {:?}", err);
+  }
+}
+
+/// Drops finished entries older than the age limit, then the oldest ones beyond the count limit.
+/// Running downloads are always kept, as cancelled ones still need their file removed.
+pub fn prune(downloads: &mut Vec<Download>, now: i64) {
+  downloads.retain(|download| {
+    download.is_running() || now - download.ended_at.unwrap_or(now) <= MAX_AGE_MS
+  });
+
+  let mut excess = downloads.len().saturating_sub(MAX_ENTRIES);
+  downloads.retain(|download| {
+    if excess > 0 && !download.is_running() {
+      excess -= 1;
+      return false;
+    }
+    true
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use super::*;
+
+  fn download(id: usize, ended_at: Option<i64>) -> Download {
+    Download {
+      id: id.to_string(),
+      url: format!("https://example.com/{}", id),
+      window_label: "main".to_string(),
+      file_name: format!("{}.zip", id),
+      destination: PathBuf::from(format!("/tmp/{}.zip", id)),
+      state: if ended_at.is_some() {
+        DownloadState::Finished
+      } else {
+        DownloadState::InProgress
+      },
+      received_bytes: 0,
+      size: None,
+      started_at: 1,
+      ended_at,
+      risk: None,
+      sha256: None,
+      is_missing: false,
+      can_retry: false,
+    }
+  }
+
+  fn ids(downloads: &[Download]) -> Vec<&str> {
+    downloads
+      .iter()
+      .map(|download| download.id.as_str())
+      .collect()
+  }
+
+  struct TempFile(PathBuf);
+
+  impl TempFile {
+    fn new(contents: &str) -> Self {
+      let path = std::env::temp_dir().join(format!("history-test-{}.json", uuid::Uuid::new_v4()));
+      std::fs::write(&path, contents).unwrap();
+      TempFile(path)
+    }
+  }
+
+  impl Drop for TempFile {
+    fn drop(&mut self) {
+      std::fs::remove_file(&self.0).unwrap_or_default();
+    }
+  }
+
+  #[test]
+  fn load_skips_broken_records() {
+    let records = serde_json::json!([
+      download(1, Some(2)),
+      { "id": "2", "state": "unknown" },
+      42,
+      download(3, None),
+    ]);
+    let file = TempFile::new(&records.to_string());
+
+    let downloads = load(&file.0);
+    assert_eq!(ids(&downloads), ["1", "3"]);
+    // Running when the app quit
+    assert_eq!(downloads[1].state, DownloadState::Failed);
+    assert_eq!(downloads[1].ended_at, Some(1));
+  }
+
+  #[test]
+  fn load_ignores_unreadable_files() {
+    let file = TempFile::new("{ not json");
+    assert!(load(&file.0).is_empty());
+    assert!(load(&file.0.with_extension("missing")).is_empty());
+  }
+
+  #[test]
+  fn prune_drops_entries_past_the_age_limit() {
+    let now = MAX_AGE_MS + 10;
+    let mut downloads = vec![
+      download(1, Some(9)),
+      download(2, Some(10)),
+      download(3, None),
+    ];
+
+    prune(&mut downloads, now);
+    assert_eq!(ids(&downloads), ["2", "3"]);
+  }
+
+  #[test]
+  fn prune_drops_the_oldest_entries_beyond_the_count_limit() {
+    let mut downloads = vec![download(0, None)];
+    downloads.extend((1..=MAX_ENTRIES + 1).map(|id| download(id, Some(id as i64))));
+
+    prune(&mut downloads, MAX_ENTRIES as i64);
+    assert_eq!(downloads.len(), MAX_ENTRIES);
+    assert_eq!(ids(&downloads[..2]), ["0", "3"]);
+  }
+}
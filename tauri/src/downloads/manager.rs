@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::risk::DownloadRisk;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DownloadState {
+  Requested,
+  InProgress,
+  Finished,
+  Failed,
+  // The webview offers no way to abort a transfer, so it keeps running and its file is removed
+  // once it ends
+  #[serde(alias = "discarded")]
+  Cancelled,
+}
+
+impl DownloadState {
+  pub fn is_active(self) -> bool {
+    matches!(self, DownloadState::Requested | DownloadState::InProgress)
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Download {
+  pub id: String,
+  pub url: String,
+  pub window_label: String,
+  pub file_name: String,
+  pub destination: PathBuf,
+  pub state: DownloadState,
+  pub received_bytes: u64,
+  // Final size, known once the download has finished
+  pub size: Option<u64>,
+  // Unix timestamps in milliseconds
+  pub started_at: i64,
+  pub ended_at: Option<i64>,
+  // Known once a finished file has been inspected
+  pub risk: Option<DownloadRisk>,
+  pub sha256: Option<String>,
+  // The file of a finished download was deleted or moved since
+  #[serde(default)]
+  pub is_missing: bool,
+  // Only sources that outlive the page can be requested again, `blob:` URLs are revoked
+  #[serde(default)]
+  pub can_retry: bool,
+}
+
+impl Download {
+  /// Whether the webview is still writing the file, cancelled downloads included.
+  pub fn is_running(&self) -> bool {
+    self.ended_at.is_none()
+  }
+}
+
+/// Keeps every known download, oldest first.
+#[derive(Debug, Default)]
+pub struct DownloadManager {
+  downloads: Vec<Download>,
+  // Retried downloads reuse their id once the webview requests the URL again
+  pending_retries: HashMap<String, String>,
+  // Destinations picked in the save dialog, applied when the webview requests the URL again
+  chosen_destinations: HashMap<String, PathBuf>,
+}
+
+impl DownloadManager {
+  pub fn list(&self) -> Vec<Download> {
+    self.downloads.clone()
+  }
+
+  /// Puts downloads from previous sessions before the ones started in this session.
+  pub fn restore(&mut self, history: Vec<Download>) {
+    let current = std::mem::replace(&mut self.downloads, history);
+    self.downloads.extend(current);
+  }
+
+  pub fn prune(&mut self, now: i64) {
+    super::history::prune(&mut self.downloads, now);
+  }
+
+  /// Forgets every download that is no longer writing to its destination.
+  pub fn clear_history(&mut self) {
+    self.downloads.retain(Download::is_running);
+  }
+
+  pub fn get(&self, id: &str) -> Option<&Download> {
+    self.downloads.iter().find(|download| download.id == id)
+  }
+
+  pub fn get_mut(&mut self, id: &str) -> Option<&mut Download> {
+    self.downloads.iter_mut().find(|download| download.id == id)
+  }
+
+  pub fn set_chosen_destination(&mut self, url: &str, destination: PathBuf) {
+    self
+      .chosen_destinations
+      .insert(url.to_string(), destination);
+  }
+
+  pub fn take_chosen_destination(&mut self, url: &str) -> Option<PathBuf> {
+    self.chosen_destinations.remove(url)
+  }
+
+  pub fn has_active(&self) -> bool {
+    self.downloads.iter().any(Download::is_running)
+  }
+
+  /// Whether an unfinished download is already writing to the path.
+  pub fn is_destination_active(&self, path: &Path) -> bool {
+    self
+      .downloads
+      .iter()
+      .any(|download| download.is_running() && download.destination == path)
+  }
+
+  /// Starts tracking a download, reusing the id of a pending retry for the same URL.
+  pub fn start(
+    &mut self,
+    url: &str,
+    window_label: &str,
+    destination: PathBuf,
+    now: i64,
+  ) -> Download {
+    let file_name = destination
+      .file_name()
+      .map(|name| name.to_string_lossy().into_owned())
+      .unwrap_or_default();
+
+    if let Some(id) = self.pending_retries.remove(url)
+      && let Some(download) = self.get_mut(&id)
+    {
+      download.window_label = window_label.to_string();
+      download.file_name = file_name;
+      download.destination = destination;
+      download.state = DownloadState::Requested;
+      download.received_bytes = 0;
+      download.size = None;
+      download.started_at = now;
+      download.ended_at = None;
+      download.risk = None;
+      download.sha256 = None;
+      download.can_retry = is_retryable(url);
+      return download.clone();
+    }
+
+    let download = Download {
+      id: uuid::Uuid::new_v4().to_string(),
+      url: url.to_string(),
+      window_label: window_label.to_string(),
+      file_name,
+      destination,
+      state: DownloadState::Requested,
+      received_bytes: 0,
+      size: None,
+      started_at: now,
+      ended_at: None,
+      risk: None,
+      sha256: None,
+      is_missing: false,
+      can_retry: is_retryable(url),
+    };
+    self.downloads.push(download.clone());
+    download
+  }
+
+  /// Records bytes written so far; returns the updated download if anything changed.
+  pub fn update_progress(&mut self, id: &str, received_bytes: u64) -> Option<Download> {
+    let download = self.get_mut(id)?;
+    if !download.state.is_active() {
+      return None;
+    }
+
+    let is_changed =
+      download.state != DownloadState::InProgress || download.received_bytes != received_bytes;
+    download.state = DownloadState::InProgress;
+    download.received_bytes = received_bytes;
+
+    is_changed.then(|| download.clone())
+  }
+
+  /// Resolves the webview's completion report to the oldest matching download still tracked.
+  /// Cancelled downloads keep their state, so the caller can remove the written file.
+  pub fn finish(
+    &mut self,
+    url: &str,
+    path: Option<PathBuf>,
+    success: bool,
+    size: Option<u64>,
+    now: i64,
+  ) -> Option<Download> {
+    let download = self
+      .downloads
+      .iter_mut()
+      .find(|download| download.url == url && download.is_running())?;
+
+    if let Some(path) = path {
+      download.destination = path;
+    }
+    download.ended_at = Some(now);
+
+    if download.state != DownloadState::Cancelled {
+      download.state = if success {
+        DownloadState::Finished
+      } else {
+        DownloadState::Failed
+      };
+      if success {
+        download.size = size;
+        download.received_bytes = size.unwrap_or(download.received_bytes);
+      }
+    }
+
+    Some(download.clone())
+  }
+
+  pub fn set_inspection(
+    &mut self,
+    id: &str,
+    risk: DownloadRisk,
+    sha256: Option<String>,
+  ) -> Option<Download> {
+    let download = self.get_mut(id)?;
+    download.risk = Some(risk);
+    download.sha256 = sha256;
+    Some(download.clone())
+  }
+
+  /// Cancels an active download. The webview offers no way to abort it, so it runs to the end
+  /// and only its file is removed.
+  pub fn cancel(&mut self, id: &str) -> Result<Download, String> {
+    let download = self
+      .get_mut(id)
+      .ok_or_else(|| format!("Unknown download {}", id))?;
+    if !download.state.is_active() {
+      return Err(format!("Download {} is not in progress", id));
+    }
+
+    download.state = DownloadState::Cancelled;
+    Ok(download.clone())
+  }
+
+  pub fn prepare_retry(&mut self, id: &str) -> Result<Download, String> {
+    let download = self
+      .get(id)
+      .ok_or_else(|| format!("Unknown download {}", id))?
+      .clone();
+    if !matches!(
+      download.state,
+      DownloadState::Failed | DownloadState::Cancelled
+    ) || download.ended_at.is_none()
+      || !download.can_retry
+    {
+      return Err(format!("Download {} cannot be retried", id));
+    }
+
+    self
+      .pending_retries
+      .insert(download.url.clone(), download.id.clone());
+    Ok(download)
+  }
+}
+
+/// Whether requesting the URL again from the page gets the same file.
+fn is_retryable(url: &str) -> bool {
+  let Ok(url) = url::Url::parse(url) else {
+    return false;
+  };
+  // Cross-origin links navigate away instead of downloading once the page clicks them
+  url.scheme() == "data" || crate::navigation::is_app_url(&url)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const DATA_URL: &str = "data:text/plain,hello";
+  const FOREIGN_URL: &str = "https://example.com/file.zip";
+  const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+  fn start(manager: &mut DownloadManager, url: &str, now: i64) -> Download {
+    manager.start(url, "main", PathBuf::from("/tmp/file.zip"), now)
+  }
+
+  #[test]
+  fn tracks_a_download_to_the_end() {
+    let mut manager = DownloadManager::default();
+    let download = start(&mut manager, FOREIGN_URL, 1);
+    assert_eq!(download.state, DownloadState::Requested);
+    assert!(download.is_running());
+
+    let download = manager.update_progress(&download.id, 10).unwrap();
+    assert_eq!(download.state, DownloadState::InProgress);
+    assert!(manager.update_progress(&download.id, 10).is_none());
+
+    let download = manager
+      .finish(FOREIGN_URL, None, true, Some(20), 2)
+      .unwrap();
+    assert_eq!(download.state, DownloadState::Finished);
+    assert_eq!(download.received_bytes, 20);
+    assert_eq!(download.ended_at, Some(2));
+    assert!(!manager.has_active());
+    assert!(manager.update_progress(&download.id, 30).is_none());
+    assert!(manager.finish(FOREIGN_URL, None, true, None, 3).is_none());
+  }
+
+  #[test]
+  fn records_failures() {
+    let mut manager = DownloadManager::default();
+    start(&mut manager, FOREIGN_URL, 1);
+
+    let download = manager.finish(FOREIGN_URL, None, false, None, 2).unwrap();
+    assert_eq!(download.state, DownloadState::Failed);
+    assert_eq!(download.size, None);
+  }
+
+  #[test]
+  fn keeps_cancelled_downloads_cancelled_until_they_end() {
+    let mut manager = DownloadManager::default();
+    let download = start(&mut manager, FOREIGN_URL, 1);
+
+    let download = manager.cancel(&download.id).unwrap();
+    assert_eq!(download.state, DownloadState::Cancelled);
+    assert!(download.is_running());
+    assert!(manager.has_active());
+    assert!(manager.update_progress(&download.id, 10).is_none());
+    assert!(manager.cancel(&download.id).is_err());
+
+    let download = manager
+      .finish(FOREIGN_URL, None, true, Some(20), 2)
+      .unwrap();
+    assert_eq!(download.state, DownloadState::Cancelled);
+    assert_eq!(download.ended_at, Some(2));
+    assert!(!manager.has_active());
+  }
+
+  #[test]
+  fn retries_only_ended_downloads_of_lasting_sources() {
+    let mut manager = DownloadManager::default();
+    let download = start(&mut manager, DATA_URL, 1);
+    assert!(manager.prepare_retry(&download.id).is_err());
+
+    manager.finish(DATA_URL, None, false, None, 2);
+    manager.prepare_retry(&download.id).unwrap();
+    let retried = start(&mut manager, DATA_URL, 3);
+    assert_eq!(retried.id, download.id);
+    assert_eq!(retried.state, DownloadState::Requested);
+    assert_eq!(retried.ended_at, None);
+    assert_eq!(manager.list().len(), 1);
+
+    let foreign = start(&mut manager, FOREIGN_URL, 4);
+    manager.finish(FOREIGN_URL, None, false, None, 5);
+    assert!(manager.prepare_retry(&foreign.id).is_err());
+  }
+
+  #[test]
+  fn pruning_keeps_running_downloads() {
+    let mut manager = DownloadManager::default();
+    let old = start(&mut manager, FOREIGN_URL, 0);
+    manager.finish(FOREIGN_URL, None, true, None, 0);
+    let cancelled = start(&mut manager, DATA_URL, 0);
+    manager.cancel(&cancelled.id).unwrap();
+
+    manager.prune(365 * DAY_MS);
+    assert!(manager.get(&old.id).is_none());
+    assert!(manager.get(&cancelled.id).is_some());
+
+    manager.clear_history();
+    assert!(manager.get(&cancelled.id).is_some());
+  }
+}
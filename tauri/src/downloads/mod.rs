@@ -0,0 +1,581 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, OnceLock};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::tray::translated_label;
+
+mod history;
+
+mod manager;
+pub use manager::Download;
+use manager::{DownloadManager, DownloadState};
+
+mod policy;
+
+mod risk;
+use risk::DownloadRisk;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+// Engines write into a temporary file next to the destination until the download completes
+const PARTIAL_FILE_EXTENSIONS: &[&str] = &["crdownload", "wkdownload", "download", "part"];
+
+// Label constants, translatable through `set_menu_translations`
+const OPEN_SUSPICIOUS_TITLE_ID: &str = "download_open_suspicious_title";
+const OPEN_SUSPICIOUS_TITLE_LABEL: &str = "Open File";
+const OPEN_SUSPICIOUS_MESSAGE_ID: &str = "download_open_suspicious";
+const OPEN_SUSPICIOUS_MESSAGE_LABEL: &str =
+  "Files of this type can run scripts or macros. Open it only if you trust where it came from.";
+const OPEN_SUSPICIOUS_OPEN_ID: &str = "download_open_suspicious_open";
+const OPEN_SUSPICIOUS_OPEN_LABEL: &str = "Open";
+const OPEN_SUSPICIOUS_CANCEL_ID: &str = "download_open_suspicious_cancel";
+const OPEN_SUSPICIOUS_CANCEL_LABEL: &str = "Cancel";
+
+static DOWNLOAD_MANAGER: LazyLock<Mutex<DownloadManager>> =
+  LazyLock::new(|| Mutex::new(DownloadManager::default()));
+
+static HISTORY_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DownloadSettings {
+  // `None` uses the system downloads directory
+  pub directory: Option<PathBuf>,
+  pub should_ask_where_to_save: bool,
+}
+
+fn now() -> i64 {
+  chrono::Utc::now().timestamp_millis()
+}
+
+pub fn init(app: &AppHandle) {
+  if let Ok(history_path) = app
+    .path()
+    .app_data_dir()
+    .map(|dir| dir.join(history::HISTORY_FILE))
+  {
+    let history = history::load(&history_path);
+    HISTORY_PATH.set(history_path).unwrap_or_default();
+
+    if let Ok(mut manager) = DOWNLOAD_MANAGER.lock() {
+      manager.restore(history);
+      save_history(&mut manager);
+    }
+  }
+}
+
+fn save_history(manager: &mut DownloadManager) {
+  let Some(path) = HISTORY_PATH.get() else {
+    return;
+  };
+
+  manager.prune(now());
+  history::save(path, &manager.list());
+}
+
+pub fn get_settings() -> DownloadSettings {
+  crate::settings::get().downloads
+}
+
+pub fn set_settings(app: &AppHandle, settings: DownloadSettings) {
+  crate::settings::modify(app, |current| current.downloads = settings);
+}
+
+/// Lets the user pick the target directory with a native dialog and stores it.
+/// Blocks until the dialog closes, so it must not run on the main thread.
+pub fn choose_directory(app: &AppHandle) -> DownloadSettings {
+  let mut settings = get_settings();
+
+  let mut dialog = app.dialog().file();
+  if let Some(directory) = target_directory(app, &settings) {
+    dialog = dialog.set_directory(directory);
+  }
+
+  if let Some(directory) = dialog
+    .blocking_pick_folder()
+    .and_then(|path| path.into_path().ok())
+  {
+    settings.directory = Some(directory);
+    set_settings(app, settings.clone());
+  }
+
+  settings
+}
+
+fn target_directory(app: &AppHandle, settings: &DownloadSettings) -> Option<PathBuf> {
+  settings
+    .directory
+    .clone()
+    .or_else(|| app.path().download_dir().ok())
+}
+
+fn suggested_file_name(url: &str, destination: &Path) -> String {
+  destination
+    .file_name()
+    .map(|name| name.to_string_lossy().into_owned())
+    .or_else(|| {
+      url::Url::parse(url)
+        .ok()?
+        .path_segments()?
+        .next_back()
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+    })
+    .unwrap_or_default()
+}
+
+fn is_path_taken(manager: &DownloadManager, path: &Path) -> bool {
+  path.exists()
+    || partial_paths(path).any(|partial| partial.exists())
+    || manager.is_destination_active(path)
+}
+
+/// Handles `DownloadEvent::Requested`. Returns whether the webview should proceed.
+pub fn handle_requested(window: &tauri::Webview, url: &str, destination: &mut PathBuf) -> bool {
+  let app = window.app_handle();
+  let settings = get_settings();
+  let file_name = suggested_file_name(url, destination);
+
+  let Ok(mut manager) = DOWNLOAD_MANAGER.lock() else {
+    return true;
+  };
+
+  match manager.take_chosen_destination(url) {
+    Some(chosen) => *destination = chosen,
+    None if settings.should_ask_where_to_save => {
+      drop(manager);
+      // The engine would block on its own dialog, so stop here and restart once a path is chosen
+      ask_destination(app, window.label(), url, &file_name, &settings);
+      return false;
+    }
+    None => {
+      // Engines disagree on (or lack) a default, so every platform goes through the same policy
+      let Some(directory) =
+        target_directory(app, &settings).or_else(|| destination.parent().map(Path::to_path_buf))
+      else {
+        return true;
+      };
+      if let Err(err) = std::fs::create_dir_all(&directory) {
+        log::warn!(
+          "Failed to create download directory {:?}: {:?}",
+          directory,
+          err
+        );
+      }
+      *destination =
+        policy::resolve_destination(&directory, &file_name, |path| is_path_taken(&manager, path));
+    }
+  }
+
+  let download = manager.start(url, window.label(), destination.clone(), now());
+  save_history(&mut manager);
+  drop(manager);
+
+  emit_progress(window.app_handle(), &download);
+  watch_progress(window.app_handle().clone(), download.id);
+
+  true
+}
+
+/// Handles `DownloadEvent::Finished`.
+pub fn handle_finished(window: &tauri::Webview, url: &str, path: Option<PathBuf>, success: bool) {
+  let size = path
+    .as_deref()
+    .and_then(|path| std::fs::metadata(path).ok())
+    .map(|metadata| metadata.len());
+
+  let download = DOWNLOAD_MANAGER.lock().ok().and_then(|mut manager| {
+    let download = manager.finish(url, path, success, size, now());
+    save_history(&mut manager);
+    download
+  });
+
+  let Some(download) = download else {
+    // Not tracked, still report the outcome to the window that started it
+    window
+      .emit_to(
+        window.label(),
+        "download-finished",
+        json!({
+          "url": url,
+          "success": success,
+        }),
+      )
+      .unwrap_or_default();
+    return;
+  };
+
+  match download.state {
+    DownloadState::Cancelled => {
+      // The webview offers no way to abort a transfer, so drop whatever it wrote
+      remove_download_files(&download.destination);
+    }
+    DownloadState::Finished => {
+      // Hashing may take a while for large files, so keep it off the webview's thread
+      let app = window.app_handle().clone();
+      std::thread::spawn(move || {
+        let download = inspect_download(download);
+        emit_finished(&app, &download);
+      });
+      return;
+    }
+    _ => {}
+  }
+
+  emit_finished(window.app_handle(), &download);
+}
+
+fn emit_finished(app: &AppHandle, download: &Download) {
+  emit_progress(app, download);
+
+  let mut payload = serde_json::to_value(download).unwrap_or_default();
+  payload["success"] = json!(download.state == DownloadState::Finished);
+  app
+    .emit_to(&download.window_label, "download-finished", payload)
+    .unwrap_or_default();
+}
+
+/// Classifies a finished file, records its SHA-256 and makes sure it cannot be executed directly.
+fn inspect_download(download: Download) -> Download {
+  let path = &download.destination;
+
+  let header = read_header(path).unwrap_or_else(|err| {
+    log::warn!("Failed to read download {:?}: {:?}", path, err);
+    Vec::new()
+  });
+  let risk = risk::classify(&download.file_name, &header);
+
+  let sha256 = compute_sha256(path)
+    .inspect_err(|err| log::warn!("Failed to hash download {:?}: {:?}", path, err))
+    .ok();
+
+  // Nothing downloaded needs to be executable, and a launcher must never run on a double click
+  if let Err(err) = strip_executable_bits(path) {
+    log::warn!("Failed to strip executable bits of {:?}: {:?}", path, err);
+  }
+
+  if risk != DownloadRisk::Safe {
+    log::info!("Download {:?} classified as {:?}", path, risk);
+  }
+
+  DOWNLOAD_MANAGER
+    .lock()
+    .ok()
+    .and_then(|mut manager| {
+      let inspected = manager.set_inspection(&download.id, risk, sha256.clone());
+      save_history(&mut manager);
+      inspected
+    })
+    .unwrap_or(Download {
+      risk: Some(risk),
+      sha256,
+      ..download
+    })
+}
+
+fn read_header(path: &Path) -> std::io::Result<Vec<u8>> {
+  let mut header = Vec::with_capacity(risk::SNIFF_BYTES);
+  std::fs::File::open(path)?
+    .take(risk::SNIFF_BYTES as u64)
+    .read_to_end(&mut header)?;
+  Ok(header)
+}
+
+fn compute_sha256(path: &Path) -> std::io::Result<String> {
+  let mut hasher = Sha256::new();
+  std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+  Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(unix)]
+fn strip_executable_bits(path: &Path) -> std::io::Result<()> {
+  use std::os::unix::fs::PermissionsExt;
+
+  let mut permissions = std::fs::metadata(path)?.permissions();
+  let mode = permissions.mode();
+  if mode & 0o111 != 0 {
+    permissions.set_mode(mode & !0o111);
+    std::fs::set_permissions(path, permissions)?;
+  }
+  Ok(())
+}
+
+#[cfg(not(unix))]
+fn strip_executable_bits(_path: &Path) -> std::io::Result<()> {
+  // Windows decides by extension, and the engine already marks the file as downloaded
+  Ok(())
+}
+
+pub fn list_downloads() -> Vec<Download> {
+  let mut downloads = DOWNLOAD_MANAGER
+    .lock()
+    .map(|manager| manager.list())
+    .unwrap_or_default();
+
+  // Checked on every call, as files are moved and deleted outside the app
+  for download in &mut downloads {
+    download.is_missing =
+      download.state == DownloadState::Finished && !download.destination.exists();
+  }
+
+  downloads
+}
+
+pub fn has_active_downloads() -> bool {
+  DOWNLOAD_MANAGER
+    .lock()
+    .map(|manager| manager.has_active())
+    .unwrap_or_default()
+}
+
+pub fn clear_download_history() -> Result<(), String> {
+  let mut manager = DOWNLOAD_MANAGER.lock().map_err(|err| err.to_string())?;
+  manager.clear_history();
+  save_history(&mut manager);
+  Ok(())
+}
+
+/// Path of a finished download, as recorded by the manager. Paths from the webview are never used,
+/// so these commands cannot be turned into opening arbitrary files.
+fn finished_path(id: &str) -> Result<PathBuf, String> {
+  let manager = DOWNLOAD_MANAGER.lock().map_err(|err| err.to_string())?;
+  let download = manager
+    .get(id)
+    .ok_or_else(|| format!("Unknown download {}", id))?;
+  if download.state != DownloadState::Finished {
+    return Err(format!("Download {} is not finished", id));
+  }
+  if !download.destination.exists() {
+    return Err(format!("Download {} no longer exists", id));
+  }
+
+  Ok(download.destination.clone())
+}
+
+fn open_path(app: &AppHandle, path: &Path) -> Result<(), String> {
+  app
+    .opener()
+    .open_path(path.to_string_lossy(), None::<&str>)
+    .map_err(|err| err.to_string())
+}
+
+fn confirm_and_open(app: &AppHandle, path: PathBuf) {
+  let file_name = path
+    .file_name()
+    .map(|name| name.to_string_lossy().into_owned())
+    .unwrap_or_default();
+
+  let app_handle = app.clone();
+  app
+    .dialog()
+    .message(format!(
+      "{}\n\n{}",
+      file_name,
+      translated_label(OPEN_SUSPICIOUS_MESSAGE_ID, OPEN_SUSPICIOUS_MESSAGE_LABEL)
+    ))
+    .title(translated_label(
+      OPEN_SUSPICIOUS_TITLE_ID,
+      OPEN_SUSPICIOUS_TITLE_LABEL,
+    ))
+    .kind(MessageDialogKind::Warning)
+    .buttons(MessageDialogButtons::OkCancelCustom(
+      translated_label(OPEN_SUSPICIOUS_OPEN_ID, OPEN_SUSPICIOUS_OPEN_LABEL),
+      translated_label(OPEN_SUSPICIOUS_CANCEL_ID, OPEN_SUSPICIOUS_CANCEL_LABEL),
+    ))
+    .show(move |is_confirmed| {
+      if !is_confirmed {
+        return;
+      }
+      if let Err(err) = open_path(&app_handle, &path) {
+        log::error!("Failed to open {:?}: {}", path, err);
+      }
+    });
+}
+
+/// Opens a finished download with its default application. Files that may carry active content
+/// are confirmed first, while dangerous ones are only ever revealed, so the user has to launch
+/// them deliberately.
+pub fn open_download(app: &AppHandle, id: &str) -> Result<(), String> {
+  let path = finished_path(id)?;
+
+  let risk = DOWNLOAD_MANAGER
+    .lock()
+    .ok()
+    .and_then(|manager| manager.get(id).and_then(|download| download.risk));
+  match risk {
+    Some(DownloadRisk::Safe) => open_path(app, &path),
+    Some(DownloadRisk::Suspicious) => {
+      confirm_and_open(app, path);
+      Ok(())
+    }
+    Some(DownloadRisk::Dangerous) => Err(format!("Download {} is dangerous", id)),
+    None => Err(format!("Download {} is still being checked", id)),
+  }
+}
+
+pub fn reveal_download(app: &AppHandle, id: &str) -> Result<(), String> {
+  app
+    .opener()
+    .reveal_item_in_dir(finished_path(id)?)
+    .map_err(|err| err.to_string())
+}
+
+/// Marks an active download as cancelled. The webview cannot abort a transfer, so it still runs
+/// to the end, then whatever it wrote is removed.
+pub fn cancel_download(app: &AppHandle, id: &str) -> Result<(), String> {
+  let download = {
+    let mut manager = DOWNLOAD_MANAGER.lock().map_err(|err| err.to_string())?;
+    let download = manager.cancel(id)?;
+    save_history(&mut manager);
+    download
+  };
+
+  emit_progress(app, &download);
+  Ok(())
+}
+
+pub fn retry_download(app: &AppHandle, id: &str) -> Result<(), String> {
+  let download = DOWNLOAD_MANAGER
+    .lock()
+    .map_err(|err| err.to_string())?
+    .prepare_retry(id)?;
+
+  trigger_download(
+    app,
+    &download.window_label,
+    &download.url,
+    &download.file_name,
+  )
+}
+
+/// Asks where to save a file of the page, then downloads it there like the page would.
+pub fn save_as(app: &AppHandle, window_label: &str, url: &str, file_name: &str) {
+  ask_destination(app, window_label, url, file_name, &get_settings());
+}
+
+/// Starts a download in the page the same way the page does, through a temporary link.
+fn trigger_download(
+  app: &AppHandle,
+  window_label: &str,
+  url: &str,
+  file_name: &str,
+) -> Result<(), String> {
+  // Prefer the window that started the download, as only it may still hold a `blob:` URL
+  let window = app
+    .get_webview_window(window_label)
+    .or_else(|| app.webview_windows().into_values().next())
+    .ok_or("No window to download in")?;
+
+  let script = format!(
+    "(() => {{ const a = document.createElement('a'); a.href = {}; a.download = {}; \
+     document.body.appendChild(a); a.click(); a.remove(); }})();",
+    serde_json::to_string(url).map_err(|err| err.to_string())?,
+    serde_json::to_string(file_name).map_err(|err| err.to_string())?,
+  );
+
+  window.eval(&script).map_err(|err| err.to_string())
+}
+
+fn ask_destination(
+  app: &AppHandle,
+  window_label: &str,
+  url: &str,
+  file_name: &str,
+  settings: &DownloadSettings,
+) {
+  let file_name = policy::sanitize_file_name(file_name);
+  let mut dialog = app.dialog().file().set_file_name(&file_name);
+  if let Some(directory) = target_directory(app, settings) {
+    dialog = dialog.set_directory(directory);
+  }
+
+  let app = app.clone();
+  let window_label = window_label.to_string();
+  let url = url.to_string();
+  dialog.save_file(move |path| {
+    let Some(path) = path.and_then(|path| path.into_path().ok()) else {
+      return;
+    };
+
+    if let Ok(mut manager) = DOWNLOAD_MANAGER.lock() {
+      manager.set_chosen_destination(&url, path);
+    }
+
+    if let Err(err) = trigger_download(&app, &window_label, &url, &file_name) {
+      log::error!(
+        "Failed to restart download after choosing destination: {}",
+        err
+      );
+    }
+  });
+}
+
+fn emit_progress(app: &AppHandle, download: &Download) {
+  app.emit("download-progress", download).unwrap_or_default();
+}
+
+fn partial_paths(destination: &Path) -> impl Iterator<Item = PathBuf> + '_ {
+  PARTIAL_FILE_EXTENSIONS.iter().map(move |extension| {
+    let mut path = destination.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+  })
+}
+
+/// Bytes written so far, read from the destination or the engine's temporary file.
+fn received_bytes(destination: &Path) -> Option<u64> {
+  std::iter::once(destination.to_path_buf())
+    .chain(partial_paths(destination))
+    .filter_map(|path| std::fs::metadata(path).ok())
+    .map(|metadata| metadata.len())
+    .max()
+}
+
+fn remove_download_files(destination: &Path) {
+  for path in std::iter::once(destination.to_path_buf()).chain(partial_paths(destination)) {
+    if path.exists() {
+      if let Err(err) = std::fs::remove_file(&path) {
+        log::warn!("Failed to remove cancelled download {:?}: {:?}", path, err);
+      }
+    }
+  }
+}
+
+/// Reports progress while the download is active. Webviews do not expose transfer progress,
+/// so it is derived from the size of the file being written.
+fn watch_progress(app: AppHandle, id: String) {
+  std::thread::spawn(move || {
+    loop {
+      std::thread::sleep(PROGRESS_INTERVAL);
+
+      let Some(destination) = DOWNLOAD_MANAGER.lock().ok().and_then(|manager| {
+        manager
+          .get(&id)
+          .filter(|download| download.state.is_active())
+          .map(|download| download.destination.clone())
+      }) else {
+        return;
+      };
+
+      let Some(received_bytes) = received_bytes(&destination) else {
+        continue;
+      };
+
+      let download = DOWNLOAD_MANAGER
+        .lock()
+        .ok()
+        .and_then(|mut manager| manager.update_progress(&id, received_bytes));
+      if let Some(download) = download {
+        emit_progress(&app, &download);
+      }
+    }
+  });
+}
@@ -0,0 +1,184 @@
+use std::path::{Path, PathBuf};
+
+const FALLBACK_FILE_NAME: &str = "download";
+// Most file systems limit a single path component to 255 bytes
+const MAX_FILE_NAME_BYTES: usize = 255;
+// Leaves room for the " (N)" suffix added when de-duplicating
+const DEDUPLICATION_RESERVE_BYTES: usize = 8;
+
+const FORBIDDEN_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+// Device names Windows refuses as file names, with or without an extension
+const RESERVED_NAMES: &[&str] = &[
+  "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+  "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Turns a server-provided name into a single safe path component, the same on every platform.
+pub fn sanitize_file_name(name: &str) -> String {
+  let replaced: String = name
+    .chars()
+    .map(|c| {
+      if c.is_control() || FORBIDDEN_CHARS.contains(&c) {
+        '_'
+      } else {
+        c
+      }
+    })
+    .collect();
+
+  // Windows drops trailing dots and spaces; leading dots would hide the file elsewhere
+  let trimmed = replaced.trim_matches(|c: char| c.is_whitespace() || c == '.');
+
+  if trimmed.is_empty() {
+    return FALLBACK_FILE_NAME.to_string();
+  }
+
+  let stem = trimmed.split('.').next().unwrap_or_default().trim_end();
+  let name = if RESERVED_NAMES
+    .iter()
+    .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+  {
+    format!("_{}", trimmed)
+  } else {
+    trimmed.to_string()
+  };
+
+  truncate_file_name(&name, MAX_FILE_NAME_BYTES - DEDUPLICATION_RESERVE_BYTES)
+}
+
+/// Splits into stem and extension (with the dot). Names without a stem have no extension.
+fn split_extension(name: &str) -> (&str, &str) {
+  match name.rfind('.') {
+    Some(index) if index > 0 => name.split_at(index),
+    _ => (name, ""),
+  }
+}
+
+fn truncate_at_char_boundary(value: &str, max_bytes: usize) -> &str {
+  if value.len() <= max_bytes {
+    return value;
+  }
+
+  let mut end = max_bytes;
+  while !value.is_char_boundary(end) {
+    end -= 1;
+  }
+  &value[..end]
+}
+
+/// Shortens the stem so the name fits, keeping the extension whenever it is reasonably short.
+fn truncate_file_name(name: &str, max_bytes: usize) -> String {
+  if name.len() <= max_bytes {
+    return name.to_string();
+  }
+
+  let (stem, extension) = split_extension(name);
+  if extension.len() >= max_bytes / 2 {
+    return truncate_at_char_boundary(name, max_bytes).to_string();
+  }
+
+  format!(
+    "{}{}",
+    truncate_at_char_boundary(stem, max_bytes - extension.len()).trim_end(),
+    extension
+  )
+}
+
+/// Picks `name.ext`, then `name (1).ext`, `name (2).ext`… until `is_taken` accepts one.
+pub fn deduplicate(dir: &Path, file_name: &str, is_taken: impl Fn(&Path) -> bool) -> PathBuf {
+  let path = dir.join(file_name);
+  if !is_taken(&path) {
+    return path;
+  }
+
+  let (stem, extension) = split_extension(file_name);
+  (1..)
+    .map(|index| dir.join(format!("{} ({}){}", stem, index, extension)))
+    .find(|path| !is_taken(path))
+    .expect("Unbounded range always yields a free name")
+}
+
+/// Final destination for a download suggested as `suggested_name`.
+pub fn resolve_destination(
+  dir: &Path,
+  suggested_name: &str,
+  is_taken: impl Fn(&Path) -> bool,
+) -> PathBuf {
+  deduplicate(dir, &sanitize_file_name(suggested_name), is_taken)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+
+  use super::*;
+
+  #[test]
+  fn prefixes_reserved_windows_names() {
+    assert_eq!(sanitize_file_name("CON"), "_CON");
+    assert_eq!(sanitize_file_name("nul.txt"), "_nul.txt");
+    assert_eq!(sanitize_file_name("Com1.tar.gz"), "_Com1.tar.gz");
+    assert_eq!(sanitize_file_name("LPT9 .log"), "_LPT9 .log");
+    assert_eq!(sanitize_file_name("CONSOLE.txt"), "CONSOLE.txt");
+    assert_eq!(sanitize_file_name("COM10"), "COM10");
+  }
+
+  #[test]
+  fn keeps_names_inside_the_directory() {
+    assert_eq!(sanitize_file_name("../../etc/passwd"), "_.._etc_passwd");
+    assert_eq!(sanitize_file_name("..\\..\\boot.ini"), "_.._boot.ini");
+    assert_eq!(sanitize_file_name("C:\\Windows\\x.exe"), "C__Windows_x.exe");
+    assert_eq!(sanitize_file_name(".."), FALLBACK_FILE_NAME);
+    assert_eq!(sanitize_file_name(" . "), FALLBACK_FILE_NAME);
+    assert_eq!(sanitize_file_name(".hidden"), "hidden");
+    assert_eq!(sanitize_file_name("report.pdf. "), "report.pdf");
+  }
+
+  #[test]
+  fn replaces_control_characters() {
+    assert_eq!(sanitize_file_name("a\0b\nc\u{7f}.txt"), "a_b_c_.txt");
+    assert_eq!(sanitize_file_name("\u{1b}[31mred"), "_[31mred");
+    assert_eq!(sanitize_file_name("tab\there"), "tab_here");
+  }
+
+  #[test]
+  fn truncates_multibyte_names_at_char_boundaries() {
+    let max_bytes = MAX_FILE_NAME_BYTES - DEDUPLICATION_RESERVE_BYTES;
+    let name = sanitize_file_name(&format!("{}.txt", "я".repeat(200)));
+    assert!(name.len() <= max_bytes);
+    assert!(name.ends_with(".txt"));
+    assert_eq!(name.len(), 121 * "я".len() + ".txt".len());
+
+    assert_eq!(truncate_file_name(&"😀".repeat(10), 10), "😀😀");
+    assert_eq!(truncate_file_name("😀😀.png", 10), "😀.png");
+    assert_eq!(truncate_file_name("short.png", 10), "short.png");
+
+    // Extensions too long to keep are cut along with the rest
+    let name = format!("a.{}", "é".repeat(200));
+    let truncated = truncate_file_name(&name, max_bytes);
+    assert_eq!(truncated.len(), max_bytes - 1);
+    assert!(name.starts_with(&truncated));
+  }
+
+  #[test]
+  fn deduplicates_taken_names() {
+    let dir = Path::new("downloads");
+    let taken: HashSet<PathBuf> = ["report.pdf", "report (1).pdf", "notes", "archive.tar.gz"]
+      .iter()
+      .map(|name| dir.join(name))
+      .collect();
+    let is_taken = |path: &Path| taken.contains(path);
+
+    assert_eq!(deduplicate(dir, "free.pdf", is_taken), dir.join("free.pdf"));
+    assert_eq!(
+      deduplicate(dir, "report.pdf", is_taken),
+      dir.join("report (2).pdf")
+    );
+    assert_eq!(deduplicate(dir, "notes", is_taken), dir.join("notes (1)"));
+    assert_eq!(
+      deduplicate(dir, "archive.tar.gz", is_taken),
+      dir.join("archive.tar (1).gz")
+    );
+  }
+}
@@ -0,0 +1,285 @@
+use serde::{Deserialize, Serialize};
+
+// Enough to recognize every signature below
+pub const SNIFF_BYTES: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DownloadRisk {
+  Safe,
+  // Documents and images that may carry active content
+  Suspicious,
+  // Runs code when opened
+  Dangerous,
+}
+
+// Launchers, scripts, installers and binaries of every desktop platform
+const DANGEROUS_EXTENSIONS: &[&str] = &[
+  "appimage",
+  "desktop",
+  "sh",
+  "bash",
+  "zsh",
+  "csh",
+  "ksh",
+  "fish",
+  "run",
+  "bin",
+  "elf",
+  "so",
+  "deb",
+  "rpm",
+  "snap",
+  "flatpakref",
+  "flatpak",
+  "exe",
+  "dll",
+  "scr",
+  "com",
+  "pif",
+  "cpl",
+  "msi",
+  "msix",
+  "msp",
+  "appx",
+  "bat",
+  "cmd",
+  "ps1",
+  "psm1",
+  "vbs",
+  "vbe",
+  "js",
+  "jse",
+  "wsf",
+  "wsh",
+  "hta",
+  "lnk",
+  "reg",
+  "inf",
+  "url",
+  "app",
+  "command",
+  "tool",
+  "pkg",
+  "mpkg",
+  "dylib",
+  "scpt",
+  "workflow",
+  "jar",
+  "jnlp",
+  "class",
+  "apk",
+  "py",
+  "pyw",
+  "pl",
+  "rb",
+  "php",
+];
+
+const SUSPICIOUS_EXTENSIONS: &[&str] = &[
+  "docm", "dotm", "xlsm", "xltm", "xlam", "pptm", "potm", "ppam", "html", "htm", "xhtml", "mht",
+  "mhtml", "svg", "iso", "img", "vhd", "vhdx", "dmg", "chm",
+];
+
+// Also covers AppImages, which are ELF files with an "AI" marker
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+// A DOS header alone is just "MZ", which plain text may start with too; real executables point
+// from it to the PE signature
+const DOS_MAGIC: &[u8] = b"MZ";
+const PE_HEADER_OFFSET_POSITION: usize = 0x3c;
+const PE_SIGNATURE: &[u8] = b"PE\0\0";
+const SHEBANG: &[u8] = b"#!";
+const DESKTOP_ENTRY: &[u8] = b"[Desktop Entry]";
+const WINDOWS_SHORTCUT_MAGIC: &[u8] = b"\x4c\x00\x00\x00\x01\x14\x02\x00";
+// Thin and fat Mach-O binaries; the fat magic is shared with Java classes, which are dangerous too
+const MACH_O_MAGICS: &[&[u8]] = &[
+  b"\xfe\xed\xfa\xce",
+  b"\xce\xfa\xed\xfe",
+  b"\xfe\xed\xfa\xcf",
+  b"\xcf\xfa\xed\xfe",
+  b"\xca\xfe\xba\xbe",
+];
+
+fn extension(file_name: &str) -> Option<String> {
+  let (stem, extension) = file_name.rsplit_once('.')?;
+  (!stem.is_empty()).then(|| extension.trim().to_ascii_lowercase())
+}
+
+/// Whether the header is of a Windows executable. Signatures past the sniffed bytes are missed,
+/// but such files are still caught by their extension.
+fn is_portable_executable(header: &[u8]) -> bool {
+  if !header.starts_with(DOS_MAGIC) {
+    return false;
+  }
+
+  let Some(offset) = header
+    .get(PE_HEADER_OFFSET_POSITION..PE_HEADER_OFFSET_POSITION + 4)
+    .and_then(|bytes| bytes.try_into().ok())
+    .map(u32::from_le_bytes)
+  else {
+    return false;
+  };
+
+  usize::try_from(offset)
+    .ok()
+    .and_then(|offset| header.get(offset..offset.checked_add(PE_SIGNATURE.len())?))
+    .is_some_and(|signature| signature == PE_SIGNATURE)
+}
+
+fn is_executable_content(header: &[u8]) -> bool {
+  if header.starts_with(ELF_MAGIC) || is_portable_executable(header) || header.starts_with(SHEBANG)
+  {
+    return true;
+  }
+
+  if header.starts_with(WINDOWS_SHORTCUT_MAGIC)
+    || MACH_O_MAGICS.iter().any(|magic| header.starts_with(magic))
+  {
+    return true;
+  }
+
+  // Launchers are plain text, possibly after a comment or a byte order mark
+  let text = String::from_utf8_lossy(header);
+  text
+    .lines()
+    .map(|line| line.trim_start_matches('\u{feff}').trim())
+    .find(|line| !line.is_empty() && !line.starts_with('#'))
+    .is_some_and(|line| line.as_bytes().starts_with(DESKTOP_ENTRY))
+}
+
+/// Classifies a downloaded file by its name and its first `SNIFF_BYTES` bytes.
+/// Content wins over the name, so renamed executables are still caught.
+pub fn classify(file_name: &str, header: &[u8]) -> DownloadRisk {
+  if is_executable_content(header) {
+    return DownloadRisk::Dangerous;
+  }
+
+  match extension(file_name) {
+    Some(extension) if DANGEROUS_EXTENSIONS.contains(&extension.as_str()) => {
+      DownloadRisk::Dangerous
+    }
+    Some(extension) if SUSPICIOUS_EXTENSIONS.contains(&extension.as_str()) => {
+      DownloadRisk::Suspicious
+    }
+    _ => DownloadRisk::Safe,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn pe_header(offset: u32) -> Vec<u8> {
+    let mut header = vec![0; SNIFF_BYTES];
+    header[..2].copy_from_slice(DOS_MAGIC);
+    header[PE_HEADER_OFFSET_POSITION..PE_HEADER_OFFSET_POSITION + 4]
+      .copy_from_slice(&offset.to_le_bytes());
+    if let Some(signature) = header.get_mut(offset as usize..offset as usize + 4) {
+      signature.copy_from_slice(PE_SIGNATURE);
+    }
+    header
+  }
+
+  #[test]
+  fn classifies_by_extension() {
+    let cases = [
+      ("photo.jpg", DownloadRisk::Safe),
+      ("notes.TXT", DownloadRisk::Safe),
+      ("README", DownloadRisk::Safe),
+      (".bashrc", DownloadRisk::Safe),
+      ("setup.exe", DownloadRisk::Dangerous),
+      ("Setup.EXE", DownloadRisk::Dangerous),
+      ("install.sh", DownloadRisk::Dangerous),
+      ("Telegram.AppImage", DownloadRisk::Dangerous),
+      ("shortcut.lnk", DownloadRisk::Dangerous),
+      ("invoice.docm", DownloadRisk::Suspicious),
+      ("page.html", DownloadRisk::Suspicious),
+      ("disk.iso", DownloadRisk::Suspicious),
+    ];
+    for (file_name, risk) in cases {
+      assert_eq!(classify(file_name, b"plain text"), risk, "{}", file_name);
+    }
+  }
+
+  #[test]
+  fn classifies_double_extensions_by_the_last_one() {
+    let cases = [
+      ("invoice.pdf.exe", DownloadRisk::Dangerous),
+      ("photo.jpg.js", DownloadRisk::Dangerous),
+      ("report.pdf.html", DownloadRisk::Suspicious),
+      ("archive.tar.gz", DownloadRisk::Safe),
+      ("setup.exe.txt", DownloadRisk::Safe),
+      ("invoice.pdf.exe ", DownloadRisk::Dangerous),
+    ];
+    for (file_name, risk) in cases {
+      assert_eq!(classify(file_name, b"plain text"), risk, "{}", file_name);
+    }
+  }
+
+  #[test]
+  fn sniffs_executable_content() {
+    let cases: [(&str, &[u8], DownloadRisk); 9] = [
+      ("photo.jpg", b"\x7fELF\x02\x01\x01", DownloadRisk::Dangerous),
+      ("notes.txt", b"#!/bin/sh\nrm -rf ~", DownloadRisk::Dangerous),
+      (
+        "app.txt",
+        b"[Desktop Entry]\nExec=sh",
+        DownloadRisk::Dangerous,
+      ),
+      (
+        "app.txt",
+        b"\xef\xbb\xbf# launcher\n\n[Desktop Entry]\nExec=sh",
+        DownloadRisk::Dangerous,
+      ),
+      (
+        "doc.pdf",
+        b"\xcf\xfa\xed\xfe\x07\x00",
+        DownloadRisk::Dangerous,
+      ),
+      (
+        "link.txt",
+        b"\x4c\x00\x00\x00\x01\x14\x02\x00",
+        DownloadRisk::Dangerous,
+      ),
+      ("notes.txt", b"# [Desktop Entry]", DownloadRisk::Safe),
+      (
+        "notes.txt",
+        b"Text mentioning [Desktop Entry]",
+        DownloadRisk::Safe,
+      ),
+      ("photo.png", b"\x89PNG\r\n\x1a\n", DownloadRisk::Safe),
+    ];
+    for (file_name, header, risk) in cases {
+      assert_eq!(classify(file_name, header), risk, "{:?}", header);
+    }
+  }
+
+  #[test]
+  fn requires_the_pe_signature_after_the_dos_header() {
+    assert_eq!(
+      classify("photo.jpg", &pe_header(0x80)),
+      DownloadRisk::Dangerous
+    );
+    assert_eq!(
+      classify("notes.txt", b"MZ is a text file"),
+      DownloadRisk::Safe
+    );
+
+    // Pointing at something else, past the sniffed bytes or out of any range
+    let mut header = pe_header(0x80);
+    header[0x80] = b'X';
+    assert_eq!(classify("notes.txt", &header), DownloadRisk::Safe);
+    assert_eq!(
+      classify("notes.txt", &pe_header(0x1000)),
+      DownloadRisk::Safe
+    );
+    assert_eq!(
+      classify("notes.txt", &pe_header(u32::MAX)),
+      DownloadRisk::Safe
+    );
+    assert_eq!(
+      classify("notes.txt", &pe_header(0x80)[..0x40]),
+      DownloadRisk::Safe
+    );
+  }
+}
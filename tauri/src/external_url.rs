@@ -0,0 +1,283 @@
+use tauri::AppHandle;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+use tauri_plugin_opener::OpenerExt;
+use url::{Host, Url};
+
+use crate::tray::translated_label;
+
+// Label constants, translatable through `set_menu_translations`
+const EXTERNAL_URL_TITLE_ID: &str = "external_url_title";
+const EXTERNAL_URL_TITLE_LABEL: &str = "Open Link";
+const EXTERNAL_URL_INTERNATIONAL_ID: &str = "external_url_international";
+const EXTERNAL_URL_INTERNATIONAL_LABEL: &str =
+  "This link leads to a domain with international characters.";
+const EXTERNAL_URL_MIXED_SCRIPTS_ID: &str = "external_url_mixed_scripts";
+const EXTERNAL_URL_MIXED_SCRIPTS_LABEL: &str = "This link leads to a domain mixing letters of different alphabets, which is often used to imitate other sites.";
+const EXTERNAL_URL_LOOK_ALIKE_ID: &str = "external_url_look_alike";
+const EXTERNAL_URL_LOOK_ALIKE_LABEL: &str =
+  "This link leads to a domain that looks like a Telegram domain, but is not one.";
+const EXTERNAL_URL_CREDENTIALS_ID: &str = "external_url_credentials";
+const EXTERNAL_URL_CREDENTIALS_LABEL: &str = "This link hides its real domain behind a user name.";
+const EXTERNAL_URL_CONFIRM_ID: &str = "external_url_confirm";
+const EXTERNAL_URL_CONFIRM_LABEL: &str = "Open it anyway?";
+const EXTERNAL_URL_OPEN_ID: &str = "external_url_open";
+const EXTERNAL_URL_OPEN_LABEL: &str = "Open";
+const EXTERNAL_URL_CANCEL_ID: &str = "external_url_cancel";
+const EXTERNAL_URL_CANCEL_LABEL: &str = "Cancel";
+
+const ALLOWED_SCHEMES: &[&str] = &["http", "https", "mailto", "tel"];
+// Domains worth imitating for users of the app
+const TELEGRAM_DOMAINS: &[&str] = &[
+  "telegram.org",
+  "t.me",
+  "telegram.me",
+  "telegram.dog",
+  "telegra.ph",
+];
+// ASCII sequences read as other letters at a glance, with what they imitate
+const CONFUSABLE_SEQUENCES: &[(&str, &str)] = &[
+  ("rn", "m"),
+  ("vv", "w"),
+  ("cl", "d"),
+  ("0", "o"),
+  ("1", "l"),
+  ("i", "l"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Warning {
+  MixedScripts,
+  International,
+  LookAlike,
+  Credentials,
+}
+
+impl Warning {
+  fn message(self) -> String {
+    match self {
+      Warning::MixedScripts => translated_label(
+        EXTERNAL_URL_MIXED_SCRIPTS_ID,
+        EXTERNAL_URL_MIXED_SCRIPTS_LABEL,
+      ),
+      Warning::International => translated_label(
+        EXTERNAL_URL_INTERNATIONAL_ID,
+        EXTERNAL_URL_INTERNATIONAL_LABEL,
+      ),
+      Warning::LookAlike => {
+        translated_label(EXTERNAL_URL_LOOK_ALIKE_ID, EXTERNAL_URL_LOOK_ALIKE_LABEL)
+      }
+      Warning::Credentials => {
+        translated_label(EXTERNAL_URL_CREDENTIALS_ID, EXTERNAL_URL_CREDENTIALS_LABEL)
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+  Latin,
+  Greek,
+  Cyrillic,
+  Other,
+}
+
+// Rough script of a letter, enough to tell the alphabets homographs are usually made of apart
+fn script(c: char) -> Option<Script> {
+  match c {
+    '0'..='9' | '-' | '_' => None,
+    'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}' => {
+      Some(Script::Latin)
+    }
+    '\u{0370}'..='\u{03FF}' | '\u{1F00}'..='\u{1FFF}' => Some(Script::Greek),
+    '\u{0400}'..='\u{052F}' => Some(Script::Cyrillic),
+    _ => Some(Script::Other),
+  }
+}
+
+fn has_mixed_scripts(label: &str) -> bool {
+  let mut scripts = label.chars().filter_map(script);
+  let Some(first) = scripts.next() else {
+    return false;
+  };
+  scripts.any(|script| script != first)
+}
+
+fn skeleton(domain: &str) -> String {
+  CONFUSABLE_SEQUENCES
+    .iter()
+    .fold(domain.to_string(), |skeleton, (sequence, letter)| {
+      skeleton.replace(sequence, letter)
+    })
+}
+
+fn is_look_alike(domain: &str) -> bool {
+  let domain = domain.trim_end_matches('.');
+  if TELEGRAM_DOMAINS
+    .iter()
+    .any(|telegram| domain == *telegram || domain.ends_with(&format!(".{}", telegram)))
+  {
+    return false;
+  }
+
+  // Imitations spell the domain with confusable letters, or prepend it to another domain
+  let domain_skeleton = skeleton(domain);
+  TELEGRAM_DOMAINS.iter().any(|telegram| {
+    let telegram_skeleton = skeleton(telegram);
+    domain_skeleton == telegram_skeleton
+      || domain_skeleton.ends_with(&format!(".{}", telegram_skeleton))
+      || domain_skeleton.starts_with(&format!("{}.", telegram_skeleton))
+  })
+}
+
+/// The domain as the user would read it, with international labels decoded.
+fn decoded_domain(domain: &str) -> String {
+  let (decoded, result) = idna::domain_to_unicode(domain);
+  if result.is_ok() {
+    decoded
+  } else {
+    domain.to_string()
+  }
+}
+
+/// Why the URL deserves a second look before leaving the app, the most specific reason first.
+fn warning(url: &Url) -> Option<Warning> {
+  if !url.username().is_empty() || url.password().is_some() {
+    return Some(Warning::Credentials);
+  }
+  let Some(Host::Domain(domain)) = url.host() else {
+    return None;
+  };
+
+  let decoded = decoded_domain(domain);
+  if decoded.split('.').any(has_mixed_scripts) {
+    Some(Warning::MixedScripts)
+  } else if domain.split('.').any(|label| label.starts_with("xn--")) {
+    Some(Warning::International)
+  } else if is_look_alike(domain) {
+    Some(Warning::LookAlike)
+  } else {
+    None
+  }
+}
+
+fn open(app: &AppHandle, url: &Url) -> Result<(), String> {
+  app
+    .opener()
+    .open_url(url.as_str(), None::<&str>)
+    .map_err(|err| err.to_string())
+}
+
+fn confirm_and_open(app: &AppHandle, url: Url, warning: Warning) {
+  let host = url.host_str().map(decoded_domain).unwrap_or_default();
+  log::info!("Confirming {} before opening: {:?}", url, warning);
+
+  let app_handle = app.clone();
+  app
+    .dialog()
+    .message(format!(
+      "{}\n\n{}\n\n{}",
+      warning.message(),
+      host,
+      translated_label(EXTERNAL_URL_CONFIRM_ID, EXTERNAL_URL_CONFIRM_LABEL)
+    ))
+    .title(translated_label(
+      EXTERNAL_URL_TITLE_ID,
+      EXTERNAL_URL_TITLE_LABEL,
+    ))
+    .kind(MessageDialogKind::Warning)
+    .buttons(MessageDialogButtons::OkCancelCustom(
+      translated_label(EXTERNAL_URL_OPEN_ID, EXTERNAL_URL_OPEN_LABEL),
+      translated_label(EXTERNAL_URL_CANCEL_ID, EXTERNAL_URL_CANCEL_LABEL),
+    ))
+    .show(move |is_confirmed| {
+      if !is_confirmed {
+        return;
+      }
+      if let Err(err) = open(&app_handle, &url) {
+        log::error!("Failed to open {}: {}", url, err);
+      }
+    });
+}
+
+/// Opens a link outside of the app, in the browser, mail or phone app. Links to suspicious
+/// domains are only opened once the user confirms them in a native dialog.
+pub fn open_external_url(app: &AppHandle, url: &str) -> Result<(), String> {
+  let url = Url::parse(url.trim()).map_err(|err| format!("Invalid URL: {}", err))?;
+  if !ALLOWED_SCHEMES.contains(&url.scheme()) {
+    return Err(format!(
+      "Links of scheme {:?} cannot be opened",
+      url.scheme()
+    ));
+  }
+
+  match warning(&url) {
+    Some(warning) => {
+      confirm_and_open(app, url, warning);
+      Ok(())
+    }
+    None => open(app, &url),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn warning_of(url: &str) -> Option<Warning> {
+    warning(&Url::parse(url).unwrap())
+  }
+
+  #[test]
+  fn detects_confusable_spellings() {
+    assert!(is_look_alike("telegrarn.org"));
+    assert!(is_look_alike("te1egram.org"));
+    assert_eq!(
+      warning_of("https://telegrarn.org/"),
+      Some(Warning::LookAlike)
+    );
+  }
+
+  #[test]
+  fn detects_telegram_domains_prepended_to_others() {
+    assert!(is_look_alike("t.me.evil.com"));
+    assert_eq!(
+      warning_of("https://t.me.evil.com/durov"),
+      Some(Warning::LookAlike)
+    );
+  }
+
+  #[test]
+  fn detects_mixed_scripts() {
+    // The "е" is Cyrillic
+    assert_eq!(
+      warning_of("https://tеlegram.org/"),
+      Some(Warning::MixedScripts)
+    );
+    assert_eq!(
+      warning_of("https://пример.рф/"),
+      Some(Warning::International)
+    );
+  }
+
+  #[test]
+  fn detects_credentials() {
+    assert_eq!(
+      warning_of("https://user@example.com/"),
+      Some(Warning::Credentials)
+    );
+    assert_eq!(
+      warning_of("https://t.me@evil.com/"),
+      Some(Warning::Credentials)
+    );
+  }
+
+  #[test]
+  fn allows_telegram_domains_and_their_subdomains() {
+    assert!(!is_look_alike("t.me"));
+    assert!(!is_look_alike("x.t.me"));
+    assert!(!is_look_alike("telegram.org."));
+    assert_eq!(warning_of("https://x.t.me/"), None);
+    assert_eq!(warning_of("https://core.telegram.org/api"), None);
+    assert_eq!(warning_of("https://example.com/"), None);
+  }
+}
@@ -1,17 +1,54 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{LazyLock, Mutex};
 
-use serde_json::json;
-use tauri::{Emitter, LogicalPosition, Manager, webview::DownloadEvent};
+use tauri::{LogicalPosition, Manager, webview::DownloadEvent};
 use url::Url;
 use uuid::Uuid;
 
+mod context_menu;
+use context_menu::ContextMenuContext;
+
 mod deeplink;
 use deeplink::Deeplink;
 
+mod dnd;
+use dnd::{MuteDuration, QuietHours};
+
+mod downloads;
+use downloads::{Download, DownloadSettings};
+
+mod external_url;
+
+mod menu;
+
+mod navigation;
+use navigation::OpenWindowError;
+
+mod notifications;
+use notifications::NotificationPayload;
+
+mod proxy;
+use proxy::ProxySettings;
+
+mod settings;
+use settings::Settings;
+
+mod shortcuts;
+
+mod spelling;
+use spelling::SpellingDictionary;
+
 mod tray;
+
+mod updater;
+use updater::{UpdateInfo, UpdaterConfig};
+
+mod webview;
+
 mod window;
-use crate::window::{WINDOW_STATES, WindowState};
+use crate::window::{WINDOW_STATES, WindowRole, WindowState};
+
+mod zoom;
 
 #[cfg(target_os = "macos")]
 mod mac;
@@ -57,8 +94,8 @@ pub static TRAFFIC_LIGHT_POSITION_OVERLAY_MOBILE: LazyLock<LogicalPosition<f64>>
   TRAFFIC_LIGHT_POSITION_OVERLAY_MOBILE_LEGACY
 });
 
-pub const WINDOW_WIDTH: f64 = 1088.0;
-pub const WINDOW_HEIGHT: f64 = 700.0;
+pub(crate) static ROBOTO_BOLD_FONT: &[u8] = include_bytes!("../fonts/Roboto-Bold.ttf");
+
 pub const WINDOW_MIN_WIDTH: f64 = 360.0;
 pub const WINDOW_MIN_HEIGHT: f64 = 200.0;
 
@@ -75,11 +112,6 @@ pub const BASE_URL: &str = match std::option_env!("BASE_URL") {
   None => "http://localhost:1234",
 };
 
-pub const WITH_UPDATER: &str = match std::option_env!("WITH_UPDATER") {
-  Some(str) => str,
-  None => "false",
-};
-
 pub(crate) fn strip_hash_from_url(url: &str) -> String {
   if let Ok(mut parsed_url) = Url::parse(url) {
     parsed_url.set_fragment(None);
@@ -113,12 +145,26 @@ pub fn run() {
     }))
     .plugin(tauri_plugin_os::init())
     .plugin(tauri_plugin_fs::init())
-    .plugin(tauri_plugin_shell::init())
     .plugin(tauri_plugin_notification::init())
     .plugin(tauri_plugin_log::Builder::default().build())
     .plugin(tauri_plugin_window_state::Builder::default().build())
     .plugin(tauri_plugin_deep_link::init())
-    .plugin(tauri_plugin_process::init());
+    .plugin(tauri_plugin_process::init())
+    .plugin(tauri_plugin_dialog::init())
+    .plugin(tauri_plugin_clipboard_manager::init())
+    .plugin(tauri_plugin_opener::init())
+    .plugin(
+      tauri_plugin_global_shortcut::Builder::new()
+        .with_handler(|app, shortcut, event| {
+          crate::shortcuts::handle(app, shortcut, event.state())
+        })
+        .build(),
+    );
+
+  let app = app.on_menu_event(|app, event| {
+    crate::menu::handle(app, &event);
+    crate::context_menu::handle(app, &event);
+  });
 
   let app = app.on_window_event(|window, event| match event {
     tauri::WindowEvent::CloseRequested { api, .. } => {
@@ -159,10 +205,16 @@ pub fn run() {
         }
       }
     }
+    tauri::WindowEvent::Focused(is_focused) => {
+      if let Some(chat_id) = crate::window::set_window_focused(window.label(), *is_focused) {
+        crate::notifications::clear_chat(&chat_id);
+      }
+    }
     tauri::WindowEvent::Destroyed => {
       if let Ok(mut states) = WINDOW_STATES.lock() {
         states.remove(window.label());
       }
+      crate::window::release_pending_events(window.label());
     }
     _ => {}
   });
@@ -171,6 +223,15 @@ pub fn run() {
     // Manage app state
     app.manage(AppState::new(AppStateStruct::default()));
 
+    crate::settings::init(app.handle());
+    crate::webview::init(&crate::settings::get().webview);
+    crate::spelling::init();
+    crate::dnd::init(app.handle());
+    crate::downloads::init(app.handle());
+    // Before the first window, so updated versions that crash while opening it are rolled back
+    crate::updater::init(app.handle())?;
+    crate::menu::init(app.handle())?;
+
     let _main_window = open_new_window(app.handle().clone(), BASE_URL.to_string())
       .expect("Failed to open main window");
 
@@ -179,14 +240,12 @@ pub fn run() {
       log::error!("Failed to setup deeplink: {:?}", err);
     }
 
-    if WITH_UPDATER == "true" {
-      app
-        .handle()
-        .plugin(tauri_plugin_updater::Builder::new().build())?;
-    }
-
     crate::tray::TrayManager::init(app.handle().clone())?;
 
+    crate::shortcuts::init(app.handle());
+
+    crate::notifications::init(app.handle());
+
     Ok(())
   });
 
@@ -195,13 +254,57 @@ pub fn run() {
     set_notifications_count,
     set_window_title,
     open_new_window_cmd,
+    open_external_url,
     save_current_url,
-    set_menu_translations
+    set_menu_translations,
+    frontend_ready,
+    subscribe_event,
+    unsubscribe_event,
+    show_notification,
+    cache_notification_avatar,
+    confirm_notification_reply,
+    set_active_chat,
+    get_dnd_status,
+    mute_notifications,
+    unmute_notifications,
+    set_quiet_hours,
+    set_follow_system_dnd,
+    list_downloads,
+    cancel_download,
+    retry_download,
+    get_download_settings,
+    set_download_settings,
+    choose_download_directory,
+    open_download,
+    reveal_download,
+    clear_download_history,
+    get_updater_status,
+    set_updater_config,
+    check_for_update,
+    download_update,
+    install_and_relaunch,
+    set_call_active,
+    get_settings,
+    update_settings,
+    zoom_in,
+    zoom_out,
+    zoom_reset,
+    set_zoom,
+    test_proxy_connection,
+    show_context_menu,
+    list_spelling_dictionaries,
+    set_spelling_languages
   ]);
 
   app
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    .build(tauri::generate_context!())
+    .expect("error while building tauri application")
+    .run(|app, event| {
+      if let tauri::RunEvent::Exit = event {
+        crate::updater::record_clean_exit();
+        crate::updater::install_on_exit(app);
+      }
+    });
 }
 
 #[tauri::command]
@@ -287,8 +390,8 @@ fn set_notifications_count(
 }
 
 #[tauri::command]
-fn set_menu_translations(translations: HashMap<String, String>) {
-  crate::tray::set_menu_translations(translations);
+fn set_menu_translations(app: tauri::AppHandle, translations: HashMap<String, String>) {
+  crate::settings::set_labels(&app, translations);
 }
 
 #[tauri::command]
@@ -304,8 +407,212 @@ fn set_window_title(window: tauri::WebviewWindow, title: String) {
 }
 
 #[tauri::command]
-async fn open_new_window_cmd(app: tauri::AppHandle, url: String) -> bool {
-  open_new_window(app, url).is_ok()
+async fn open_new_window_cmd(app: tauri::AppHandle, url: String) -> Result<(), OpenWindowError> {
+  open_new_window(app, url).map(|_| ())
+}
+
+#[tauri::command]
+fn open_external_url(app: tauri::AppHandle, url: String) -> Result<(), String> {
+  crate::external_url::open_external_url(&app, &url)
+}
+
+#[tauri::command]
+fn frontend_ready(app: tauri::AppHandle, window: tauri::WebviewWindow) {
+  crate::window::mark_window_ready(&window);
+  crate::updater::confirm_launch(&app, window.label());
+}
+
+#[tauri::command]
+fn subscribe_event(window: tauri::WebviewWindow, event: String) {
+  crate::window::subscribe_event(&window, &event);
+}
+
+#[tauri::command]
+fn unsubscribe_event(window: tauri::WebviewWindow, event: String) {
+  crate::window::unsubscribe_event(window.label(), &event);
+}
+
+#[tauri::command]
+fn show_notification(
+  app: tauri::AppHandle,
+  window: tauri::WebviewWindow,
+  payload: NotificationPayload,
+) -> Result<(), String> {
+  crate::notifications::show_notification(&app, window.label(), payload)
+}
+
+#[tauri::command]
+fn cache_notification_avatar(app: tauri::AppHandle, request: tauri::ipc::Request) -> Result<(), String> {
+  crate::notifications::cache_avatar_photo(&app, &request)
+}
+
+#[tauri::command]
+fn confirm_notification_reply(id: u32, success: bool) {
+  crate::notifications::confirm_reply(id, success);
+}
+
+#[tauri::command]
+fn set_active_chat(window: tauri::WebviewWindow, chat_id: Option<String>) {
+  if let Some(chat_id) = crate::window::set_active_chat(window.label(), chat_id) {
+    crate::notifications::clear_chat(&chat_id);
+  }
+}
+
+#[tauri::command]
+fn get_dnd_status() -> serde_json::Value {
+  crate::dnd::get_status()
+}
+
+#[tauri::command]
+fn mute_notifications(app: tauri::AppHandle, duration: MuteDuration) {
+  crate::dnd::mute(&app, duration);
+}
+
+#[tauri::command]
+fn unmute_notifications(app: tauri::AppHandle) {
+  crate::dnd::unmute(&app);
+}
+
+#[tauri::command]
+fn set_quiet_hours(app: tauri::AppHandle, quiet_hours: Option<QuietHours>) -> Result<(), String> {
+  crate::dnd::set_quiet_hours(&app, quiet_hours)
+}
+
+#[tauri::command]
+fn set_follow_system_dnd(app: tauri::AppHandle, should_follow: bool) {
+  crate::dnd::set_follow_system(&app, should_follow);
+}
+
+#[tauri::command]
+fn list_downloads() -> Vec<Download> {
+  crate::downloads::list_downloads()
+}
+
+#[tauri::command]
+fn cancel_download(app: tauri::AppHandle, id: String) -> Result<(), String> {
+  crate::downloads::cancel_download(&app, &id)
+}
+
+#[tauri::command]
+fn retry_download(app: tauri::AppHandle, id: String) -> Result<(), String> {
+  crate::downloads::retry_download(&app, &id)
+}
+
+#[tauri::command]
+fn clear_download_history() -> Result<(), String> {
+  crate::downloads::clear_download_history()
+}
+
+#[tauri::command]
+fn open_download(app: tauri::AppHandle, id: String) -> Result<(), String> {
+  crate::downloads::open_download(&app, &id)
+}
+
+#[tauri::command]
+fn reveal_download(app: tauri::AppHandle, id: String) -> Result<(), String> {
+  crate::downloads::reveal_download(&app, &id)
+}
+
+#[tauri::command]
+fn get_download_settings() -> DownloadSettings {
+  crate::downloads::get_settings()
+}
+
+#[tauri::command]
+fn set_download_settings(app: tauri::AppHandle, settings: DownloadSettings) {
+  crate::downloads::set_settings(&app, settings);
+}
+
+#[tauri::command]
+async fn choose_download_directory(app: tauri::AppHandle) -> DownloadSettings {
+  crate::downloads::choose_directory(&app)
+}
+
+#[tauri::command]
+fn get_updater_status(app: tauri::AppHandle) -> serde_json::Value {
+  crate::updater::get_status(&app)
+}
+
+#[tauri::command]
+fn set_updater_config(app: tauri::AppHandle, config: UpdaterConfig) -> Result<(), String> {
+  crate::updater::set_config(&app, config)
+}
+
+#[tauri::command]
+async fn check_for_update(app: tauri::AppHandle) -> Result<Option<UpdateInfo>, String> {
+  crate::updater::check_for_update(&app).await
+}
+
+#[tauri::command]
+async fn download_update(app: tauri::AppHandle) -> Result<UpdateInfo, String> {
+  crate::updater::download_update(&app).await
+}
+
+#[tauri::command]
+fn install_and_relaunch(app: tauri::AppHandle) -> Result<(), String> {
+  crate::updater::install_and_relaunch(&app)
+}
+
+#[tauri::command]
+fn get_settings() -> Settings {
+  crate::settings::get().redacted()
+}
+
+#[tauri::command]
+fn update_settings(app: tauri::AppHandle, patch: serde_json::Value) -> Result<Settings, String> {
+  crate::settings::update(&app, patch).map(|settings| settings.redacted())
+}
+
+#[tauri::command]
+fn zoom_in(app: tauri::AppHandle, window: tauri::WebviewWindow) -> Result<f64, String> {
+  crate::zoom::zoom_in(&app, &window)
+}
+
+#[tauri::command]
+fn zoom_out(app: tauri::AppHandle, window: tauri::WebviewWindow) -> Result<f64, String> {
+  crate::zoom::zoom_out(&app, &window)
+}
+
+#[tauri::command]
+fn zoom_reset(app: tauri::AppHandle, window: tauri::WebviewWindow) -> Result<f64, String> {
+  crate::zoom::zoom_reset(&app, &window)
+}
+
+#[tauri::command]
+fn set_zoom(
+  app: tauri::AppHandle,
+  window: tauri::WebviewWindow,
+  factor: f64,
+) -> Result<f64, String> {
+  crate::zoom::set_zoom(&app, &window, factor)
+}
+
+#[tauri::command]
+async fn test_proxy_connection(
+  proxy: ProxySettings,
+  target: Option<String>,
+) -> Result<u64, String> {
+  crate::proxy::test_connection(proxy, target).await
+}
+
+#[tauri::command]
+fn list_spelling_dictionaries() -> Vec<SpellingDictionary> {
+  crate::spelling::list_dictionaries()
+}
+
+#[tauri::command]
+fn set_spelling_languages(app: tauri::AppHandle, languages: Vec<String>) -> Result<(), String> {
+  crate::spelling::set_languages(&app, languages)
+}
+
+#[tauri::command]
+fn show_context_menu(window: tauri::WebviewWindow, context: ContextMenuContext) -> Result<(), String> {
+  crate::context_menu::show(&window, context)
+}
+
+#[tauri::command]
+fn set_call_active(is_active: bool) {
+  crate::updater::set_call_active(is_active);
 }
 
 #[tauri::command]
@@ -321,52 +628,68 @@ fn save_current_url(window: tauri::WebviewWindow) {
 pub(crate) fn open_new_window(
   app: tauri::AppHandle,
   url: String,
-) -> Result<tauri::WebviewWindow, tauri::Error> {
+) -> Result<tauri::WebviewWindow, OpenWindowError> {
+  let role = if app.webview_windows().is_empty() {
+    WindowRole::Main
+  } else {
+    WindowRole::Secondary
+  };
+  open_window_with_role(app, url, role)
+}
+
+pub(crate) fn open_window_with_role(
+  app: tauri::AppHandle,
+  url: String,
+  role: WindowRole,
+) -> Result<tauri::WebviewWindow, OpenWindowError> {
+  let url = crate::navigation::resolve_window_url(&url)?;
   let window_label = Uuid::new_v4().to_string();
+  let settings = crate::settings::get();
+  let window_settings = settings.window;
+  #[cfg(not(target_os = "macos"))]
+  let webview_settings = settings.webview;
+  #[cfg(target_os = "linux")]
+  let spelling_settings = settings.spelling;
+  let proxy = settings.network.proxy;
+  let zoom = crate::zoom::level_for(role);
+  let navigation_app = app.clone();
+  let new_window_app = app.clone();
+
   let new_window_builder = tauri::WebviewWindowBuilder::new(
     &app,
     window_label.clone(),
-    tauri::WebviewUrl::App(url.into()),
+    tauri::WebviewUrl::App(url.to_string().into()),
   )
-  .additional_browser_args("--autoplay-policy=no-user-gesture-required")
   .fullscreen(false)
   .resizable(true)
   .title(DEFAULT_WINDOW_TITLE)
-  .inner_size(WINDOW_WIDTH, WINDOW_HEIGHT)
+  .inner_size(window_settings.width, window_settings.height)
   .min_inner_size(WINDOW_MIN_WIDTH, WINDOW_MIN_HEIGHT)
   .disable_drag_drop_handler() // Required for Drag & Drop on Windows
   .initialization_script(&format!(
     "window.tauri = {{ version: '{}' }};",
     env!("CARGO_PKG_VERSION")
   ))
-  .on_download(|window, event| {
-    match event {
-      #[allow(unused_variables)]
-      DownloadEvent::Requested { destination, .. } => {
-        // On macOS, Webview does not provide basic download logic
-        #[cfg(target_os = "macos")]
-        if let Some(filename) = destination.file_name() {
-          if let Ok(downloads_dir) = window.app_handle().path().download_dir() {
-            let new_destination = downloads_dir.join(filename);
-            *destination = new_destination;
-          }
-        }
-      }
-      DownloadEvent::Finished { url, success, .. } => {
-        window
-          .emit_to(
-            window.label(),
-            "download-finished",
-            json!({
-              "url": url.to_string(),
-              "success": success
-            }),
-          )
-          .unwrap_or_default();
-      }
-      _ => {}
-    };
-    true
+  .initialization_script_for_all_frames(crate::zoom::shortcuts_script())
+  .on_navigation(move |url| crate::navigation::handle_navigation(&navigation_app, url))
+  .on_page_load(|window, payload| {
+    if payload.event() == tauri::webview::PageLoadEvent::Started {
+      crate::window::reset_window_page(window.label());
+    }
+    crate::navigation::handle_page_load(&window, payload.url(), payload.event());
+  })
+  .on_new_window(move |url, features| {
+    crate::navigation::handle_new_window(&new_window_app, url, features)
+  })
+  .on_download(|window, event| match event {
+    DownloadEvent::Requested { url, destination } => {
+      crate::downloads::handle_requested(&window, url.as_str(), destination)
+    }
+    DownloadEvent::Finished { url, path, success } => {
+      crate::downloads::handle_finished(&window, url.as_str(), path, success);
+      true
+    }
+    _ => true,
   });
 
   if let Ok(mut states) = WINDOW_STATES.lock() {
@@ -374,6 +697,13 @@ pub(crate) fn open_new_window(
       title: DEFAULT_WINDOW_TITLE.to_string(),
       is_overlay: cfg!(target_os = "macos"),
       is_mobile: false,
+      is_ready: false,
+      is_focused: false,
+      active_chat_id: None,
+      role,
+      zoom,
+      app_url: url.clone(),
+      subscribed_events: HashSet::new(),
     };
     states.insert(window_label.to_string(), new_state);
   }
@@ -383,8 +713,44 @@ pub(crate) fn open_new_window(
   #[cfg(target_os = "macos")]
   let new_window_builder = new_window_builder.title("");
 
+  #[cfg(windows)]
+  let new_window_builder = {
+    let browser_args = crate::webview::browser_args(&webview_settings);
+    new_window_builder
+      .additional_browser_args(&crate::proxy::with_browser_args(&browser_args, proxy.as_ref()))
+  };
+  #[cfg(target_os = "linux")]
+  let new_window_builder = match &proxy {
+    Some(proxy) => new_window_builder.proxy_url(proxy.endpoint_url()),
+    None => new_window_builder,
+  };
+  // Checked again here, as the Network framework APIs behind it are missing before macOS 14
+  #[cfg(target_os = "macos")]
+  let new_window_builder = match &proxy {
+    Some(proxy) => match crate::proxy::validate_for_engine(proxy) {
+      Ok(()) => new_window_builder.proxy_url(proxy.endpoint_url()),
+      Err(err) => {
+        log::warn!("Ignoring proxy settings: {}", err);
+        new_window_builder
+      }
+    },
+    None => new_window_builder,
+  };
+
   let window = new_window_builder.build()?;
 
+  #[cfg(target_os = "linux")]
+  {
+    crate::webview::apply_to_webview(&window, &webview_settings);
+    crate::proxy::apply_to_webview(&window, proxy.as_ref());
+    crate::menu::apply_to_window(&window);
+    crate::spelling::apply_to_webview(&window, &spelling_settings);
+  }
+
+  if zoom != 1.0 {
+    window.set_zoom(zoom).unwrap_or_default();
+  }
+
   #[cfg(target_os = "macos")]
   if let Some(base_window) = app.get_window(&window_label) {
     mac::setup_traffic_light_positioner(&base_window, *TRAFFIC_LIGHT_POSITION_OVERLAY);
@@ -0,0 +1,437 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::menu::{AboutMetadata, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu};
+use tauri::{AppHandle, WebviewWindow, Wry};
+
+use crate::tray::{MENU_ITEM_QUIT_ID, MENU_ITEM_QUIT_LABEL, translated_label};
+use crate::{BASE_URL, DEFAULT_WINDOW_TITLE};
+
+// Label constants, translatable through `set_menu_translations`
+const MENU_FILE_ID: &str = "menu_file";
+const MENU_FILE_LABEL: &str = "File";
+const MENU_NEW_WINDOW_ID: &str = "menu_new_window";
+const MENU_NEW_WINDOW_LABEL: &str = "New Window";
+const MENU_SETTINGS_ID: &str = "menu_settings";
+const MENU_SETTINGS_LABEL: &str = "Settings…";
+const MENU_CLOSE_WINDOW_ID: &str = "menu_close_window";
+const MENU_CLOSE_WINDOW_LABEL: &str = "Close Window";
+const MENU_QUIT_ID: &str = "menu_quit";
+const MENU_EDIT_ID: &str = "menu_edit";
+const MENU_EDIT_LABEL: &str = "Edit";
+const MENU_UNDO_ID: &str = "menu_undo";
+const MENU_UNDO_LABEL: &str = "Undo";
+const MENU_REDO_ID: &str = "menu_redo";
+const MENU_REDO_LABEL: &str = "Redo";
+const MENU_CUT_ID: &str = "menu_cut";
+const MENU_CUT_LABEL: &str = "Cut";
+const MENU_COPY_ID: &str = "menu_copy";
+const MENU_COPY_LABEL: &str = "Copy";
+const MENU_PASTE_ID: &str = "menu_paste";
+const MENU_PASTE_LABEL: &str = "Paste";
+const MENU_SELECT_ALL_ID: &str = "menu_select_all";
+const MENU_SELECT_ALL_LABEL: &str = "Select All";
+const MENU_VIEW_ID: &str = "menu_view";
+const MENU_VIEW_LABEL: &str = "View";
+const MENU_ZOOM_IN_ID: &str = "menu_zoom_in";
+const MENU_ZOOM_IN_LABEL: &str = "Zoom In";
+const MENU_ZOOM_OUT_ID: &str = "menu_zoom_out";
+const MENU_ZOOM_OUT_LABEL: &str = "Zoom Out";
+const MENU_ZOOM_RESET_ID: &str = "menu_zoom_reset";
+const MENU_ZOOM_RESET_LABEL: &str = "Actual Size";
+const MENU_FULLSCREEN_ID: &str = "menu_fullscreen";
+const MENU_FULLSCREEN_LABEL: &str = "Toggle Full Screen";
+const MENU_HIDE_MENU_BAR_ID: &str = "menu_hide_menu_bar";
+#[cfg(target_os = "linux")]
+const MENU_HIDE_MENU_BAR_LABEL: &str = "Hide Menu Bar";
+const MENU_WINDOW_ID: &str = "menu_window";
+const MENU_WINDOW_LABEL: &str = "Window";
+const MENU_MINIMIZE_ID: &str = "menu_minimize";
+const MENU_MINIMIZE_LABEL: &str = "Minimize";
+const MENU_MAXIMIZE_ID: &str = "menu_maximize";
+const MENU_MAXIMIZE_LABEL: &str = "Zoom";
+const MENU_HELP_ID: &str = "menu_help";
+const MENU_HELP_LABEL: &str = "Help";
+const MENU_ABOUT_ID: &str = "menu_about";
+const MENU_ABOUT_LABEL: &str = "About Telegram";
+const MENU_CHECK_UPDATES_ID: &str = "menu_check_updates";
+const MENU_CHECK_UPDATES_LABEL: &str = "Check for Updates…";
+const MENU_FAQ_ID: &str = "menu_faq";
+const MENU_FAQ_LABEL: &str = "Telegram FAQ";
+#[cfg(target_os = "macos")]
+const MENU_SERVICES_ID: &str = "menu_services";
+#[cfg(target_os = "macos")]
+const MENU_SERVICES_LABEL: &str = "Services";
+#[cfg(target_os = "macos")]
+const MENU_HIDE_ID: &str = "menu_hide";
+#[cfg(target_os = "macos")]
+const MENU_HIDE_LABEL: &str = "Hide Telegram";
+#[cfg(target_os = "macos")]
+const MENU_HIDE_OTHERS_ID: &str = "menu_hide_others";
+#[cfg(target_os = "macos")]
+const MENU_HIDE_OTHERS_LABEL: &str = "Hide Others";
+#[cfg(target_os = "macos")]
+const MENU_SHOW_ALL_ID: &str = "menu_show_all";
+#[cfg(target_os = "macos")]
+const MENU_SHOW_ALL_LABEL: &str = "Show All";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MenuBarSettings {
+  // Only Linux shows the menu bar inside windows and can hide it
+  pub is_visible: bool,
+}
+
+impl Default for MenuBarSettings {
+  fn default() -> Self {
+    Self { is_visible: true }
+  }
+}
+
+fn item(
+  app: &AppHandle,
+  id: &str,
+  default: &str,
+  accelerator: Option<&str>,
+) -> tauri::Result<MenuItem<Wry>> {
+  MenuItem::with_id(app, id, translated_label(id, default), true, accelerator)
+}
+
+// Shares its label with the tray menu
+fn quit_item(app: &AppHandle) -> tauri::Result<MenuItem<Wry>> {
+  MenuItem::with_id(
+    app,
+    MENU_QUIT_ID,
+    translated_label(MENU_ITEM_QUIT_ID, MENU_ITEM_QUIT_LABEL),
+    true,
+    Some("CmdOrCtrl+Q"),
+  )
+}
+
+fn about_item(app: &AppHandle) -> tauri::Result<PredefinedMenuItem<Wry>> {
+  let metadata = AboutMetadata {
+    name: Some(DEFAULT_WINDOW_TITLE.to_string()),
+    version: Some(env!("CARGO_PKG_VERSION").to_string()),
+    ..Default::default()
+  };
+  PredefinedMenuItem::about(
+    app,
+    Some(&translated_label(MENU_ABOUT_ID, MENU_ABOUT_LABEL)),
+    Some(metadata),
+  )
+}
+
+// The app menu, first on macOS, holds About, Settings and Quit there
+#[cfg(target_os = "macos")]
+fn app_submenu(app: &AppHandle) -> tauri::Result<Option<Submenu<Wry>>> {
+  let separator = || PredefinedMenuItem::separator(app);
+  Submenu::with_items(
+    app,
+    DEFAULT_WINDOW_TITLE,
+    true,
+    &[
+      &about_item(app)?,
+      &item(app, MENU_CHECK_UPDATES_ID, MENU_CHECK_UPDATES_LABEL, None)?,
+      &separator()?,
+      &item(
+        app,
+        MENU_SETTINGS_ID,
+        MENU_SETTINGS_LABEL,
+        Some("CmdOrCtrl+,"),
+      )?,
+      &separator()?,
+      &PredefinedMenuItem::services(
+        app,
+        Some(&translated_label(MENU_SERVICES_ID, MENU_SERVICES_LABEL)),
+      )?,
+      &separator()?,
+      &PredefinedMenuItem::hide(app, Some(&translated_label(MENU_HIDE_ID, MENU_HIDE_LABEL)))?,
+      &PredefinedMenuItem::hide_others(
+        app,
+        Some(&translated_label(
+          MENU_HIDE_OTHERS_ID,
+          MENU_HIDE_OTHERS_LABEL,
+        )),
+      )?,
+      &PredefinedMenuItem::show_all(
+        app,
+        Some(&translated_label(MENU_SHOW_ALL_ID, MENU_SHOW_ALL_LABEL)),
+      )?,
+      &separator()?,
+      &quit_item(app)?,
+    ],
+  )
+  .map(Some)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn app_submenu(_app: &AppHandle) -> tauri::Result<Option<Submenu<Wry>>> {
+  Ok(None)
+}
+
+fn file_submenu(app: &AppHandle) -> tauri::Result<Submenu<Wry>> {
+  let submenu = Submenu::with_id_and_items(
+    app,
+    MENU_FILE_ID,
+    translated_label(MENU_FILE_ID, MENU_FILE_LABEL),
+    true,
+    &[
+      &item(
+        app,
+        MENU_NEW_WINDOW_ID,
+        MENU_NEW_WINDOW_LABEL,
+        Some("CmdOrCtrl+Shift+N"),
+      )?,
+      &PredefinedMenuItem::separator(app)?,
+      &PredefinedMenuItem::close_window(
+        app,
+        Some(&translated_label(
+          MENU_CLOSE_WINDOW_ID,
+          MENU_CLOSE_WINDOW_LABEL,
+        )),
+      )?,
+    ],
+  )?;
+
+  // Settings and Quit live in the app menu on macOS
+  #[cfg(not(target_os = "macos"))]
+  {
+    submenu.insert(
+      &item(
+        app,
+        MENU_SETTINGS_ID,
+        MENU_SETTINGS_LABEL,
+        Some("CmdOrCtrl+,"),
+      )?,
+      1,
+    )?;
+    submenu.append(&PredefinedMenuItem::separator(app)?)?;
+    submenu.append(&quit_item(app)?)?;
+  }
+
+  Ok(submenu)
+}
+
+fn edit_submenu(app: &AppHandle) -> tauri::Result<Submenu<Wry>> {
+  Submenu::with_id_and_items(
+    app,
+    MENU_EDIT_ID,
+    translated_label(MENU_EDIT_ID, MENU_EDIT_LABEL),
+    true,
+    &[
+      &PredefinedMenuItem::undo(app, Some(&translated_label(MENU_UNDO_ID, MENU_UNDO_LABEL)))?,
+      &PredefinedMenuItem::redo(app, Some(&translated_label(MENU_REDO_ID, MENU_REDO_LABEL)))?,
+      &PredefinedMenuItem::separator(app)?,
+      &PredefinedMenuItem::cut(app, Some(&translated_label(MENU_CUT_ID, MENU_CUT_LABEL)))?,
+      &PredefinedMenuItem::copy(app, Some(&translated_label(MENU_COPY_ID, MENU_COPY_LABEL)))?,
+      &PredefinedMenuItem::paste(
+        app,
+        Some(&translated_label(MENU_PASTE_ID, MENU_PASTE_LABEL)),
+      )?,
+      &PredefinedMenuItem::select_all(
+        app,
+        Some(&translated_label(MENU_SELECT_ALL_ID, MENU_SELECT_ALL_LABEL)),
+      )?,
+    ],
+  )
+}
+
+fn view_submenu(app: &AppHandle) -> tauri::Result<Submenu<Wry>> {
+  // Zoom keys are handled in the webview by `zoom::shortcuts_script`, so these items have no
+  // accelerators that would catch the same keys
+  let submenu = Submenu::with_id_and_items(
+    app,
+    MENU_VIEW_ID,
+    translated_label(MENU_VIEW_ID, MENU_VIEW_LABEL),
+    true,
+    &[
+      &item(app, MENU_ZOOM_IN_ID, MENU_ZOOM_IN_LABEL, None)?,
+      &item(app, MENU_ZOOM_OUT_ID, MENU_ZOOM_OUT_LABEL, None)?,
+      &item(app, MENU_ZOOM_RESET_ID, MENU_ZOOM_RESET_LABEL, None)?,
+      &PredefinedMenuItem::separator(app)?,
+    ],
+  )?;
+
+  #[cfg(target_os = "macos")]
+  submenu.append(&PredefinedMenuItem::fullscreen(
+    app,
+    Some(&translated_label(MENU_FULLSCREEN_ID, MENU_FULLSCREEN_LABEL)),
+  )?)?;
+  #[cfg(not(target_os = "macos"))]
+  submenu.append(&item(
+    app,
+    MENU_FULLSCREEN_ID,
+    MENU_FULLSCREEN_LABEL,
+    Some("F11"),
+  )?)?;
+
+  #[cfg(target_os = "linux")]
+  submenu.append(&item(
+    app,
+    MENU_HIDE_MENU_BAR_ID,
+    MENU_HIDE_MENU_BAR_LABEL,
+    None,
+  )?)?;
+
+  Ok(submenu)
+}
+
+fn window_submenu(app: &AppHandle) -> tauri::Result<Submenu<Wry>> {
+  let submenu = Submenu::with_id_and_items(
+    app,
+    MENU_WINDOW_ID,
+    translated_label(MENU_WINDOW_ID, MENU_WINDOW_LABEL),
+    true,
+    &[
+      &PredefinedMenuItem::minimize(
+        app,
+        Some(&translated_label(MENU_MINIMIZE_ID, MENU_MINIMIZE_LABEL)),
+      )?,
+      &PredefinedMenuItem::maximize(
+        app,
+        Some(&translated_label(MENU_MAXIMIZE_ID, MENU_MAXIMIZE_LABEL)),
+      )?,
+    ],
+  )?;
+
+  // Lets macOS list the open windows in it
+  #[cfg(target_os = "macos")]
+  submenu.set_as_windows_menu_for_nsapp()?;
+
+  Ok(submenu)
+}
+
+fn help_submenu(app: &AppHandle) -> tauri::Result<Submenu<Wry>> {
+  let submenu = Submenu::with_id_and_items(
+    app,
+    MENU_HELP_ID,
+    translated_label(MENU_HELP_ID, MENU_HELP_LABEL),
+    true,
+    &[&item(app, MENU_FAQ_ID, MENU_FAQ_LABEL, None)?],
+  )?;
+
+  // About and Check for Updates live in the app menu on macOS
+  #[cfg(not(target_os = "macos"))]
+  {
+    submenu.append(&PredefinedMenuItem::separator(app)?)?;
+    submenu.append(&item(
+      app,
+      MENU_CHECK_UPDATES_ID,
+      MENU_CHECK_UPDATES_LABEL,
+      None,
+    )?)?;
+    submenu.append(&about_item(app)?)?;
+  }
+
+  #[cfg(target_os = "macos")]
+  submenu.set_as_help_menu_for_nsapp()?;
+
+  Ok(submenu)
+}
+
+fn build(app: &AppHandle) -> tauri::Result<Menu<Wry>> {
+  let menu = Menu::with_items(
+    app,
+    &[
+      &file_submenu(app)?,
+      &edit_submenu(app)?,
+      &view_submenu(app)?,
+      &window_submenu(app)?,
+      &help_submenu(app)?,
+    ],
+  )?;
+  if let Some(app_submenu) = app_submenu(app)? {
+    menu.prepend(&app_submenu)?;
+  }
+
+  Ok(menu)
+}
+
+/// Sets the menu bar of every window, or the app menu on macOS.
+pub fn init(app: &AppHandle) -> tauri::Result<()> {
+  app.set_menu(build(app)?)?;
+  Ok(())
+}
+
+/// Rebuilds the menu with the current translations. Predefined items have no stable ids to find
+/// them by, so they cannot be relabeled in place like the tray menu.
+pub fn refresh_labels(app: &AppHandle) {
+  if let Err(err) = init(app) {
+    log::error!("Failed to rebuild menu: {:?}", err);
+  }
+}
+
+/// Hides the menu bar of a new window if the user chose so.
+#[cfg(target_os = "linux")]
+pub fn apply_to_window(window: &WebviewWindow) {
+  if !crate::settings::get().menu_bar.is_visible {
+    window.hide_menu().unwrap_or_default();
+  }
+}
+
+/// Shows or hides the menu bar of every window after the settings change.
+#[cfg(target_os = "linux")]
+pub fn apply(app: &AppHandle, settings: &MenuBarSettings) {
+  use tauri::Manager;
+
+  for window in app.webview_windows().values() {
+    let result = if settings.is_visible {
+      window.show_menu()
+    } else {
+      window.hide_menu()
+    };
+    result.unwrap_or_default();
+  }
+  crate::tray::set_show_menu_bar_enabled(!settings.is_visible);
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn apply(_app: &AppHandle, _settings: &MenuBarSettings) {}
+
+// Actions the frontend runs itself, sent with the `menu-action` event
+fn emit_action(app: &AppHandle, action: &str) {
+  let window = crate::window::primary_window(app);
+  crate::window::emit_when_ready(
+    app,
+    window.as_ref().map(|window| window.label()),
+    "menu-action",
+    json!({ "action": action }),
+  );
+}
+
+fn with_primary_window(app: &AppHandle, action: impl FnOnce(&WebviewWindow)) {
+  if let Some(window) = crate::window::primary_window(app) {
+    action(&window);
+  }
+}
+
+/// Runs the action of a clicked menu bar item. Events of other menus come here too and are ignored.
+pub fn handle(app: &AppHandle, event: &MenuEvent) {
+  match event.id().as_ref() {
+    MENU_NEW_WINDOW_ID => {
+      if let Err(err) = crate::open_new_window(app.clone(), BASE_URL.to_string()) {
+        log::error!("Failed to open window from menu: {:?}", err);
+      }
+    }
+    MENU_SETTINGS_ID => emit_action(app, "openSettings"),
+    MENU_QUIT_ID => app.exit(0),
+    MENU_ZOOM_IN_ID => with_primary_window(app, |window| {
+      crate::zoom::zoom_in(app, window).unwrap_or_default();
+    }),
+    MENU_ZOOM_OUT_ID => with_primary_window(app, |window| {
+      crate::zoom::zoom_out(app, window).unwrap_or_default();
+    }),
+    MENU_ZOOM_RESET_ID => with_primary_window(app, |window| {
+      crate::zoom::zoom_reset(app, window).unwrap_or_default();
+    }),
+    MENU_FULLSCREEN_ID => with_primary_window(app, |window| {
+      let is_fullscreen = window.is_fullscreen().unwrap_or(false);
+      window.set_fullscreen(!is_fullscreen).unwrap_or_default();
+    }),
+    MENU_HIDE_MENU_BAR_ID => {
+      crate::settings::modify(app, |settings| settings.menu_bar.is_visible = false);
+    }
+    MENU_CHECK_UPDATES_ID => crate::updater::check_now(app),
+    MENU_FAQ_ID => emit_action(app, "openFaq"),
+    _ => {}
+  }
+}
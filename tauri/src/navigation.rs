@@ -0,0 +1,308 @@
+use std::sync::LazyLock;
+
+use serde::Serialize;
+use tauri::webview::{NewWindowFeatures, NewWindowResponse, PageLoadEvent};
+use tauri::{AppHandle, Manager, WebviewWindow, Wry};
+use url::Url;
+
+use crate::BASE_URL;
+
+// Its origin is the only one windows may show, as pages there get the whole IPC surface
+static APP_URL: LazyLock<Option<Url>> = LazyLock::new(|| Url::parse(BASE_URL).ok());
+
+// WebKitGTK and WKWebView also ask about navigations inside frames, without telling them apart from
+// the window's own, while WebView2 only asks about the window's
+const HAS_FRAME_NAVIGATIONS: bool = cfg!(not(target_os = "windows"));
+
+/// Why a window could not be opened, sent to the frontend as `{ kind, ... }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum OpenWindowError {
+  InvalidUrl { reason: String },
+  ForeignOrigin { origin: String },
+  // Userinfo could make a URL of another host look like one of the app
+  Credentials,
+  Window { reason: String },
+}
+
+impl From<tauri::Error> for OpenWindowError {
+  fn from(err: tauri::Error) -> Self {
+    OpenWindowError::Window {
+      reason: err.to_string(),
+    }
+  }
+}
+
+/// Resolves the URL of a new window against the app URL, so paths relative to it work.
+/// Anything off the app origin is rejected.
+pub fn resolve_window_url(url: &str) -> Result<Url, OpenWindowError> {
+  let app_url = APP_URL
+    .as_ref()
+    .ok_or_else(|| OpenWindowError::InvalidUrl {
+      reason: format!("Invalid app URL {:?}", BASE_URL),
+    })?;
+  let resolved = app_url
+    .join(url)
+    .map_err(|err| OpenWindowError::InvalidUrl {
+      reason: err.to_string(),
+    })?;
+
+  if !resolved.username().is_empty() || resolved.password().is_some() {
+    return Err(OpenWindowError::Credentials);
+  }
+  if resolved.origin() != app_url.origin() {
+    return Err(OpenWindowError::ForeignOrigin {
+      origin: resolved.origin().ascii_serialization(),
+    });
+  }
+
+  Ok(resolved)
+}
+
+/// Whether the URL belongs to the app origin. `blob:` URLs never do, as they do not outlive the page.
+pub fn is_app_url(url: &Url) -> bool {
+  url.scheme() != "blob" && APP_URL.as_ref().map(Url::origin) == Some(url.origin())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Decision {
+  Allow,
+  OpenExternally,
+  Block,
+}
+
+fn decide(url: &Url) -> Decision {
+  // `blob:` URLs have the origin of the page that created them, opaque origins match nothing
+  if APP_URL.as_ref().map(Url::origin) == Some(url.origin()) {
+    return Decision::Allow;
+  }
+
+  match url.scheme() {
+    "http" | "https" => Decision::OpenExternally,
+    // Empty documents of frames, which have no content to run
+    "about" if matches!(url.path(), "blank" | "srcdoc") => Decision::Allow,
+    // `javascript:`, `file:`, `data:` and anything unknown
+    _ => Decision::Block,
+  }
+}
+
+/// Decision for a navigation the engine asks about. Frames may show external pages the CSP allows,
+/// so those load when the navigation may be one of a frame, and `handle_page_load` keeps the
+/// window itself on the app.
+fn decide_navigation(url: &Url, may_be_frame: bool) -> Decision {
+  match decide(url) {
+    Decision::OpenExternally if may_be_frame => Decision::Allow,
+    decision => decision,
+  }
+}
+
+fn open_in_browser(app: &AppHandle, url: &Url) {
+  log::info!("Opening {} in the browser", url);
+  // Goes through the same checks as links the frontend opens, suspicious domains included
+  if let Err(err) = crate::external_url::open_external_url(app, url.as_str()) {
+    log::error!("Failed to open {} in the browser: {:?}", url, err);
+  }
+}
+
+fn log_blocked(url: &Url) {
+  // The rest of `javascript:` and `data:` URLs may be long or sensitive
+  match url.scheme() {
+    "file" => log::warn!("Blocked navigation to {}", url),
+    scheme => log::warn!("Blocked navigation to a {}: URL", scheme),
+  }
+}
+
+/// Decides whether a window or one of its frames may navigate to the URL. External pages open in
+/// the browser instead, where the engine only asks about the window.
+pub fn handle_navigation(app: &AppHandle, url: &Url) -> bool {
+  match decide_navigation(url, HAS_FRAME_NAVIGATIONS) {
+    Decision::Allow => true,
+    Decision::OpenExternally => {
+      open_in_browser(app, url);
+      false
+    }
+    Decision::Block => {
+      log_blocked(url);
+      false
+    }
+  }
+}
+
+/// Keeps windows on the app where `handle_navigation` lets external pages load for frames. A window
+/// that starts loading one opens it in the browser and goes back to the last app page instead.
+pub fn handle_page_load(window: &WebviewWindow, url: &Url, event: PageLoadEvent) {
+  if event != PageLoadEvent::Started {
+    if is_app_url(url) {
+      crate::window::set_app_url(window.label(), url.clone());
+    }
+    return;
+  }
+  if decide(url) != Decision::OpenExternally {
+    return;
+  }
+
+  open_in_browser(window.app_handle(), url);
+  let Some(app_url) = crate::window::app_url(window.label()) else {
+    return;
+  };
+  // Navigated later, like new windows are opened, as the engine is still in its load handler
+  let window_handle = window.clone();
+  let result = window.run_on_main_thread(move || {
+    if let Err(err) = window_handle.navigate(app_url) {
+      log::error!("Failed to return to the app: {:?}", err);
+    }
+  });
+  if let Err(err) = result {
+    log::error!("Failed to schedule returning to the app: {:?}", err);
+  }
+}
+
+/// Handles pages opening windows themselves. The engine never creates them: pages of the app
+/// get a regular app window, external ones open in the browser.
+pub fn handle_new_window(
+  app: &AppHandle,
+  url: Url,
+  _features: NewWindowFeatures,
+) -> NewWindowResponse<Wry> {
+  match decide(&url) {
+    // Blank windows would only be filled by their opener, which the app does not do
+    Decision::Allow if url.scheme() == "about" => {}
+    // Opened later, as creating a window while the engine waits for the response can deadlock
+    Decision::Allow => {
+      let app_handle = app.clone();
+      let result = app.run_on_main_thread(move || {
+        if let Err(err) = crate::open_new_window(app_handle, url.to_string()) {
+          log::error!("Failed to open window for {}: {:?}", url, err);
+        }
+      });
+      if let Err(err) = result {
+        log::error!("Failed to schedule new window: {:?}", err);
+      }
+    }
+    Decision::OpenExternally => open_in_browser(app, &url),
+    Decision::Block => log_blocked(&url),
+  }
+
+  NewWindowResponse::Deny
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn app_url() -> Url {
+    APP_URL.clone().expect("the app URL parses")
+  }
+
+  // Host and port of the app, as they appear in a URL
+  fn app_authority() -> String {
+    let app_url = app_url();
+    let host = app_url.host_str().expect("the app URL has a host");
+    match app_url.port() {
+      Some(port) => format!("{}:{}", host, port),
+      None => host.to_string(),
+    }
+  }
+
+  // Swaps the first Latin letter with a Cyrillic one that looks the same
+  fn look_alike(host: &str) -> String {
+    const HOMOGLYPHS: &[(char, char)] =
+      &[('a', 'а'), ('c', 'с'), ('e', 'е'), ('o', 'о'), ('p', 'р')];
+
+    let (index, replacement) = host
+      .char_indices()
+      .find_map(|(index, char)| {
+        HOMOGLYPHS
+          .iter()
+          .find(|(latin, _)| *latin == char)
+          .map(|(_, cyrillic)| (index, *cyrillic))
+      })
+      .expect("the app host has a letter with a homoglyph");
+    let mut look_alike = host.to_string();
+    look_alike.replace_range(index..index + 1, &replacement.to_string());
+    look_alike
+  }
+
+  #[test]
+  fn lets_frames_load_external_pages() {
+    let frame_url = Url::parse("https://www.youtube.com/embed/dQw4w9WgXcQ").unwrap();
+    assert_eq!(decide_navigation(&frame_url, true), Decision::Allow);
+    assert_eq!(
+      decide_navigation(&frame_url, false),
+      Decision::OpenExternally
+    );
+
+    let app_frame_url = app_url().join("/a/").unwrap();
+    assert_eq!(decide_navigation(&app_frame_url, true), Decision::Allow);
+    assert_eq!(decide_navigation(&app_frame_url, false), Decision::Allow);
+
+    // Frames get no more than windows otherwise
+    for url in [
+      "javascript:alert(1)",
+      "data:text/html,<script>alert(1)</script>",
+      "file:///etc/passwd",
+    ] {
+      let url = Url::parse(url).unwrap();
+      assert_eq!(decide_navigation(&url, true), Decision::Block);
+    }
+  }
+
+  #[test]
+  fn resolves_relative_paths_against_the_app_url() {
+    let resolved = resolve_window_url("?account=2").unwrap();
+    assert_eq!(resolved.origin(), app_url().origin());
+    assert_eq!(resolved.query(), Some("account=2"));
+
+    let resolved = resolve_window_url("/a/#/settings").unwrap();
+    assert_eq!(resolved.origin(), app_url().origin());
+    assert_eq!(resolved.path(), "/a/");
+  }
+
+  #[test]
+  fn rejects_protocol_relative_urls_of_other_hosts() {
+    assert_eq!(
+      resolve_window_url("//evil.com"),
+      Err(OpenWindowError::ForeignOrigin {
+        origin: format!("{}://evil.com", app_url().scheme()),
+      }),
+    );
+  }
+
+  #[test]
+  fn rejects_credentials() {
+    let app_scheme = app_url().scheme().to_string();
+    let authority = app_authority();
+
+    let user_at_app = format!("{}://user@{}/", app_scheme, authority);
+    assert_eq!(
+      resolve_window_url(&user_at_app),
+      Err(OpenWindowError::Credentials)
+    );
+
+    let app_at_evil = format!("{}://{}@evil.com/", app_scheme, authority);
+    assert_eq!(
+      resolve_window_url(&app_at_evil),
+      Err(OpenWindowError::Credentials)
+    );
+  }
+
+  #[test]
+  fn rejects_javascript_urls() {
+    assert!(matches!(
+      resolve_window_url("javascript:alert(1)"),
+      Err(OpenWindowError::ForeignOrigin { .. })
+    ));
+  }
+
+  #[test]
+  fn rejects_look_alikes_of_the_app_host() {
+    let app_url = app_url();
+    let host = look_alike(app_url.host_str().unwrap());
+    let url = format!("{}://{}/", app_url.scheme(), host);
+
+    let Err(OpenWindowError::ForeignOrigin { origin }) = resolve_window_url(&url) else {
+      panic!("{} resolved to the app origin", url);
+    };
+    assert!(origin.contains("xn--"), "{} is not punycode", origin);
+  }
+}
@@ -0,0 +1,460 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::{Duration, SystemTime};
+
+use ab_glyph::{Font, FontArc, FontVec, OutlinedGlyph, PxScale, ScaleFont, point};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageReader, Limits, Rgba, RgbaImage};
+use serde::Deserialize;
+use tauri::{AppHandle, Manager};
+
+const AVATAR_SIZE: u32 = 128;
+const AVATAR_CACHE_DIR: &str = "avatars";
+// Bump when rendering changes, so stale cached files are not reused
+const AVATAR_CACHE_VERSION: u32 = 1;
+const AVATAR_CACHE_MAX_FILES: usize = 1000;
+const AVATAR_CACHE_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+// Photos come from the webview, so decoding is bounded well above any real avatar
+const MAX_PHOTO_BYTES: usize = 10 * 1024 * 1024;
+const MAX_PHOTO_DIMENSION: u32 = 4096;
+const MAX_PHOTO_ALLOC: u64 = 128 * 1024 * 1024;
+const MAX_PHOTO_HASH_LENGTH: usize = 128;
+
+const AVATAR_TEXT_COLOR: Rgba<u8> = Rgba([0xFF, 0xFF, 0xFF, 0xFF]);
+
+// Classic peer colors, indexed by the peer color key used by the web app
+const AVATAR_COLORS: [Rgba<u8>; 7] = [
+  Rgba([0xFF, 0x84, 0x5E, 0xFF]), // red
+  Rgba([0xFE, 0xBB, 0x5B, 0xFF]), // orange
+  Rgba([0xB6, 0x94, 0xF9, 0xFF]), // violet
+  Rgba([0x9A, 0xD1, 0x64, 0xFF]), // green
+  Rgba([0x5B, 0xCB, 0xE3, 0xFF]), // cyan
+  Rgba([0x5C, 0xAF, 0xFA, 0xFF]), // blue
+  Rgba([0xFF, 0x8A, 0xAC, 0xFF]), // pink
+];
+
+// System fonts tried in order for characters Roboto has no outline for (CJK, emoji, etc.)
+#[cfg(target_os = "linux")]
+const FALLBACK_FONT_PATHS: &[&str] = &[
+  "/usr/share/fonts/opentype/noto/NotoSansCJK-Bold.ttc",
+  "/usr/share/fonts/noto-cjk/NotoSansCJK-Bold.ttc",
+  "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Bold.ttc",
+  "/usr/share/fonts/truetype/dejavu/DejaVuSans-Bold.ttf",
+  "/usr/share/fonts/TTF/DejaVuSans-Bold.ttf",
+  "/usr/share/fonts/truetype/noto/NotoEmoji-Regular.ttf",
+  "/usr/share/fonts/noto/NotoEmoji-Regular.ttf",
+];
+
+#[cfg(target_os = "macos")]
+const FALLBACK_FONT_PATHS: &[&str] = &[
+  "/System/Library/Fonts/PingFang.ttc",
+  "/System/Library/Fonts/Supplemental/Arial Unicode.ttf",
+];
+
+#[cfg(windows)]
+const FALLBACK_FONT_PATHS: &[&str] = &[
+  "C:\\Windows\\Fonts\\msyhbd.ttc",
+  "C:\\Windows\\Fonts\\arialbd.ttf",
+  "C:\\Windows\\Fonts\\seguiemj.ttf",
+  "C:\\Windows\\Fonts\\seguisym.ttf",
+];
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+const FALLBACK_FONT_PATHS: &[&str] = &[];
+
+static FONTS: LazyLock<Vec<FontArc>> = LazyLock::new(|| {
+  let mut fonts = vec![FontArc::try_from_slice(crate::ROBOTO_BOLD_FONT).expect("Invalid font")];
+
+  for path in FALLBACK_FONT_PATHS {
+    let Ok(data) = std::fs::read(path) else {
+      continue;
+    };
+
+    // Only the first face of a collection is used: Japanese Bold for Noto Sans CJK, Bold for
+    // Microsoft YaHei, but Regular for PingFang, which has no bolder first face
+    match FontVec::try_from_vec_and_index(data, 0) {
+      Ok(font) => fonts.push(FontArc::new(font)),
+      Err(err) => log::warn!("Failed to load fallback font {}: {:?}", path, err),
+    }
+  }
+
+  fonts
+});
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AvatarPayload {
+  pub name: String,
+  pub color_index: Option<usize>,
+  // Stable id of the photo, whose bytes arrive separately through `cache_photo`
+  pub photo_hash: Option<String>,
+}
+
+fn cache_dir(app: &AppHandle) -> Result<PathBuf, String> {
+  Ok(
+    app
+      .path()
+      .app_cache_dir()
+      .map_err(|err| err.to_string())?
+      .join(AVATAR_CACHE_DIR),
+  )
+}
+
+/// Renders a photo sent by the frontend as raw bytes, so notifications of the chat can use it.
+pub fn cache_photo(app: &AppHandle, photo_hash: &str, photo: &[u8]) -> Result<(), String> {
+  if photo_hash.is_empty()
+    || photo_hash.len() > MAX_PHOTO_HASH_LENGTH
+    || !photo_hash.chars().all(|c| c.is_ascii_graphic())
+  {
+    return Err(format!("Invalid photo hash {:?}", photo_hash));
+  }
+
+  let cache_dir = cache_dir(app)?;
+  let path = cache_dir.join(format!("{}.png", photo_cache_key(photo_hash)));
+  if path.exists() {
+    touch(&path);
+    return Ok(());
+  }
+
+  let photo = decode_photo(photo)?
+    .resize_to_fill(AVATAR_SIZE, AVATAR_SIZE, FilterType::Lanczos3)
+    .to_rgba8();
+  std::fs::create_dir_all(&cache_dir).map_err(|err| err.to_string())?;
+  save_atomically(&crop_circle(&photo), &path)
+}
+
+/// Returns the path of the rendered avatar: the cached photo if there is one, initials otherwise.
+pub fn render_cached(app: &AppHandle, avatar: &AvatarPayload) -> Result<PathBuf, String> {
+  let cache_dir = cache_dir(app)?;
+
+  if let Some(photo_hash) = &avatar.photo_hash {
+    let path = cache_dir.join(format!("{}.png", photo_cache_key(photo_hash)));
+    if path.exists() {
+      touch(&path);
+      return Ok(path);
+    }
+  }
+
+  let path = cache_dir.join(format!("{}.png", initials_cache_key(avatar)));
+  if path.exists() {
+    touch(&path);
+    return Ok(path);
+  }
+
+  let image = render_initials(avatar);
+  std::fs::create_dir_all(&cache_dir).map_err(|err| err.to_string())?;
+  save_atomically(&image, &path)?;
+
+  Ok(path)
+}
+
+fn save_atomically(image: &RgbaImage, path: &Path) -> Result<(), String> {
+  // Notification servers may read the file at any moment, so never expose a partial write
+  let tmp_path = path.with_extension("png.tmp");
+  image
+    .save_with_format(&tmp_path, image::ImageFormat::Png)
+    .map_err(|err| err.to_string())?;
+  std::fs::rename(&tmp_path, path).map_err(|err| err.to_string())
+}
+
+// The age limit of the cache counts from the last use
+fn touch(path: &Path) {
+  let result = std::fs::File::options()
+    .write(true)
+    .open(path)
+    .and_then(|file| file.set_modified(SystemTime::now()));
+  if let Err(err) = result {
+    log::warn!("Failed to touch cached avatar {:?}: {:?}", path, err);
+  }
+}
+
+/// Removes cached avatars unused for longer than the age limit, then the least recently used
+/// ones beyond the count limit.
+pub fn prune_cache(app: &AppHandle) {
+  let Ok(cache_dir) = cache_dir(app) else {
+    return;
+  };
+  let Ok(entries) = std::fs::read_dir(&cache_dir) else {
+    return;
+  };
+
+  let now = SystemTime::now();
+  let mut files: Vec<(PathBuf, SystemTime)> = entries
+    .flatten()
+    .filter_map(|entry| {
+      let modified = entry
+        .metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()?;
+      Some((entry.path(), modified))
+    })
+    .collect();
+  files.sort_by(|a, b| b.1.cmp(&a.1));
+
+  for (index, (path, modified)) in files.iter().enumerate() {
+    let age = now.duration_since(*modified).unwrap_or_default();
+    if index >= AVATAR_CACHE_MAX_FILES || age > AVATAR_CACHE_MAX_AGE {
+      if let Err(err) = std::fs::remove_file(path) {
+        log::warn!("Failed to remove cached avatar {:?}: {:?}", path, err);
+      }
+    }
+  }
+}
+
+fn decode_photo(bytes: &[u8]) -> Result<DynamicImage, String> {
+  if bytes.len() > MAX_PHOTO_BYTES {
+    return Err(format!("Photo is too large: {} bytes", bytes.len()));
+  }
+
+  let mut limits = Limits::default();
+  limits.max_image_width = Some(MAX_PHOTO_DIMENSION);
+  limits.max_image_height = Some(MAX_PHOTO_DIMENSION);
+  limits.max_alloc = Some(MAX_PHOTO_ALLOC);
+
+  let mut reader = ImageReader::new(Cursor::new(bytes))
+    .with_guessed_format()
+    .map_err(|err| err.to_string())?;
+  reader.limits(limits);
+  reader.decode().map_err(|err| err.to_string())
+}
+
+fn photo_cache_key(photo_hash: &str) -> String {
+  cache_key(&format!("photo:{}", photo_hash))
+}
+
+fn initials_cache_key(avatar: &AvatarPayload) -> String {
+  cache_key(&format!(
+    "initials:{}:{}",
+    get_initials(&avatar.name),
+    avatar.color_index.unwrap_or(0)
+  ))
+}
+
+fn cache_key(source: &str) -> String {
+  format!(
+    "{:016x}",
+    fnv1a(format!("{}:{}:{}", AVATAR_CACHE_VERSION, AVATAR_SIZE, source).as_bytes())
+  )
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+  bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+    (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+  })
+}
+
+fn render_initials(avatar: &AvatarPayload) -> RgbaImage {
+  let color = AVATAR_COLORS[avatar.color_index.unwrap_or(0) % AVATAR_COLORS.len()];
+  let mut image = crop_circle(&RgbaImage::from_pixel(AVATAR_SIZE, AVATAR_SIZE, color));
+  draw_initials(&mut image, &get_initials(&avatar.name));
+  image
+}
+
+/// Applies a circular alpha mask with an anti-aliased edge.
+fn crop_circle(source: &RgbaImage) -> RgbaImage {
+  let size = source.width().min(source.height());
+  let radius = size as f32 / 2.0;
+
+  RgbaImage::from_fn(size, size, |x, y| {
+    let dx = x as f32 + 0.5 - radius;
+    let dy = y as f32 + 0.5 - radius;
+    let coverage = (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0);
+
+    let mut pixel = *source.get_pixel(x, y);
+    pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+    pixel
+  })
+}
+
+fn font_for_char(c: char) -> Option<&'static FontArc> {
+  FONTS.iter().find(|font| {
+    let glyph_id = font.glyph_id(c);
+    glyph_id.0 != 0 && font.outline(glyph_id).is_some()
+  })
+}
+
+fn draw_initials(image: &mut RgbaImage, initials: &str) {
+  // Matches the web avatar, where the font size is half of the avatar size minus a few pixels
+  let scale = PxScale::from(AVATAR_SIZE as f32 / 2.0 - 4.0);
+
+  let mut caret = 0.0;
+  let mut glyphs: Vec<OutlinedGlyph> = Vec::new();
+  // Outline fonts have no ligatures for joined emoji, so only the leading character is drawn
+  let mut is_joined = false;
+  for c in initials.chars() {
+    let was_joined = std::mem::replace(&mut is_joined, c == '\u{200D}');
+    if was_joined || is_grapheme_extender(c) {
+      continue;
+    }
+
+    let Some(font) = font_for_char(c) else {
+      continue;
+    };
+
+    let scaled_font = font.as_scaled(scale);
+    let glyph_id = font.glyph_id(c);
+    let glyph = glyph_id.with_scale_and_position(scale, point(caret, scaled_font.ascent()));
+    caret += scaled_font.h_advance(glyph_id);
+
+    if let Some(outlined) = font.outline_glyph(glyph) {
+      glyphs.push(outlined);
+    }
+  }
+
+  let Some(first) = glyphs.first() else {
+    return;
+  };
+
+  // Center the actual ink bounds, so glyphs without descenders are not shifted up
+  let mut bounds = first.px_bounds();
+  for glyph in &glyphs[1..] {
+    let glyph_bounds = glyph.px_bounds();
+    bounds.min.x = bounds.min.x.min(glyph_bounds.min.x);
+    bounds.min.y = bounds.min.y.min(glyph_bounds.min.y);
+    bounds.max.x = bounds.max.x.max(glyph_bounds.max.x);
+    bounds.max.y = bounds.max.y.max(glyph_bounds.max.y);
+  }
+
+  let offset_x = ((AVATAR_SIZE as f32 - bounds.width()) / 2.0 - bounds.min.x).round() as i32;
+  let offset_y = ((AVATAR_SIZE as f32 - bounds.height()) / 2.0 - bounds.min.y).round() as i32;
+
+  for glyph in glyphs {
+    let glyph_bounds = glyph.px_bounds();
+    glyph.draw(|gx, gy, coverage| {
+      let x = glyph_bounds.min.x as i32 + gx as i32 + offset_x;
+      let y = glyph_bounds.min.y as i32 + gy as i32 + offset_y;
+      if x < 0 || y < 0 || x >= image.width() as i32 || y >= image.height() as i32 {
+        return;
+      }
+
+      let pixel = image.get_pixel_mut(x as u32, y as u32);
+      let coverage = coverage.clamp(0.0, 1.0);
+      for channel in 0..3 {
+        pixel[channel] = (pixel[channel] as f32 * (1.0 - coverage)
+          + AVATAR_TEXT_COLOR[channel] as f32 * coverage)
+          .round() as u8;
+      }
+    });
+  }
+}
+
+/// Characters that only modify the preceding one: joiners, variation selectors, skin tones, marks.
+fn is_grapheme_extender(c: char) -> bool {
+  matches!(
+    c,
+    '\u{200D}'
+      | '\u{FE00}'..='\u{FE0F}'
+      | '\u{1F3FB}'..='\u{1F3FF}'
+      | '\u{0300}'..='\u{036F}'
+      | '\u{E0020}'..='\u{E007F}'
+  )
+}
+
+fn is_regional_indicator(c: char) -> bool {
+  ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+/// First character of a word, keeping emoji sequences and combining marks together.
+fn first_grapheme(word: &str) -> String {
+  let mut chars = word.chars().peekable();
+  let Some(first) = chars.next() else {
+    return String::new();
+  };
+
+  let mut grapheme = String::from(first);
+  while let Some(&next) = chars.peek() {
+    if next == '\u{200D}' {
+      grapheme.push(next);
+      chars.next();
+      if let Some(joined) = chars.next() {
+        grapheme.push(joined);
+      }
+    } else if is_grapheme_extender(next)
+      || (is_regional_indicator(first)
+        && grapheme.len() == first.len_utf8()
+        && is_regional_indicator(next))
+    {
+      grapheme.push(next);
+      chars.next();
+    } else {
+      break;
+    }
+  }
+
+  grapheme
+}
+
+/// Up to two initials, following the web app's `getFirstLetters`.
+pub fn get_initials(name: &str) -> String {
+  const PUNCTUATION: &str = ".,!@#$%^&*()_+=-`~[]/\\{}:\"|<>?";
+
+  let cleaned: String = name.chars().filter(|c| !PUNCTUATION.contains(*c)).collect();
+  cleaned
+    .split_whitespace()
+    .take(2)
+    .map(|word| first_grapheme(word).to_uppercase())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn initials_of_latin_names() {
+    assert_eq!(get_initials("John Doe"), "JD");
+    assert_eq!(get_initials("john"), "J");
+    assert_eq!(get_initials("Anna Maria Smith"), "AM");
+    assert_eq!(get_initials("  spaced   out  "), "SO");
+    assert_eq!(get_initials(""), "");
+  }
+
+  #[test]
+  fn initials_skip_punctuation() {
+    assert_eq!(get_initials("Dr. (Who)"), "DW");
+    assert_eq!(get_initials("@channel_name"), "C");
+    assert_eq!(get_initials("!!!"), "");
+  }
+
+  #[test]
+  fn initials_of_non_latin_names() {
+    assert_eq!(get_initials("иван петров"), "ИП");
+    assert_eq!(get_initials("Ελένη Παππά"), "ΕΠ");
+    assert_eq!(get_initials("李小龍"), "李");
+    assert_eq!(get_initials("محمد علی"), "مع");
+    // Combining marks stay with their letter
+    assert_eq!(get_initials("e\u{301}mile"), "E\u{301}");
+  }
+
+  #[test]
+  fn initials_keep_emoji_sequences_together() {
+    assert_eq!(get_initials("🚀 Launch"), "🚀L");
+    assert_eq!(get_initials("👍🏽 Thumbs"), "👍🏽T");
+    assert_eq!(
+      get_initials("👨\u{200D}👩\u{200D}👧 Family"),
+      "👨\u{200D}👩\u{200D}👧F"
+    );
+    assert_eq!(get_initials("🇩🇪🇫🇷 Flags"), "🇩🇪F");
+    assert_eq!(get_initials("❤\u{FE0F} Love"), "❤\u{FE0F}L");
+  }
+
+  #[test]
+  fn photos_beyond_the_limits_are_rejected() {
+    let mut png = Vec::new();
+    RgbaImage::new(MAX_PHOTO_DIMENSION + 1, 1)
+      .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+      .unwrap();
+    assert!(decode_photo(&png).is_err());
+
+    let mut png = Vec::new();
+    RgbaImage::new(16, 16)
+      .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+      .unwrap();
+    assert!(decode_photo(&png).is_ok());
+
+    assert!(decode_photo(&vec![0; MAX_PHOTO_BYTES + 1]).is_err());
+    assert!(decode_photo(b"not an image").is_err());
+  }
+}
@@ -0,0 +1,129 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+// At most this many notifications are shown within the window, across all chats
+pub const RATE_LIMIT_COUNT: usize = 5;
+pub const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default, Clone)]
+pub struct ChatGroup {
+  // Server id of the notification currently on screen for this chat
+  pub notification_id: Option<u32>,
+  pub count: u32,
+}
+
+/// Groups notifications per chat, so a burst replaces one notification instead of stacking.
+#[derive(Debug, Default)]
+pub struct ChatGroups {
+  groups: HashMap<String, ChatGroup>,
+}
+
+impl ChatGroups {
+  /// Counts a new message for the chat and returns the updated group.
+  pub fn add(&mut self, chat_id: &str) -> ChatGroup {
+    let group = self.groups.entry(chat_id.to_string()).or_default();
+    group.count += 1;
+    group.clone()
+  }
+
+  pub fn set_notification_id(&mut self, chat_id: &str, id: u32) {
+    if let Some(group) = self.groups.get_mut(chat_id) {
+      group.notification_id = Some(id);
+    }
+  }
+
+  pub fn get(&self, chat_id: &str) -> Option<ChatGroup> {
+    self.groups.get(chat_id).cloned()
+  }
+
+  pub fn remove(&mut self, chat_id: &str) -> Option<ChatGroup> {
+    self.groups.remove(chat_id)
+  }
+
+  pub fn remove_by_notification_id(&mut self, id: u32) {
+    self
+      .groups
+      .retain(|_, group| group.notification_id != Some(id));
+  }
+}
+
+/// Sliding-window limiter shared by every chat.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+  shown_at: VecDeque<Instant>,
+}
+
+impl RateLimiter {
+  fn expire(&mut self, now: Instant) {
+    while self
+      .shown_at
+      .front()
+      .is_some_and(|shown_at| now.duration_since(*shown_at) >= RATE_LIMIT_WINDOW)
+    {
+      self.shown_at.pop_front();
+    }
+  }
+
+  pub fn try_acquire(&mut self, now: Instant) -> bool {
+    self.expire(now);
+    if self.shown_at.len() >= RATE_LIMIT_COUNT {
+      return false;
+    }
+
+    self.shown_at.push_back(now);
+    true
+  }
+
+  /// How long until `try_acquire` succeeds again.
+  pub fn time_until_available(&mut self, now: Instant) -> Duration {
+    self.expire(now);
+    if self.shown_at.len() < RATE_LIMIT_COUNT {
+      return Duration::ZERO;
+    }
+
+    self
+      .shown_at
+      .front()
+      .map(|shown_at| (*shown_at + RATE_LIMIT_WINDOW).saturating_duration_since(now))
+      .unwrap_or_default()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn groups_count_messages_per_chat() {
+    let mut groups = ChatGroups::default();
+    groups.add("1");
+    groups.set_notification_id("1", 7);
+    assert_eq!(groups.add("1").count, 2);
+    assert_eq!(groups.add("2").count, 1);
+
+    groups.remove_by_notification_id(7);
+    assert!(groups.get("1").is_none());
+    assert!(groups.get("2").is_some());
+  }
+
+  #[test]
+  fn rate_limiter_frees_up_after_the_window() {
+    let mut limiter = RateLimiter::default();
+    let start = Instant::now();
+    for _ in 0..RATE_LIMIT_COUNT {
+      assert!(limiter.try_acquire(start));
+    }
+    assert!(!limiter.try_acquire(start));
+
+    let later = start + Duration::from_secs(4);
+    assert_eq!(
+      limiter.time_until_available(later),
+      RATE_LIMIT_WINDOW - Duration::from_secs(4)
+    );
+    assert_eq!(
+      limiter.time_until_available(start + RATE_LIMIT_WINDOW),
+      Duration::ZERO
+    );
+    assert!(limiter.try_acquire(start + RATE_LIMIT_WINDOW));
+  }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use tauri::AppHandle;
+use zbus::MatchRule;
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::message::Type as MessageType;
+use zbus::zvariant::Value;
+
+use super::NotificationPayload;
+use crate::DEFAULT_WINDOW_TITLE;
+use crate::tray::translated_label;
+
+const NOTIFICATIONS_INTERFACE: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+
+const CAPABILITY_ACTIONS: &str = "actions";
+const CAPABILITY_INLINE_REPLY: &str = "inline-reply";
+
+const ACTION_DEFAULT: &str = "default";
+const ACTION_INLINE_REPLY: &str = "inline-reply";
+
+// Server-defined expiration
+const EXPIRE_TIMEOUT_DEFAULT: i32 = -1;
+const EXPIRE_TIMEOUT_STATUS: i32 = 3000;
+
+// Notifications are replaced in place through their server id
+pub const CAN_REPLACE: bool = true;
+
+pub const NOTIFICATION_OPEN_ID: &str = "notification_open";
+pub const NOTIFICATION_OPEN_LABEL: &str = "Open";
+pub const NOTIFICATION_REPLY_ID: &str = "notification_reply";
+pub const NOTIFICATION_REPLY_LABEL: &str = "Reply";
+pub const NOTIFICATION_REPLY_PLACEHOLDER_ID: &str = "notification_reply_placeholder";
+pub const NOTIFICATION_REPLY_PLACEHOLDER_LABEL: &str = "Write a message…";
+
+#[zbus::proxy(
+  interface = "org.freedesktop.Notifications",
+  default_service = "org.freedesktop.Notifications",
+  default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+  fn get_capabilities(&self) -> zbus::Result<Vec<String>>;
+
+  #[allow(clippy::too_many_arguments)]
+  fn notify(
+    &self,
+    app_name: &str,
+    replaces_id: u32,
+    app_icon: &str,
+    summary: &str,
+    body: &str,
+    actions: &[&str],
+    hints: HashMap<&str, &Value<'_>>,
+    expire_timeout: i32,
+  ) -> zbus::Result<u32>;
+
+  fn close_notification(&self, id: u32) -> zbus::Result<()>;
+}
+
+struct NotificationServer {
+  connection: Connection,
+  capabilities: Vec<String>,
+}
+
+impl NotificationServer {
+  fn connect() -> zbus::Result<Self> {
+    let connection = Connection::session()?;
+    let capabilities = NotificationsProxyBlocking::new(&connection)?.get_capabilities()?;
+    log::info!("Notification server capabilities: {:?}", capabilities);
+
+    Ok(Self {
+      connection,
+      capabilities,
+    })
+  }
+
+  fn has_capability(&self, capability: &str) -> bool {
+    self.capabilities.iter().any(|c| c == capability)
+  }
+
+  fn proxy(&self) -> zbus::Result<NotificationsProxyBlocking<'_>> {
+    NotificationsProxyBlocking::new(&self.connection)
+  }
+}
+
+static SERVER: OnceLock<Option<NotificationServer>> = OnceLock::new();
+
+fn server() -> Option<&'static NotificationServer> {
+  SERVER
+    .get_or_init(|| match NotificationServer::connect() {
+      Ok(server) => Some(server),
+      Err(err) => {
+        log::error!("Failed to connect to notification server: {:?}", err);
+        None
+      }
+    })
+    .as_ref()
+}
+
+pub fn init(app: &AppHandle) {
+  let Some(server) = server() else {
+    return;
+  };
+
+  let connection = server.connection.clone();
+  let app = app.clone();
+  std::thread::spawn(move || {
+    if let Err(err) = listen_signals(&app, &connection) {
+      log::error!("Notification signal listener stopped: {:?}", err);
+    }
+  });
+}
+
+fn listen_signals(app: &AppHandle, connection: &Connection) -> zbus::Result<()> {
+  let rule = MatchRule::builder()
+    .msg_type(MessageType::Signal)
+    .interface(NOTIFICATIONS_INTERFACE)?
+    .path(NOTIFICATIONS_PATH)?
+    .build();
+
+  for message in MessageIterator::for_match_rule(rule, connection, None)? {
+    let message = message?;
+    let header = message.header();
+    let Some(member) = header.member() else {
+      continue;
+    };
+
+    match member.as_str() {
+      "ActionInvoked" => {
+        if let Ok((id, action)) = message.body().deserialize::<(u32, String)>() {
+          if action == ACTION_DEFAULT {
+            super::handle_activation(app, id);
+          }
+        }
+      }
+      "NotificationReplied" => {
+        if let Ok((id, text)) = message.body().deserialize::<(u32, String)>() {
+          super::handle_reply(app, id, text);
+        }
+      }
+      "NotificationClosed" => {
+        if let Ok((id, _reason)) = message.body().deserialize::<(u32, u32)>() {
+          super::handle_closed(id);
+        }
+      }
+      _ => {}
+    }
+  }
+
+  Ok(())
+}
+
+pub fn show(
+  _app: &AppHandle,
+  payload: &NotificationPayload,
+  replaces_id: Option<u32>,
+  icon: Option<&Path>,
+) -> Result<Option<u32>, String> {
+  let Some(server) = server() else {
+    return Err("Notification server is not available".to_string());
+  };
+
+  let open_label = translated_label(NOTIFICATION_OPEN_ID, NOTIFICATION_OPEN_LABEL);
+  let reply_label = translated_label(NOTIFICATION_REPLY_ID, NOTIFICATION_REPLY_LABEL);
+  let placeholder = translated_label(
+    NOTIFICATION_REPLY_PLACEHOLDER_ID,
+    NOTIFICATION_REPLY_PLACEHOLDER_LABEL,
+  );
+
+  // Servers without inline reply still get the default action, which opens the chat to reply there
+  let mut actions: Vec<&str> = Vec::new();
+  if server.has_capability(CAPABILITY_ACTIONS) {
+    actions.extend([ACTION_DEFAULT, open_label.as_str()]);
+  }
+  if server.has_capability(CAPABILITY_INLINE_REPLY) {
+    actions.extend([ACTION_INLINE_REPLY, reply_label.as_str()]);
+  }
+
+  let category = Value::from("im.received");
+  let placeholder = Value::from(placeholder.as_str());
+  let suppress_sound = Value::from(true);
+  let mut hints: HashMap<&str, &Value<'_>> = HashMap::new();
+  hints.insert("category", &category);
+  hints.insert("x-kde-reply-placeholder-text", &placeholder);
+  if payload.is_silent {
+    hints.insert("suppress-sound", &suppress_sound);
+  }
+
+  let icon = icon.map(|path| path.to_string_lossy()).unwrap_or_default();
+
+  server
+    .proxy()
+    .and_then(|proxy| {
+      proxy.notify(
+        DEFAULT_WINDOW_TITLE,
+        replaces_id.unwrap_or(0),
+        &icon,
+        &payload.title,
+        &payload.body,
+        &actions,
+        hints,
+        EXPIRE_TIMEOUT_DEFAULT,
+      )
+    })
+    .map(Some)
+    .map_err(|err| err.to_string())
+}
+
+/// Replaces the notification in place with a short-lived status line.
+pub fn replace_with_status(id: u32, title: &str, status: &str) {
+  let Some(server) = server() else {
+    return;
+  };
+
+  let transient = Value::from(true);
+  let mut hints: HashMap<&str, &Value<'_>> = HashMap::new();
+  hints.insert("transient", &transient);
+
+  let result = server.proxy().and_then(|proxy| {
+    proxy.notify(
+      DEFAULT_WINDOW_TITLE,
+      id,
+      "",
+      title,
+      status,
+      &[],
+      hints,
+      EXPIRE_TIMEOUT_STATUS,
+    )
+  });
+
+  if let Err(err) = result {
+    log::warn!("Failed to confirm notification reply: {:?}", err);
+  }
+}
+
+pub fn close(id: u32) {
+  let Some(server) = server() else {
+    return;
+  };
+
+  if let Err(err) = server
+    .proxy()
+    .and_then(|proxy| proxy.close_notification(id))
+  {
+    log::warn!("Failed to close notification {}: {:?}", id, err);
+  }
+}
@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Instant;
+
+use serde::Deserialize;
+use serde_json::json;
+use tauri::ipc::{InvokeBody, Request};
+use tauri::{AppHandle, Manager};
+
+use crate::tray::translated_label;
+use crate::window::emit_when_ready;
+
+mod avatar;
+use avatar::AvatarPayload;
+
+mod coalesce;
+use coalesce::{ChatGroup, ChatGroups, RateLimiter};
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "linux")]
+use linux as platform;
+
+// Label constants, translatable through `set_menu_translations`
+pub const NOTIFICATION_REPLY_SENT_ID: &str = "notification_reply_sent";
+pub const NOTIFICATION_REPLY_SENT_LABEL: &str = "Reply sent";
+pub const NOTIFICATION_REPLY_FAILED_ID: &str = "notification_reply_failed";
+pub const NOTIFICATION_REPLY_FAILED_LABEL: &str = "Failed to send reply";
+pub const NOTIFICATION_NEW_MESSAGES_ID: &str = "notification_new_messages";
+pub const NOTIFICATION_NEW_MESSAGES_LABEL: &str = "{count} new messages";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationPayload {
+  pub chat_id: String,
+  pub message_id: Option<i64>,
+  pub title: String,
+  pub body: String,
+  #[serde(default)]
+  pub is_silent: bool,
+  pub avatar: Option<AvatarPayload>,
+}
+
+#[derive(Debug, Clone)]
+struct NotificationTarget {
+  window_label: String,
+  chat_id: String,
+  message_id: Option<i64>,
+  title: String,
+  has_pending_reply: bool,
+}
+
+static NOTIFICATION_TARGETS: LazyLock<Mutex<HashMap<u32, NotificationTarget>>> =
+  LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static CHAT_GROUPS: LazyLock<Mutex<ChatGroups>> =
+  LazyLock::new(|| Mutex::new(ChatGroups::default()));
+
+static RATE_LIMITER: LazyLock<Mutex<RateLimiter>> =
+  LazyLock::new(|| Mutex::new(RateLimiter::default()));
+
+// Latest rate-limited notification of each chat, with the label of its window
+static DEFERRED_NOTIFICATIONS: LazyLock<Mutex<HashMap<String, (String, NotificationPayload)>>> =
+  LazyLock::new(|| Mutex::new(HashMap::new()));
+static IS_FLUSH_SCHEDULED: AtomicBool = AtomicBool::new(false);
+
+// Header of `cache_notification_avatar` naming the photo sent as the raw request body
+const AVATAR_PHOTO_HASH_HEADER: &str = "Tauri-Photo-Hash";
+
+pub fn init(app: &AppHandle) {
+  platform::init(app);
+
+  let app = app.clone();
+  std::thread::spawn(move || avatar::prune_cache(&app));
+}
+
+/// Renders an avatar photo the frontend sent as raw bytes, for notifications naming its hash.
+pub fn cache_avatar_photo(app: &AppHandle, request: &Request) -> Result<(), String> {
+  let InvokeBody::Raw(photo) = request.body() else {
+    return Err("Expected the photo as the raw request body".to_string());
+  };
+  let photo_hash = request
+    .headers()
+    .get(AVATAR_PHOTO_HASH_HEADER)
+    .and_then(|value| value.to_str().ok())
+    .ok_or_else(|| format!("Missing {} header", AVATAR_PHOTO_HASH_HEADER))?;
+
+  avatar::cache_photo(app, photo_hash, photo)
+}
+
+pub fn show_notification(
+  app: &AppHandle,
+  window_label: &str,
+  payload: NotificationPayload,
+) -> Result<(), String> {
+  if crate::dnd::is_active() || crate::window::is_chat_active_in_focused_window(&payload.chat_id) {
+    return Ok(());
+  }
+
+  let group = CHAT_GROUPS
+    .lock()
+    .map(|mut groups| groups.add(&payload.chat_id))
+    .unwrap_or_default();
+
+  let is_allowed = RATE_LIMITER
+    .lock()
+    .map(|mut limiter| limiter.try_acquire(Instant::now()))
+    .unwrap_or(true);
+  if !is_allowed {
+    // The message stays counted, and the latest one of the chat is shown once the limit frees up
+    defer(app, window_label, payload);
+    return Ok(());
+  }
+
+  display(app, window_label, payload, &group)
+}
+
+fn display(
+  app: &AppHandle,
+  window_label: &str,
+  mut payload: NotificationPayload,
+  group: &ChatGroup,
+) -> Result<(), String> {
+  if let Ok(mut deferred) = DEFERRED_NOTIFICATIONS.lock() {
+    deferred.remove(&payload.chat_id);
+  }
+
+  // Without a notification to replace, summaries would stack up as separate ones
+  if group.count > 1 && platform::CAN_REPLACE {
+    payload.body = translated_label(
+      NOTIFICATION_NEW_MESSAGES_ID,
+      NOTIFICATION_NEW_MESSAGES_LABEL,
+    )
+    .replace("{count}", &group.count.to_string());
+  }
+
+  let icon = payload
+    .avatar
+    .as_ref()
+    .and_then(|avatar| match avatar::render_cached(app, avatar) {
+      Ok(path) => Some(path),
+      Err(err) => {
+        log::warn!("Failed to render notification avatar: {}", err);
+        None
+      }
+    });
+
+  // Only servers that report back (actions, replies) return an id worth tracking
+  let Some(id) = platform::show(app, &payload, group.notification_id, icon.as_deref())? else {
+    return Ok(());
+  };
+
+  if let Ok(mut groups) = CHAT_GROUPS.lock() {
+    groups.set_notification_id(&payload.chat_id, id);
+  }
+
+  if let Ok(mut targets) = NOTIFICATION_TARGETS.lock() {
+    targets.insert(
+      id,
+      NotificationTarget {
+        window_label: window_label.to_string(),
+        chat_id: payload.chat_id,
+        message_id: payload.message_id,
+        title: payload.title,
+        has_pending_reply: false,
+      },
+    );
+  }
+
+  Ok(())
+}
+
+/// Keeps the latest rate-limited notification of the chat and schedules a flush for when the
+/// limit frees up, so it is not lost if no other message for the chat arrives.
+fn defer(app: &AppHandle, window_label: &str, payload: NotificationPayload) {
+  if let Ok(mut deferred) = DEFERRED_NOTIFICATIONS.lock() {
+    deferred.insert(payload.chat_id.clone(), (window_label.to_string(), payload));
+  }
+
+  if IS_FLUSH_SCHEDULED.swap(true, Ordering::SeqCst) {
+    return;
+  }
+
+  let delay = RATE_LIMITER
+    .lock()
+    .map(|mut limiter| limiter.time_until_available(Instant::now()))
+    .unwrap_or_default();
+  let app = app.clone();
+  std::thread::spawn(move || {
+    std::thread::sleep(delay);
+    IS_FLUSH_SCHEDULED.store(false, Ordering::SeqCst);
+    flush_deferred(&app);
+  });
+}
+
+fn flush_deferred(app: &AppHandle) {
+  let deferred: Vec<(String, NotificationPayload)> = DEFERRED_NOTIFICATIONS
+    .lock()
+    .map(|mut deferred| deferred.drain().map(|(_, entry)| entry).collect())
+    .unwrap_or_default();
+
+  for (window_label, payload) in deferred {
+    // The chat may have been opened or muted in the meantime
+    if crate::dnd::is_active() || crate::window::is_chat_active_in_focused_window(&payload.chat_id)
+    {
+      continue;
+    }
+    let Some(group) = CHAT_GROUPS
+      .lock()
+      .ok()
+      .and_then(|groups| groups.get(&payload.chat_id))
+    else {
+      continue;
+    };
+
+    let is_allowed = RATE_LIMITER
+      .lock()
+      .map(|mut limiter| limiter.try_acquire(Instant::now()))
+      .unwrap_or(true);
+    if !is_allowed {
+      defer(app, &window_label, payload);
+      continue;
+    }
+
+    if let Err(err) = display(app, &window_label, payload, &group) {
+      log::warn!("Failed to show deferred notification: {}", err);
+    }
+  }
+}
+
+/// Dismisses the grouped notification of a chat the user is now looking at.
+pub fn clear_chat(chat_id: &str) {
+  if let Ok(mut deferred) = DEFERRED_NOTIFICATIONS.lock() {
+    deferred.remove(chat_id);
+  }
+
+  let Some(group) = CHAT_GROUPS
+    .lock()
+    .ok()
+    .and_then(|mut groups| groups.remove(chat_id))
+  else {
+    return;
+  };
+
+  let Some(id) = group.notification_id else {
+    return;
+  };
+
+  let has_pending_reply = NOTIFICATION_TARGETS
+    .lock()
+    .map(|targets| {
+      targets
+        .get(&id)
+        .is_some_and(|target| target.has_pending_reply)
+    })
+    .unwrap_or(false);
+
+  // A notification awaiting reply confirmation is still needed to show the result
+  if !has_pending_reply {
+    handle_closed(id);
+    platform::close(id);
+  }
+}
+
+/// Reports the outcome of a reply back to the notification server.
+pub fn confirm_reply(id: u32, success: bool) {
+  let Some(target) = NOTIFICATION_TARGETS
+    .lock()
+    .ok()
+    .and_then(|mut targets| targets.remove(&id))
+  else {
+    return;
+  };
+
+  if let Ok(mut groups) = CHAT_GROUPS.lock() {
+    groups.remove(&target.chat_id);
+  }
+
+  let status = if success {
+    translated_label(NOTIFICATION_REPLY_SENT_ID, NOTIFICATION_REPLY_SENT_LABEL)
+  } else {
+    translated_label(
+      NOTIFICATION_REPLY_FAILED_ID,
+      NOTIFICATION_REPLY_FAILED_LABEL,
+    )
+  };
+
+  platform::replace_with_status(id, &target.title, &status);
+}
+
+fn get_target(id: u32) -> Option<NotificationTarget> {
+  NOTIFICATION_TARGETS
+    .lock()
+    .ok()
+    .and_then(|targets| targets.get(&id).cloned())
+}
+
+pub(crate) fn handle_reply(app: &AppHandle, id: u32, text: String) {
+  let target = NOTIFICATION_TARGETS.lock().ok().and_then(|mut targets| {
+    let target = targets.get_mut(&id)?;
+    // Keep the target until the frontend confirms, even if the server closes the notification
+    target.has_pending_reply = true;
+    Some(target.clone())
+  });
+
+  let Some(target) = target else {
+    log::warn!("Reply for unknown notification {}", id);
+    return;
+  };
+
+  emit_when_ready(
+    app,
+    Some(&target.window_label),
+    "notification-reply",
+    json!({
+      "id": id,
+      "chatId": target.chat_id,
+      "messageId": target.message_id,
+      "text": text,
+    }),
+  );
+}
+
+pub(crate) fn handle_activation(app: &AppHandle, id: u32) {
+  let Some(target) = get_target(id) else {
+    return;
+  };
+
+  if let Some(window) = app.get_webview_window(&target.window_label) {
+    window.unminimize().unwrap_or_default();
+    window.show().unwrap_or_default();
+    window.set_focus().unwrap_or_default();
+  }
+
+  emit_when_ready(
+    app,
+    Some(&target.window_label),
+    "notification-click",
+    json!({
+      "chatId": target.chat_id,
+      "messageId": target.message_id,
+    }),
+  );
+}
+
+pub(crate) fn handle_closed(id: u32) {
+  if let Ok(mut groups) = CHAT_GROUPS.lock() {
+    groups.remove_by_notification_id(id);
+  }
+
+  if let Ok(mut targets) = NOTIFICATION_TARGETS.lock() {
+    if targets
+      .get(&id)
+      .is_some_and(|target| !target.has_pending_reply)
+    {
+      targets.remove(&id);
+    }
+  }
+}
+
+// -------------------------------------------------------------------------------------------------
+// Fallback for platforms without a notification server we can talk to directly
+// -------------------------------------------------------------------------------------------------
+#[cfg(not(target_os = "linux"))]
+mod platform {
+  use std::path::Path;
+
+  use tauri::AppHandle;
+  use tauri_plugin_notification::NotificationExt;
+
+  use super::NotificationPayload;
+
+  // Toasts shown through the plugin cannot be replaced or closed later
+  pub const CAN_REPLACE: bool = false;
+
+  pub fn init(_app: &AppHandle) {}
+
+  pub fn show(
+    app: &AppHandle,
+    payload: &NotificationPayload,
+    _replaces_id: Option<u32>,
+    icon: Option<&Path>,
+  ) -> Result<Option<u32>, String> {
+    let mut builder = app
+      .notification()
+      .builder()
+      .title(&payload.title)
+      .body(&payload.body);
+    if let Some(icon) = icon {
+      builder = builder.icon(icon.to_string_lossy());
+    }
+    if payload.is_silent {
+      builder = builder.silent();
+    }
+
+    builder.show().map_err(|err| err.to_string())?;
+
+    Ok(None)
+  }
+
+  pub fn replace_with_status(_id: u32, _title: &str, _status: &str) {}
+
+  pub fn close(_id: u32) {}
+}
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+
+use tauri::AppHandle;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+use url::Url;
+
+use super::{ProxyKind, ProxySettings};
+use crate::tray::translated_label;
+
+// Label constants, translatable through `set_menu_translations`
+const PROXY_LINK_TITLE_ID: &str = "proxy_link_title";
+const PROXY_LINK_TITLE_LABEL: &str = "Proxy";
+const PROXY_LINK_CONFIRM_ID: &str = "proxy_link_confirm";
+const PROXY_LINK_CONFIRM_LABEL: &str = "Connect through this proxy? All windows will be reloaded.";
+const PROXY_LINK_INVALID_ID: &str = "proxy_link_invalid";
+const PROXY_LINK_INVALID_LABEL: &str = "This proxy link cannot be used.";
+const PROXY_LINK_SERVER_ID: &str = "proxy_link_server";
+const PROXY_LINK_SERVER_LABEL: &str = "Server";
+const PROXY_LINK_PORT_ID: &str = "proxy_link_port";
+const PROXY_LINK_PORT_LABEL: &str = "Port";
+const PROXY_LINK_USERNAME_ID: &str = "proxy_link_username";
+const PROXY_LINK_USERNAME_LABEL: &str = "Username";
+const PROXY_LINK_CONNECT_ID: &str = "proxy_link_connect";
+const PROXY_LINK_CONNECT_LABEL: &str = "Connect";
+const PROXY_LINK_CANCEL_ID: &str = "proxy_link_cancel";
+const PROXY_LINK_CANCEL_LABEL: &str = "Cancel";
+
+const TELEGRAM_HOSTS: &[&str] = &["t.me", "telegram.me", "telegram.dog"];
+// SOCKS5 sends credentials with a one byte length
+const MAX_CREDENTIAL_LENGTH: usize = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LinkKind {
+  // `proxy` links, always MTProto
+  MtProto,
+  Socks,
+}
+
+fn link_kind(url: &Url) -> Option<LinkKind> {
+  let name = match url.scheme() {
+    "tg" => url.host_str().unwrap_or_else(|| url.path()),
+    "http" | "https" => {
+      let host = url.host_str()?;
+      if !TELEGRAM_HOSTS.contains(&host.trim_start_matches("www.")) {
+        return None;
+      }
+      url.path().trim_matches('/')
+    }
+    _ => return None,
+  };
+
+  if name.eq_ignore_ascii_case("proxy") {
+    Some(LinkKind::MtProto)
+  } else if name.eq_ignore_ascii_case("socks") {
+    Some(LinkKind::Socks)
+  } else {
+    None
+  }
+}
+
+fn parse_params(url: &Url, kind: LinkKind) -> Result<ProxySettings, String> {
+  let mut params = HashMap::new();
+  for (key, value) in url.query_pairs() {
+    if params.insert(key.to_string(), value.to_string()).is_some() {
+      return Err(format!("Parameter {:?} is repeated", key));
+    }
+  }
+  let mut param = |key: &str| params.remove(key).filter(|value| !value.is_empty());
+
+  let host = param("server").ok_or("Missing server")?;
+  let port = param("port").ok_or("Missing port")?;
+  let port = port
+    .parse::<u16>()
+    .ok()
+    .filter(|parsed| *parsed != 0 && !port.starts_with('+'))
+    .ok_or_else(|| format!("Invalid port {:?}", port))?;
+
+  if kind == LinkKind::MtProto {
+    param("secret").ok_or("Missing secret")?;
+    return Err("MTProto proxies cannot be used by the webview".to_string());
+  }
+
+  let username = param("user");
+  let password = param("pass");
+  if [&username, &password]
+    .into_iter()
+    .flatten()
+    .any(|value| value.len() > MAX_CREDENTIAL_LENGTH)
+  {
+    return Err(format!(
+      "Credentials are longer than {} bytes",
+      MAX_CREDENTIAL_LENGTH
+    ));
+  }
+
+  let proxy = ProxySettings {
+    kind: ProxyKind::Socks5,
+    host,
+    port,
+    username,
+    password,
+    bypass: Vec::new(),
+  };
+  proxy.validate()?;
+  Ok(proxy)
+}
+
+/// Recognizes `tg://proxy` and `tg://socks` links and their `https://t.me/` forms.
+/// Returns `None` for other links, which belong to the frontend.
+pub fn parse(url: &Url) -> Option<Result<ProxySettings, String>> {
+  let kind = link_kind(url)?;
+  Some(parse_params(url, kind))
+}
+
+fn details(proxy: &ProxySettings) -> String {
+  let mut lines = vec![
+    format!(
+      "{}: {}",
+      translated_label(PROXY_LINK_SERVER_ID, PROXY_LINK_SERVER_LABEL),
+      proxy.host
+    ),
+    format!(
+      "{}: {}",
+      translated_label(PROXY_LINK_PORT_ID, PROXY_LINK_PORT_LABEL),
+      proxy.port
+    ),
+  ];
+  if let Some(username) = &proxy.username {
+    lines.push(format!(
+      "{}: {}",
+      translated_label(PROXY_LINK_USERNAME_ID, PROXY_LINK_USERNAME_LABEL),
+      username
+    ));
+  }
+  lines.join("\n")
+}
+
+/// Asks the user to confirm the proxy of a link, then stores it, which recreates the windows.
+pub fn handle(app: &AppHandle, link: Result<ProxySettings, String>) {
+  let title = translated_label(PROXY_LINK_TITLE_ID, PROXY_LINK_TITLE_LABEL);
+  let proxy = match link.and_then(|proxy| super::validate_for_engine(&proxy).map(|()| proxy)) {
+    Ok(proxy) => proxy,
+    Err(reason) => {
+      log::warn!("Rejected proxy link: {}", reason);
+      app
+        .dialog()
+        .message(format!(
+          "{}\n\n{}",
+          translated_label(PROXY_LINK_INVALID_ID, PROXY_LINK_INVALID_LABEL),
+          reason
+        ))
+        .title(title)
+        .kind(MessageDialogKind::Error)
+        .show(|_| {});
+      return;
+    }
+  };
+
+  let app_handle = app.clone();
+  app
+    .dialog()
+    .message(format!(
+      "{}\n\n{}",
+      translated_label(PROXY_LINK_CONFIRM_ID, PROXY_LINK_CONFIRM_LABEL),
+      details(&proxy)
+    ))
+    .title(title)
+    .kind(MessageDialogKind::Info)
+    .buttons(MessageDialogButtons::OkCancelCustom(
+      translated_label(PROXY_LINK_CONNECT_ID, PROXY_LINK_CONNECT_LABEL),
+      translated_label(PROXY_LINK_CANCEL_ID, PROXY_LINK_CANCEL_LABEL),
+    ))
+    .show(move |is_confirmed| {
+      if !is_confirmed {
+        return;
+      }
+
+      log::info!("Using proxy {} from link", proxy.endpoint());
+      crate::settings::modify(&app_handle, |settings| {
+        // Links have no bypass list, so the current one is kept
+        let bypass = settings
+          .network
+          .proxy
+          .take()
+          .map(|current| current.bypass)
+          .unwrap_or_default();
+        settings.network.proxy = Some(ProxySettings { bypass, ..proxy });
+      });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse_link(link: &str) -> Option<Result<ProxySettings, String>> {
+    parse(&Url::parse(link).unwrap())
+  }
+
+  #[test]
+  fn parses_socks_links() {
+    let proxy = parse_link("tg://socks?server=127.0.0.1&port=1080&user=alice&pass=p%40ss")
+      .unwrap()
+      .unwrap();
+    assert_eq!(proxy.kind, ProxyKind::Socks5);
+    assert_eq!(proxy.host, "127.0.0.1");
+    assert_eq!(proxy.port, 1080);
+    assert_eq!(proxy.username.as_deref(), Some("alice"));
+    assert_eq!(proxy.password.as_deref(), Some("p@ss"));
+
+    for link in [
+      "https://t.me/socks?server=proxy.example.com&port=443",
+      "https://www.telegram.me/socks/?server=proxy.example.com&port=443",
+      "tg:socks?server=proxy.example.com&port=443",
+      "tg://SOCKS?server=proxy.example.com&port=443",
+    ] {
+      let proxy = parse_link(link).unwrap().unwrap();
+      assert_eq!(
+        (proxy.host.as_str(), proxy.port),
+        ("proxy.example.com", 443)
+      );
+    }
+  }
+
+  #[test]
+  fn leaves_other_links_to_the_frontend() {
+    for link in [
+      "https://example.com/socks?server=127.0.0.1&port=1080",
+      "https://t.me.example.com/socks?server=127.0.0.1&port=1080",
+      "https://t.me/durov",
+      "tg://resolve?domain=durov",
+      "socks5://127.0.0.1:1080",
+    ] {
+      assert!(parse_link(link).is_none(), "{}", link);
+    }
+  }
+
+  #[test]
+  fn rejects_missing_or_empty_servers() {
+    for link in [
+      "tg://socks?port=1080",
+      "tg://socks?server=&port=1080",
+      "tg://socks?server=exa%20mple.com&port=1080",
+    ] {
+      assert!(parse_link(link).unwrap().is_err(), "{}", link);
+    }
+  }
+
+  #[test]
+  fn rejects_invalid_ports() {
+    for port in ["", "0", "65536", "-1", "+80", "80a", "http", "1e3"] {
+      let link = format!("tg://socks?server=127.0.0.1&port={}", port);
+      assert!(parse_link(&link).unwrap().is_err(), "{}", port);
+    }
+    assert!(parse_link("tg://socks?server=127.0.0.1").unwrap().is_err());
+    assert!(
+      parse_link("tg://socks?server=127.0.0.1&port=65535")
+        .unwrap()
+        .is_ok()
+    );
+  }
+
+  #[test]
+  fn rejects_over_long_credentials() {
+    let long = "a".repeat(MAX_CREDENTIAL_LENGTH + 1);
+    let fitting = "a".repeat(MAX_CREDENTIAL_LENGTH);
+    let link = |user: &str, pass: &str| {
+      format!(
+        "tg://socks?server=127.0.0.1&port=1080&user={}&pass={}",
+        user, pass
+      )
+    };
+
+    assert!(parse_link(&link(&long, "pass")).unwrap().is_err());
+    assert!(parse_link(&link("user", &long)).unwrap().is_err());
+    assert!(parse_link(&link(&fitting, &fitting)).unwrap().is_ok());
+    // Without a username, a password cannot be sent
+    assert!(
+      parse_link("tg://socks?server=127.0.0.1&port=1080&pass=secret")
+        .unwrap()
+        .is_err()
+    );
+  }
+
+  #[test]
+  fn rejects_mtproto_and_repeated_parameters() {
+    assert!(
+      parse_link("tg://proxy?server=127.0.0.1&port=443&secret=ee00")
+        .unwrap()
+        .is_err()
+    );
+    assert!(
+      parse_link("tg://socks?server=127.0.0.1&server=evil.com&port=1080")
+        .unwrap()
+        .is_err()
+    );
+  }
+}
@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+use url::{Host, Url};
+
+pub mod link;
+mod probe;
+
+#[cfg(target_os = "macos")]
+const MACOS_PROXY_MIN_VERSION: u64 = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProxyKind {
+  Http,
+  Socks5,
+}
+
+impl ProxyKind {
+  fn scheme(self) -> &'static str {
+    match self {
+      ProxyKind::Http => "http",
+      ProxyKind::Socks5 => "socks5",
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxySettings {
+  #[serde(rename = "type")]
+  pub kind: ProxyKind,
+  pub host: String,
+  pub port: u16,
+  #[serde(default)]
+  pub username: Option<String>,
+  // Left out when missing, so a redacted copy sent back as a patch keeps the stored password
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub password: Option<String>,
+  // Hosts reached directly, e.g. `localhost` or `*.example.com`
+  #[serde(default)]
+  pub bypass: Vec<String>,
+}
+
+impl ProxySettings {
+  fn parsed_host(&self) -> Result<Host, String> {
+    // IPv6 addresses are accepted with or without brackets
+    let host = if self.host.contains(':') && !self.host.starts_with('[') {
+      format!("[{}]", self.host)
+    } else {
+      self.host.clone()
+    };
+
+    Host::parse(&host).map_err(|err| format!("Invalid proxy host {:?}: {}", self.host, err))
+  }
+
+  pub fn validate(&self) -> Result<(), String> {
+    self.parsed_host()?;
+    if self.port == 0 {
+      return Err("Proxy port must not be 0".to_string());
+    }
+    if self.password.is_some() && self.username.is_none() {
+      return Err("Proxy password needs a username".to_string());
+    }
+    if let Some(entry) = self.bypass.iter().find(|entry| {
+      entry.is_empty() || entry.contains(|c: char| c.is_whitespace() || c == ';' || c == ',')
+    }) {
+      return Err(format!("Invalid proxy bypass entry {:?}", entry));
+    }
+
+    Ok(())
+  }
+
+  /// Proxy endpoint without credentials, e.g. `socks5://127.0.0.1:1080`.
+  pub fn endpoint(&self) -> String {
+    let host = self
+      .parsed_host()
+      .map(|host| host.to_string())
+      .unwrap_or_else(|_| self.host.clone());
+    format!("{}://{}:{}", self.kind.scheme(), host, self.port)
+  }
+
+  #[cfg(any(target_os = "linux", target_os = "macos"))]
+  pub fn endpoint_url(&self) -> Url {
+    Url::parse(&self.endpoint()).expect("Proxy URL is built from a validated host")
+  }
+
+  /// Proxy endpoint with percent-encoded credentials.
+  #[cfg(target_os = "linux")]
+  fn credentials_url(&self) -> Url {
+    let mut url = self.endpoint_url();
+    if let Some(username) = &self.username {
+      url.set_username(username).unwrap_or_default();
+      url
+        .set_password(self.password.as_deref())
+        .unwrap_or_default();
+    }
+    url
+  }
+}
+
+/// Checks that the proxy can open a tunnel to `target` (`host:port`), by default the app server.
+/// Returns the time it took in milliseconds.
+pub async fn test_connection(proxy: ProxySettings, target: Option<String>) -> Result<u64, String> {
+  let target = match target {
+    Some(target) => target,
+    None => default_target()?,
+  };
+
+  tauri::async_runtime::spawn_blocking(move || probe::open_tunnel(&proxy, &target))
+    .await
+    .map_err(|err| err.to_string())?
+    .map(|elapsed| elapsed.as_millis() as u64)
+}
+
+fn default_target() -> Result<String, String> {
+  let url = Url::parse(crate::BASE_URL).map_err(|err| err.to_string())?;
+  let host = url.host_str().ok_or("App URL has no host")?;
+  let port = url.port_or_known_default().ok_or("App URL has no port")?;
+  Ok(format!("{}:{}", host, port))
+}
+
+/// Rejects what the webview engine of this platform cannot apply, rather than silently connecting
+/// without it.
+#[cfg(target_os = "linux")]
+pub fn validate_for_engine(_proxy: &ProxySettings) -> Result<(), String> {
+  Ok(())
+}
+
+// WebView2 takes the proxy as a Chromium switch, which has no place for credentials
+#[cfg(windows)]
+pub fn validate_for_engine(proxy: &ProxySettings) -> Result<(), String> {
+  if proxy.username.is_some() {
+    return Err("Proxy credentials are not supported by WebView2".to_string());
+  }
+  Ok(())
+}
+
+// WKWebView takes proxies from macOS 14 on, and only as an endpoint, without credentials or
+// a bypass list
+#[cfg(target_os = "macos")]
+pub fn validate_for_engine(proxy: &ProxySettings) -> Result<(), String> {
+  let is_supported = matches!(
+    tauri_plugin_os::version(),
+    tauri_plugin_os::Version::Semantic(major, _, _) if major >= MACOS_PROXY_MIN_VERSION
+  );
+  if !is_supported {
+    return Err(format!(
+      "Proxies need macOS {} or later",
+      MACOS_PROXY_MIN_VERSION
+    ));
+  }
+  if proxy.username.is_some() {
+    return Err("Proxy credentials are not supported by WKWebView".to_string());
+  }
+  if !proxy.bypass.is_empty() {
+    return Err("Proxy bypass lists are not supported by WKWebView".to_string());
+  }
+  Ok(())
+}
+
+/// Adds the Chromium switches that route WebView2 through the proxy. They apply to the whole
+/// browser process, shared by every window.
+#[cfg(windows)]
+pub fn with_browser_args(args: &str, proxy: Option<&ProxySettings>) -> String {
+  let Some(proxy) = proxy else {
+    return args.to_string();
+  };
+
+  let mut args = format!("{} --proxy-server=\"{}\"", args, proxy.endpoint());
+  if !proxy.bypass.is_empty() {
+    args.push_str(&format!(
+      " --proxy-bypass-list=\"{}\"",
+      proxy.bypass.join(";")
+    ));
+  }
+  args
+}
+
+#[cfg(target_os = "linux")]
+static IS_PROXY_APPLIED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Completes the proxy on WebKitGTK. `proxy_url` on the builder routes the first load through the
+/// endpoint, but cannot carry credentials or the bypass list, so those are set on the shared
+/// website data manager afterwards and the page is reloaded. Removing the proxy also has to reset
+/// that manager, which outlives the windows.
+#[cfg(target_os = "linux")]
+pub fn apply_to_webview(window: &tauri::WebviewWindow, proxy: Option<&ProxySettings>) {
+  use std::sync::atomic::Ordering;
+
+  let was_applied = IS_PROXY_APPLIED.swap(proxy.is_some(), Ordering::Relaxed);
+  let settings = match proxy {
+    Some(proxy) if proxy.username.is_some() || !proxy.bypass.is_empty() => {
+      Some((proxy.credentials_url().to_string(), proxy.bypass.clone()))
+    }
+    Some(_) => return,
+    None if was_applied => None,
+    None => return,
+  };
+
+  let result = window.with_webview(move |webview| {
+    use webkit2gtk::{
+      NetworkProxyMode, NetworkProxySettings, WebContextExt, WebViewExt, WebsiteDataManagerExt,
+    };
+
+    let webview = webview.inner();
+    let Some(manager) = webview
+      .context()
+      .and_then(|context| context.website_data_manager())
+    else {
+      return;
+    };
+
+    match &settings {
+      Some((uri, bypass)) => {
+        let bypass: Vec<&str> = bypass.iter().map(String::as_str).collect();
+        let mut proxy_settings = NetworkProxySettings::new(Some(uri), &bypass);
+        manager.set_network_proxy_settings(NetworkProxyMode::Custom, Some(&mut proxy_settings));
+      }
+      None => manager.set_network_proxy_settings(NetworkProxyMode::Default, None),
+    }
+    webview.reload();
+  });
+
+  if let Err(err) = result {
+    log::error!("Failed to apply proxy settings: {:?}", err);
+  }
+}
@@ -0,0 +1,389 @@
+use std::io::{Read, Write};
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+
+use super::{ProxyKind, ProxySettings};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_RESPONSE_HEAD: usize = 8 * 1024;
+
+/// Opens a tunnel through the proxy to `target` (`host:port`) and returns how long it took.
+/// Only the handshake is checked, nothing is sent through the tunnel.
+pub fn open_tunnel(proxy: &ProxySettings, target: &str) -> Result<Duration, String> {
+  proxy.validate()?;
+  let (target_host, target_port) = split_target(target)?;
+
+  let started_at = Instant::now();
+  let address = (
+    proxy.host.trim_matches(|c| c == '[' || c == ']'),
+    proxy.port,
+  )
+    .to_socket_addrs()
+    .map_err(|err| format!("Cannot resolve proxy {}: {}", proxy.host, err))?
+    .next()
+    .ok_or_else(|| format!("Cannot resolve proxy {}", proxy.host))?;
+  let mut stream = TcpStream::connect_timeout(&address, PROBE_TIMEOUT)
+    .map_err(|err| format!("Cannot connect to proxy {}: {}", proxy.endpoint(), err))?;
+  stream
+    .set_read_timeout(Some(PROBE_TIMEOUT))
+    .and_then(|()| stream.set_write_timeout(Some(PROBE_TIMEOUT)))
+    .map_err(|err| err.to_string())?;
+
+  match proxy.kind {
+    ProxyKind::Http => http_connect(&mut stream, proxy, target_host, target_port)?,
+    ProxyKind::Socks5 => socks5_connect(&mut stream, proxy, target_host, target_port)?,
+  }
+
+  Ok(started_at.elapsed())
+}
+
+fn split_target(target: &str) -> Result<(&str, u16), String> {
+  let (host, port) = target
+    .rsplit_once(':')
+    .ok_or_else(|| format!("Target {:?} has no port", target))?;
+  let port = port
+    .parse()
+    .map_err(|_| format!("Invalid target port in {:?}", target))?;
+  let host = host.trim_start_matches('[').trim_end_matches(']');
+  if host.is_empty() {
+    return Err(format!("Target {:?} has no host", target));
+  }
+  Ok((host, port))
+}
+
+fn credentials(proxy: &ProxySettings) -> Option<(&str, &str)> {
+  let username = proxy.username.as_deref()?;
+  Some((username, proxy.password.as_deref().unwrap_or_default()))
+}
+
+fn http_connect_request(proxy: &ProxySettings, host: &str, port: u16) -> String {
+  let authority = if host.contains(':') {
+    format!("[{}]:{}", host, port)
+  } else {
+    format!("{}:{}", host, port)
+  };
+
+  let mut request = format!(
+    "CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n",
+    authority = authority
+  );
+  if let Some((username, password)) = credentials(proxy) {
+    let token =
+      base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+    request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", token));
+  }
+  request.push_str("\r\n");
+  request
+}
+
+/// Checks the status line of the proxy's answer to `CONNECT`.
+fn check_http_response(head: &str) -> Result<(), String> {
+  let status_line = head.lines().next().unwrap_or_default();
+  let mut parts = status_line.split_whitespace();
+  let is_http = parts
+    .next()
+    .is_some_and(|version| version.starts_with("HTTP/1."));
+  let status = parts.next().and_then(|status| status.parse::<u16>().ok());
+
+  match (is_http, status) {
+    (true, Some(200..=299)) => Ok(()),
+    (true, Some(407)) => Err("Proxy rejected the credentials".to_string()),
+    (true, Some(status)) => Err(format!("Proxy answered with status {}", status)),
+    _ => Err("Proxy did not answer with HTTP".to_string()),
+  }
+}
+
+fn http_connect(
+  stream: &mut TcpStream,
+  proxy: &ProxySettings,
+  host: &str,
+  port: u16,
+) -> Result<(), String> {
+  stream
+    .write_all(http_connect_request(proxy, host, port).as_bytes())
+    .map_err(|err| err.to_string())?;
+
+  let mut head = Vec::new();
+  let mut buffer = [0; 512];
+  while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+    if head.len() > MAX_RESPONSE_HEAD {
+      return Err("Proxy answer is too long".to_string());
+    }
+    let read = stream.read(&mut buffer).map_err(|err| err.to_string())?;
+    if read == 0 {
+      break;
+    }
+    head.extend_from_slice(&buffer[..read]);
+  }
+
+  check_http_response(&String::from_utf8_lossy(&head))
+}
+
+const SOCKS_VERSION: u8 = 5;
+const SOCKS_NO_AUTH: u8 = 0;
+const SOCKS_USERNAME_AUTH: u8 = 2;
+const SOCKS_NO_ACCEPTABLE_AUTH: u8 = 0xFF;
+
+fn socks5_greeting(proxy: &ProxySettings) -> Vec<u8> {
+  if credentials(proxy).is_some() {
+    vec![SOCKS_VERSION, 2, SOCKS_NO_AUTH, SOCKS_USERNAME_AUTH]
+  } else {
+    vec![SOCKS_VERSION, 1, SOCKS_NO_AUTH]
+  }
+}
+
+/// RFC 1929 username and password request.
+fn socks5_auth_request(username: &str, password: &str) -> Result<Vec<u8>, String> {
+  let username_length =
+    u8::try_from(username.len()).map_err(|_| "Proxy username is too long".to_string())?;
+  let password_length =
+    u8::try_from(password.len()).map_err(|_| "Proxy password is too long".to_string())?;
+
+  let mut request = vec![1, username_length];
+  request.extend_from_slice(username.as_bytes());
+  request.push(password_length);
+  request.extend_from_slice(password.as_bytes());
+  Ok(request)
+}
+
+fn socks5_connect_request(host: &str, port: u16) -> Result<Vec<u8>, String> {
+  let mut request = vec![SOCKS_VERSION, 1, 0];
+  match host.parse::<IpAddr>() {
+    Ok(IpAddr::V4(ip)) => {
+      request.push(1);
+      request.extend_from_slice(&ip.octets());
+    }
+    Ok(IpAddr::V6(ip)) => {
+      request.push(4);
+      request.extend_from_slice(&ip.octets());
+    }
+    Err(_) => {
+      let length = u8::try_from(host.len()).map_err(|_| "Target host is too long".to_string())?;
+      request.push(3);
+      request.push(length);
+      request.extend_from_slice(host.as_bytes());
+    }
+  }
+  request.extend_from_slice(&port.to_be_bytes());
+  Ok(request)
+}
+
+fn socks5_reply_error(code: u8) -> String {
+  let reason = match code {
+    1 => "general failure",
+    2 => "connection not allowed by ruleset",
+    3 => "network unreachable",
+    4 => "host unreachable",
+    5 => "connection refused",
+    6 => "TTL expired",
+    7 => "command not supported",
+    8 => "address type not supported",
+    _ => "unknown error",
+  };
+  format!("Proxy could not connect: {}", reason)
+}
+
+fn read_exact(stream: &mut TcpStream, length: usize) -> Result<Vec<u8>, String> {
+  let mut buffer = vec![0; length];
+  stream
+    .read_exact(&mut buffer)
+    .map_err(|err| format!("Proxy closed the connection: {}", err))?;
+  Ok(buffer)
+}
+
+fn socks5_connect(
+  stream: &mut TcpStream,
+  proxy: &ProxySettings,
+  host: &str,
+  port: u16,
+) -> Result<(), String> {
+  stream
+    .write_all(&socks5_greeting(proxy))
+    .map_err(|err| err.to_string())?;
+  let choice = read_exact(stream, 2)?;
+  if choice[0] != SOCKS_VERSION {
+    return Err("Proxy did not answer with SOCKS5".to_string());
+  }
+
+  match (choice[1], credentials(proxy)) {
+    (SOCKS_NO_AUTH, _) => {}
+    (SOCKS_USERNAME_AUTH, Some((username, password))) => {
+      stream
+        .write_all(&socks5_auth_request(username, password)?)
+        .map_err(|err| err.to_string())?;
+      let status = read_exact(stream, 2)?;
+      if status[1] != 0 {
+        return Err("Proxy rejected the credentials".to_string());
+      }
+    }
+    (SOCKS_USERNAME_AUTH, None) | (SOCKS_NO_ACCEPTABLE_AUTH, None) => {
+      return Err("Proxy requires credentials".to_string());
+    }
+    _ => return Err("Proxy offered no supported authentication".to_string()),
+  }
+
+  stream
+    .write_all(&socks5_connect_request(host, port)?)
+    .map_err(|err| err.to_string())?;
+  let reply = read_exact(stream, 4)?;
+  if reply[1] != 0 {
+    return Err(socks5_reply_error(reply[1]));
+  }
+
+  // Bound address, read to leave the stream in a clean state
+  let address_length = match reply[3] {
+    1 => 4,
+    4 => 16,
+    3 => read_exact(stream, 1)?[0] as usize,
+    _ => return Err("Proxy answered with an unknown address type".to_string()),
+  };
+  read_exact(stream, address_length + 2)?;
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::net::TcpListener;
+  use std::thread::JoinHandle;
+
+  use super::*;
+
+  fn proxy(kind: ProxyKind, port: u16, credentials: Option<(&str, &str)>) -> ProxySettings {
+    ProxySettings {
+      kind,
+      host: "127.0.0.1".to_string(),
+      port,
+      username: credentials.map(|(username, _)| username.to_string()),
+      password: credentials.map(|(_, password)| password.to_string()),
+      bypass: Vec::new(),
+    }
+  }
+
+  /// Accepts one connection on a local port and lets `serve` play the proxy.
+  fn serve_once(serve: impl FnOnce(&mut TcpStream) + Send + 'static) -> (u16, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      serve(&mut stream);
+    });
+    (port, server)
+  }
+
+  fn read_head(stream: &mut TcpStream) -> String {
+    let mut head = Vec::new();
+    let mut byte = [0; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+      stream.read_exact(&mut byte).unwrap();
+      head.push(byte[0]);
+    }
+    String::from_utf8(head).unwrap()
+  }
+
+  #[test]
+  fn opens_http_tunnels() {
+    let (port, server) = serve_once(|stream| {
+      let head = read_head(stream);
+      assert!(head.starts_with("CONNECT example.org:443 HTTP/1.1\r\n"));
+      // "user:secret"
+      assert!(head.contains("Proxy-Authorization: Basic dXNlcjpzZWNyZXQ=\r\n"));
+      stream
+        .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+        .unwrap();
+    });
+
+    let proxy = proxy(ProxyKind::Http, port, Some(("user", "secret")));
+    open_tunnel(&proxy, "example.org:443").unwrap();
+    server.join().unwrap();
+  }
+
+  #[test]
+  fn reports_rejected_http_credentials() {
+    let (port, server) = serve_once(|stream| {
+      read_head(stream);
+      stream
+        .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+        .unwrap();
+    });
+
+    let proxy = proxy(ProxyKind::Http, port, None);
+    assert_eq!(
+      open_tunnel(&proxy, "example.org:443"),
+      Err("Proxy rejected the credentials".to_string())
+    );
+    server.join().unwrap();
+  }
+
+  #[test]
+  fn opens_socks5_tunnels() {
+    let (port, server) = serve_once(|stream| {
+      let mut greeting = [0; 4];
+      stream.read_exact(&mut greeting).unwrap();
+      assert_eq!(
+        greeting,
+        [SOCKS_VERSION, 2, SOCKS_NO_AUTH, SOCKS_USERNAME_AUTH]
+      );
+      stream
+        .write_all(&[SOCKS_VERSION, SOCKS_USERNAME_AUTH])
+        .unwrap();
+
+      let mut auth = [0; 13];
+      stream.read_exact(&mut auth).unwrap();
+      assert_eq!(&auth, b"\x01\x04user\x06secret");
+      stream.write_all(&[1, 0]).unwrap();
+
+      let mut request = [0; 7 + "example.org".len()];
+      stream.read_exact(&mut request).unwrap();
+      assert_eq!(&request[..5], &[SOCKS_VERSION, 1, 0, 3, 11]);
+      assert_eq!(&request[5..16], b"example.org");
+      assert_eq!(&request[16..], &443u16.to_be_bytes());
+      stream
+        .write_all(&[SOCKS_VERSION, 0, 0, 1, 127, 0, 0, 1, 0x1f, 0x90])
+        .unwrap();
+    });
+
+    let proxy = proxy(ProxyKind::Socks5, port, Some(("user", "secret")));
+    open_tunnel(&proxy, "example.org:443").unwrap();
+    server.join().unwrap();
+  }
+
+  #[test]
+  fn reports_socks5_failures() {
+    let (port, server) = serve_once(|stream| {
+      let mut greeting = [0; 3];
+      stream.read_exact(&mut greeting).unwrap();
+      stream.write_all(&[SOCKS_VERSION, SOCKS_NO_AUTH]).unwrap();
+
+      let mut request = [0; 10];
+      stream.read_exact(&mut request).unwrap();
+      assert_eq!(&request[..4], &[SOCKS_VERSION, 1, 0, 1]);
+      stream
+        .write_all(&[SOCKS_VERSION, 5, 0, 1, 0, 0, 0, 0, 0, 0])
+        .unwrap();
+    });
+
+    let proxy = proxy(ProxyKind::Socks5, port, None);
+    assert_eq!(
+      open_tunnel(&proxy, "10.0.0.1:443"),
+      Err("Proxy could not connect: connection refused".to_string())
+    );
+    server.join().unwrap();
+  }
+
+  #[test]
+  fn reports_closed_ports() {
+    // Bound and dropped right away, so nothing listens there
+    let port = TcpListener::bind("127.0.0.1:0")
+      .unwrap()
+      .local_addr()
+      .unwrap()
+      .port();
+
+    let proxy = proxy(ProxyKind::Http, port, None);
+    let err = open_tunnel(&proxy, "example.org:443").unwrap_err();
+    assert!(err.starts_with("Cannot connect to proxy"), "{}", err);
+  }
+}
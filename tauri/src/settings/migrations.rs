@@ -0,0 +1,61 @@
+use serde_json::{Map, Value};
+
+pub const CURRENT_VERSION: u32 = 1;
+
+type Migration = fn(&mut Map<String, Value>);
+
+// Entry `n` upgrades a version `n + 1` document to version `n + 2`
+const MIGRATIONS: &[Migration] = &[];
+
+fn document_version(document: &Map<String, Value>) -> Option<u32> {
+  document
+    .get("version")
+    .and_then(Value::as_u64)
+    .and_then(|version| u32::try_from(version).ok())
+}
+
+/// Upgrades a stored document to the current schema. Returns whether anything changed.
+/// Documents without a version were written by hand and are read as current. Documents of a newer
+/// version, left by an update that was rolled back, are kept as they are.
+pub fn migrate(document: &mut Map<String, Value>) -> bool {
+  let Some(version) = document_version(document).filter(|version| *version >= 1) else {
+    return false;
+  };
+  if version >= CURRENT_VERSION {
+    return false;
+  }
+
+  for migration in MIGRATIONS.iter().skip(version as usize - 1) {
+    migration(document);
+  }
+  document.insert("version".to_string(), CURRENT_VERSION.into());
+
+  true
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  fn document(value: Value) -> Map<String, Value> {
+    match value {
+      Value::Object(document) => document,
+      _ => unreachable!(),
+    }
+  }
+
+  #[test]
+  fn keeps_current_unversioned_and_newer_documents() {
+    for value in [
+      json!({ "version": CURRENT_VERSION, "tray": {} }),
+      json!({ "tray": {} }),
+      json!({ "version": CURRENT_VERSION + 1, "tray": {} }),
+    ] {
+      let mut settings = document(value.clone());
+      assert!(!migrate(&mut settings));
+      assert_eq!(Value::Object(settings), value);
+    }
+  }
+}
@@ -0,0 +1,535 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::dnd::DndConfig;
+use crate::downloads::DownloadSettings;
+use crate::menu::MenuBarSettings;
+use crate::proxy::ProxySettings;
+use crate::shortcuts::ShortcutSettings;
+use crate::spelling::SpellingSettings;
+use crate::updater::UpdaterConfig;
+use crate::webview::WebviewSettings;
+use crate::window::WindowRole;
+
+mod migrations;
+
+use migrations::CURRENT_VERSION;
+
+const SETTINGS_FILE: &str = "settings.json";
+// Copy of a settings file that could not be read as a whole, before defaults replace parts of it
+const INVALID_SETTINGS_EXTENSION: &str = "invalid.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WindowSettings {
+  // Size of new windows, until the user resizes them
+  pub width: f64,
+  pub height: f64,
+}
+
+impl Default for WindowSettings {
+  fn default() -> Self {
+    Self {
+      width: 1088.0,
+      height: 700.0,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BadgeSettings {
+  // `#RRGGBB` or `#RRGGBBAA`
+  pub background_color: String,
+  pub muted_background_color: String,
+  pub text_color: String,
+}
+
+impl Default for BadgeSettings {
+  fn default() -> Self {
+    Self {
+      background_color: "#F23C34".to_string(),
+      muted_background_color: "#888888".to_string(),
+      text_color: "#FFFFFF".to_string(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TranslationSettings {
+  // Translated labels of native menus and notifications by id, kept for the next launch
+  pub labels: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ZoomSettings {
+  // Zoom factor of each window role, 1.0 when missing
+  pub levels: HashMap<WindowRole, f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NetworkSettings {
+  // Applied to windows when they are created, direct connection when missing
+  pub proxy: Option<ProxySettings>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+  pub version: u32,
+  pub window: WindowSettings,
+  pub badge: BadgeSettings,
+  pub translations: TranslationSettings,
+  pub zoom: ZoomSettings,
+  pub network: NetworkSettings,
+  pub webview: WebviewSettings,
+  pub shortcuts: ShortcutSettings,
+  pub menu_bar: MenuBarSettings,
+  pub spelling: SpellingSettings,
+  pub dnd: DndConfig,
+  pub downloads: DownloadSettings,
+  pub updater: UpdaterConfig,
+  // Fields of a newer version, kept so a rolled back version does not drop them
+  #[serde(flatten)]
+  pub unknown: Map<String, Value>,
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Self {
+      version: CURRENT_VERSION,
+      window: WindowSettings::default(),
+      badge: BadgeSettings::default(),
+      translations: TranslationSettings::default(),
+      zoom: ZoomSettings::default(),
+      network: NetworkSettings::default(),
+      webview: WebviewSettings::default(),
+      shortcuts: ShortcutSettings::default(),
+      menu_bar: MenuBarSettings::default(),
+      spelling: SpellingSettings::default(),
+      dnd: DndConfig::default(),
+      downloads: DownloadSettings::default(),
+      updater: UpdaterConfig::default(),
+      unknown: Map::new(),
+    }
+  }
+}
+
+impl Settings {
+  /// Copy for the frontend. The proxy password never leaves the shell, and patches without it keep
+  /// the stored one for the same proxy.
+  pub fn redacted(&self) -> Settings {
+    let mut settings = self.clone();
+    if let Some(proxy) = &mut settings.network.proxy {
+      proxy.password = None;
+    }
+    settings
+  }
+
+  fn validate(&self) -> Result<(), String> {
+    if self.window.width < crate::WINDOW_MIN_WIDTH || self.window.height < crate::WINDOW_MIN_HEIGHT
+    {
+      return Err(format!(
+        "Window size must be at least {}x{}",
+        crate::WINDOW_MIN_WIDTH,
+        crate::WINDOW_MIN_HEIGHT
+      ));
+    }
+
+    for color in [
+      &self.badge.background_color,
+      &self.badge.muted_background_color,
+      &self.badge.text_color,
+    ] {
+      parse_color(color).ok_or_else(|| format!("Invalid color {:?}", color))?;
+    }
+
+    for factor in self.zoom.levels.values() {
+      crate::zoom::validate_factor(*factor)?;
+    }
+
+    if let Some(proxy) = &self.network.proxy {
+      proxy.validate()?;
+    }
+
+    self.shortcuts.validate()?;
+    self.spelling.validate()?;
+    self.dnd.validate()?;
+    self.updater.validate()?;
+
+    Ok(())
+  }
+}
+
+struct SettingsState {
+  settings: Settings,
+  path: Option<PathBuf>,
+}
+
+static SETTINGS_STATE: LazyLock<Mutex<SettingsState>> = LazyLock::new(|| {
+  Mutex::new(SettingsState {
+    settings: Settings::default(),
+    path: None,
+  })
+});
+
+/// Parses `#RRGGBB` and `#RRGGBBAA` colors.
+pub fn parse_color(color: &str) -> Option<[u8; 4]> {
+  let hex = color.strip_prefix('#')?;
+  if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+    return None;
+  }
+
+  let mut rgba = [0xFF; 4];
+  for (index, channel) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+    *channel = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).ok()?;
+  }
+  Some(rgba)
+}
+
+/// Applies an RFC 7396 merge patch: objects are merged, `null` removes a field, so it falls back to
+/// its default, and anything else replaces the value.
+fn merge_patch(target: &mut Value, patch: &Value) {
+  let Value::Object(patch) = patch else {
+    *target = patch.clone();
+    return;
+  };
+
+  if !target.is_object() {
+    *target = Value::Object(Map::new());
+  }
+  if let Value::Object(target) = target {
+    for (key, value) in patch {
+      if value.is_null() {
+        target.remove(key);
+      } else {
+        merge_patch(target.entry(key.as_str()).or_insert(Value::Null), value);
+      }
+    }
+  }
+}
+
+fn parse_settings(document: Map<String, Value>) -> Result<Settings, String> {
+  serde_json::from_value::<Settings>(Value::Object(document))
+    .map_err(|err| err.to_string())
+    .and_then(|settings| settings.validate().map(|()| settings))
+}
+
+/// Removes the sections that are invalid on their own, so the rest survives. Returns their keys.
+fn drop_invalid_sections(document: &mut Map<String, Value>) -> Vec<String> {
+  let invalid: Vec<String> = document
+    .iter()
+    .filter(|(key, value)| {
+      parse_settings(Map::from_iter([((*key).clone(), (*value).clone())])).is_err()
+    })
+    .map(|(key, _)| key.clone())
+    .collect();
+
+  for key in &invalid {
+    document.remove(key);
+  }
+  invalid
+}
+
+/// Keeps a copy of a file that is not used as it is, as the next save overwrites it.
+fn back_up_settings(path: &Path) {
+  let backup = path.with_extension(INVALID_SETTINGS_EXTENSION);
+  match std::fs::copy(path, &backup) {
+    Ok(_) => log::warn!("Kept a copy of the settings file at {:?}", backup),
+    Err(err) => log::error!("Failed to back up settings: {:?}", err),
+  }
+}
+
+fn load_settings(path: &Path) -> Settings {
+  let Ok(contents) = std::fs::read_to_string(path) else {
+    return Settings::default();
+  };
+
+  let mut document = match serde_json::from_str::<Value>(&contents) {
+    Ok(Value::Object(document)) => document,
+    Ok(_) => {
+      log::warn!("Ignoring settings that are not an object");
+      back_up_settings(path);
+      return Settings::default();
+    }
+    Err(err) => {
+      log::warn!("Ignoring broken settings: {:?}", err);
+      back_up_settings(path);
+      return Settings::default();
+    }
+  };
+
+  let is_migrated = migrations::migrate(&mut document);
+
+  match parse_settings(document.clone()) {
+    Ok(settings) => {
+      if is_migrated {
+        save_settings(path, &settings);
+      }
+      settings
+    }
+    Err(err) => {
+      log::warn!("Invalid settings: {}", err);
+      back_up_settings(path);
+      let invalid = drop_invalid_sections(&mut document);
+      log::warn!("Reset settings {:?} to their defaults", invalid);
+      parse_settings(document).unwrap_or_default()
+    }
+  }
+}
+
+/// Replaces a file through a temporary one next to it, so a crash never leaves it half written.
+pub fn write_atomically(path: &Path, contents: &str) -> std::io::Result<()> {
+  if let Some(dir) = path.parent() {
+    std::fs::create_dir_all(dir)?;
+  }
+  let mut tmp_path = path.as_os_str().to_owned();
+  tmp_path.push(".tmp");
+  std::fs::write(&tmp_path, contents)?;
+  std::fs::rename(&tmp_path, path)
+}
+
+fn save_settings(path: &Path, settings: &Settings) {
+  let result = serde_json::to_string_pretty(settings)
+    .map_err(std::io::Error::other)
+    .and_then(|contents| write_atomically(path, &contents));
+
+  if let Err(err) = result {
+    log::error!("Failed to save settings: {:?}", err);
+  }
+}
+
+/// Loads the settings. Runs before every other subsystem, as they read from it.
+pub fn init(app: &AppHandle) {
+  let path = app
+    .path()
+    .app_config_dir()
+    .map(|dir| dir.join(SETTINGS_FILE))
+    .ok();
+
+  if let Ok(mut state) = SETTINGS_STATE.lock() {
+    state.settings = path.as_deref().map(load_settings).unwrap_or_default();
+    state.path = path;
+  }
+}
+
+pub fn get() -> Settings {
+  SETTINGS_STATE
+    .lock()
+    .map(|state| state.settings.clone())
+    .unwrap_or_default()
+}
+
+/// Applies a partial update, given as a merge patch of the serialized settings.
+pub fn update(app: &AppHandle, patch: Value) -> Result<Settings, String> {
+  change(app, |settings| {
+    *settings = patched(settings, &patch)?;
+    Ok(())
+  })
+}
+
+fn patched(previous: &Settings, patch: &Value) -> Result<Settings, String> {
+  let mut document = serde_json::to_value(previous).map_err(|err| err.to_string())?;
+  merge_patch(&mut document, patch);
+
+  let mut settings: Settings = serde_json::from_value(document).map_err(|err| err.to_string())?;
+  if let Some(key) = settings
+    .unknown
+    .keys()
+    .find(|key| !previous.unknown.contains_key(*key))
+  {
+    return Err(format!("Unknown setting {:?}", key));
+  }
+  settings.version = previous.version;
+  // The stored password is only ever sent to the proxy it was entered for
+  if let (Some(proxy), Some(previous_proxy)) =
+    (&mut settings.network.proxy, &previous.network.proxy)
+    && proxy.endpoint() != previous_proxy.endpoint()
+    && patch.pointer("/network/proxy/password").is_none()
+  {
+    proxy.password = None;
+  }
+  settings.validate()?;
+  // Only checked on change, so a stored proxy the engine cannot apply does not block other updates
+  let new_proxy = (settings.network != previous.network)
+    .then_some(settings.network.proxy.as_ref())
+    .flatten();
+  if let Some(proxy) = new_proxy {
+    crate::proxy::validate_for_engine(proxy)?;
+  }
+  if settings.webview != previous.webview {
+    crate::webview::validate_for_engine(&settings.webview)?;
+  }
+
+  Ok(settings)
+}
+
+/// Changes settings from native code, which keeps them valid itself.
+pub fn modify(app: &AppHandle, change_settings: impl FnOnce(&mut Settings)) {
+  let result = change(app, |settings| {
+    change_settings(settings);
+    Ok(())
+  });
+  if let Err(err) = result {
+    log::error!("Failed to change settings: {}", err);
+  }
+}
+
+/// Stores the translations of native labels sent by the frontend.
+pub fn set_labels(app: &AppHandle, labels: HashMap<String, String>) {
+  modify(app, |settings| settings.translations.labels = labels);
+}
+
+/// Reads, changes and stores the settings under one lock, so concurrent changes are not lost, then
+/// applies what changed.
+fn change(
+  app: &AppHandle,
+  change_settings: impl FnOnce(&mut Settings) -> Result<(), String>,
+) -> Result<Settings, String> {
+  let (previous, settings) = {
+    let mut state = SETTINGS_STATE.lock().map_err(|err| err.to_string())?;
+    let mut settings = state.settings.clone();
+    change_settings(&mut settings)?;
+    if state.settings == settings {
+      return Ok(settings);
+    }
+    if let Some(path) = &state.path {
+      save_settings(path, &settings);
+    }
+    (
+      std::mem::replace(&mut state.settings, settings.clone()),
+      settings,
+    )
+  };
+
+  apply(app, &previous, &settings);
+  Ok(settings)
+}
+
+fn apply(app: &AppHandle, previous: &Settings, settings: &Settings) {
+  if previous.badge != settings.badge {
+    crate::tray::refresh_notifications_count(app);
+  }
+  if previous.translations != settings.translations {
+    crate::tray::refresh_labels();
+    crate::menu::refresh_labels(app);
+  }
+  if previous.network != settings.network {
+    crate::window::recreate_windows(app);
+  }
+  if previous.webview != settings.webview {
+    crate::webview::apply(app, &previous.webview, &settings.webview);
+  }
+  if previous.shortcuts != settings.shortcuts {
+    crate::shortcuts::apply(app, &settings.shortcuts);
+  }
+  if previous.menu_bar != settings.menu_bar {
+    crate::menu::apply(app, &settings.menu_bar);
+  }
+  if previous.spelling != settings.spelling {
+    crate::spelling::apply(app, &settings.spelling);
+  }
+  if previous.dnd != settings.dnd {
+    crate::dnd::apply(app);
+  }
+  if previous.updater != settings.updater {
+    crate::updater::apply(&previous.updater, &settings.updater);
+  }
+
+  app
+    .emit("settings-changed", &settings.redacted())
+    .unwrap_or_default();
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+  use crate::proxy::ProxyKind;
+
+  fn proxy_settings() -> Settings {
+    let mut settings = Settings::default();
+    settings.network.proxy = Some(ProxySettings {
+      kind: ProxyKind::Socks5,
+      host: "127.0.0.1".to_string(),
+      port: 1080,
+      username: Some("user".to_string()),
+      password: Some("secret".to_string()),
+      bypass: Vec::new(),
+    });
+    settings
+  }
+
+  #[test]
+  fn keeps_valid_sections_and_backs_up_invalid_files() {
+    let dir = std::env::temp_dir().join(format!("settings-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(SETTINGS_FILE);
+    let contents = json!({
+      "version": CURRENT_VERSION,
+      "window": { "width": 1200.0, "height": 800.0 },
+      "badge": { "backgroundColor": "red" },
+    })
+    .to_string();
+    std::fs::write(&path, &contents).unwrap();
+
+    let settings = load_settings(&path);
+    let backup = std::fs::read_to_string(path.with_extension(INVALID_SETTINGS_EXTENSION));
+    std::fs::remove_dir_all(&dir).unwrap_or_default();
+
+    assert_eq!(
+      settings.window,
+      WindowSettings {
+        width: 1200.0,
+        height: 800.0,
+      }
+    );
+    assert_eq!(settings.badge, BadgeSettings::default());
+    assert_eq!(backup.unwrap(), contents);
+  }
+
+  #[test]
+  fn redacts_the_proxy_password() {
+    let settings = proxy_settings();
+
+    let redacted = serde_json::to_value(settings.redacted()).unwrap();
+    assert_eq!(redacted["network"]["proxy"].get("password"), None);
+    assert_eq!(redacted["network"]["proxy"]["username"], "user");
+
+    // Sent back as a patch, the redacted copy keeps the stored password
+    let patch = json!({ "network": redacted["network"] });
+    assert_eq!(patched(&settings, &patch).unwrap(), settings);
+  }
+
+  #[test]
+  fn drops_the_proxy_password_for_another_server() {
+    let settings = proxy_settings();
+
+    for proxy in [
+      json!({ "host": "proxy.example.com" }),
+      json!({ "port": 1081 }),
+      json!({ "type": "http" }),
+    ] {
+      let patch = json!({ "network": { "proxy": proxy } });
+      let proxy = patched(&settings, &patch).unwrap().network.proxy.unwrap();
+      assert_eq!(proxy.username.as_deref(), Some("user"));
+      assert_eq!(proxy.password, None);
+    }
+
+    // Unless the patch brings a password for it
+    let patch = json!({
+      "network": { "proxy": { "host": "proxy.example.com", "password": "other" } },
+    });
+    let proxy = patched(&settings, &patch).unwrap().network.proxy.unwrap();
+    assert_eq!(proxy.password.as_deref(), Some("other"));
+  }
+}
@@ -0,0 +1,145 @@
+use std::str::FromStr;
+use std::sync::{LazyLock, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::AppHandle;
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+use crate::window::emit_when_ready;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ShortcutSettings {
+  // Accelerators like `CommandOrControl+Shift+T`, empty when disabled
+  pub toggle_app: String,
+  pub quick_compose: String,
+}
+
+impl Default for ShortcutSettings {
+  fn default() -> Self {
+    Self {
+      toggle_app: "CommandOrControl+Shift+T".to_string(),
+      quick_compose: String::new(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum ShortcutAction {
+  ToggleApp,
+  QuickCompose,
+}
+
+impl ShortcutSettings {
+  fn accelerators(&self) -> [(ShortcutAction, &str); 2] {
+    [
+      (ShortcutAction::ToggleApp, self.toggle_app.as_str()),
+      (ShortcutAction::QuickCompose, self.quick_compose.as_str()),
+    ]
+  }
+
+  pub fn validate(&self) -> Result<(), String> {
+    let mut shortcuts = Vec::new();
+    for (_, accelerator) in self.accelerators() {
+      if accelerator.is_empty() {
+        continue;
+      }
+      let shortcut = Shortcut::from_str(accelerator)
+        .map_err(|err| format!("Invalid shortcut {:?}: {}", accelerator, err))?;
+      if shortcuts.contains(&shortcut) {
+        return Err(format!("Shortcut {:?} is used twice", accelerator));
+      }
+      shortcuts.push(shortcut);
+    }
+
+    Ok(())
+  }
+}
+
+// Shortcuts registered with the system, by action
+static REGISTERED_SHORTCUTS: LazyLock<Mutex<Vec<(ShortcutAction, Shortcut)>>> =
+  LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// Registers the stored shortcuts.
+pub fn init(app: &AppHandle) {
+  register(app, &crate::settings::get().shortcuts);
+}
+
+/// Registers the shortcuts without holding the lock, as the handler waits for it on key presses,
+/// possibly on the thread the plugin needs for registering.
+fn register(app: &AppHandle, settings: &ShortcutSettings) {
+  let mut shortcuts = Vec::new();
+  let mut failures = Vec::new();
+
+  for (action, accelerator) in settings.accelerators() {
+    let Ok(shortcut) = Shortcut::from_str(accelerator) else {
+      continue;
+    };
+
+    // Another application may own the combination, which is only known when registering
+    match app.global_shortcut().register(shortcut) {
+      Ok(()) => shortcuts.push((action, shortcut)),
+      Err(err) => {
+        log::warn!("Failed to register shortcut {}: {:?}", accelerator, err);
+        failures
+          .push(json!({ "action": action, "shortcut": accelerator, "reason": err.to_string() }));
+      }
+    }
+  }
+
+  if let Ok(mut registered) = REGISTERED_SHORTCUTS.lock() {
+    registered.extend(shortcuts);
+  }
+
+  for failure in failures {
+    emit_when_ready(app, None, "shortcut-registration-failed", failure);
+  }
+}
+
+/// Replaces the registered shortcuts after the settings change.
+pub fn apply(app: &AppHandle, settings: &ShortcutSettings) {
+  let registered = REGISTERED_SHORTCUTS
+    .lock()
+    .map(|mut registered| std::mem::take(&mut *registered))
+    .unwrap_or_default();
+
+  for (_, shortcut) in registered {
+    if let Err(err) = app.global_shortcut().unregister(shortcut) {
+      log::warn!("Failed to unregister shortcut: {:?}", err);
+    }
+  }
+
+  register(app, settings);
+}
+
+/// Runs the action of a pressed shortcut, passed as the handler of the global shortcut plugin.
+pub fn handle(app: &AppHandle, shortcut: &Shortcut, state: ShortcutState) {
+  if state != ShortcutState::Pressed {
+    return;
+  }
+
+  let action = REGISTERED_SHORTCUTS.lock().ok().and_then(|registered| {
+    registered
+      .iter()
+      .find(|(_, registered_shortcut)| registered_shortcut == shortcut)
+      .map(|(action, _)| *action)
+  });
+
+  match action {
+    Some(ShortcutAction::ToggleApp) => crate::tray::handle_icon_click(app, false),
+    Some(ShortcutAction::QuickCompose) => open_quick_compose(app),
+    None => {}
+  }
+}
+
+fn open_quick_compose(app: &AppHandle) {
+  match crate::open_new_window(app.clone(), crate::BASE_URL.to_string()) {
+    Ok(window) => {
+      window.set_focus().unwrap_or_default();
+      emit_when_ready(app, Some(window.label()), "quick-compose", json!({}));
+    }
+    Err(err) => log::error!("Failed to open quick compose window: {:?}", err),
+  }
+}
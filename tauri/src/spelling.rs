@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+use serde::{Deserialize, Serialize};
+use spellbook::Dictionary;
+use tauri::AppHandle;
+
+// Where distributions install hunspell dictionaries, after the directories in `DICPATH`
+const DICTIONARY_DIRS: &[&str] = &[
+  "/usr/share/hunspell",
+  "/usr/local/share/hunspell",
+  "/usr/share/myspell",
+  "/usr/share/myspell/dicts",
+];
+const MAX_SUGGESTIONS: usize = 5;
+const MAX_LANGUAGE_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SpellingSettings {
+  // Dictionary names like `en_US`, spell checking is off when empty
+  pub languages: Vec<String>,
+}
+
+impl SpellingSettings {
+  pub fn validate(&self) -> Result<(), String> {
+    for (index, language) in self.languages.iter().enumerate() {
+      if language.is_empty()
+        || language.len() > MAX_LANGUAGE_LENGTH
+        || !language
+          .chars()
+          .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+      {
+        return Err(format!("Invalid spelling language {:?}", language));
+      }
+      if self.languages[..index].contains(language) {
+        return Err(format!("Spelling language {:?} is listed twice", language));
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpellingDictionary {
+  pub language: String,
+  // The `.dic` file, with the `.aff` file next to it
+  pub path: PathBuf,
+}
+
+// Dictionaries of the active languages, parsed once as they are large
+static LOADED_DICTIONARIES: LazyLock<Mutex<Vec<Dictionary>>> =
+  LazyLock::new(|| Mutex::new(Vec::new()));
+
+fn dictionary_dirs() -> Vec<PathBuf> {
+  let mut dirs: Vec<PathBuf> = std::env::var_os("DICPATH")
+    .map(|paths| std::env::split_paths(&paths).collect())
+    .unwrap_or_default();
+  dirs.extend(DICTIONARY_DIRS.iter().map(PathBuf::from));
+  dirs
+}
+
+/// Lists the hunspell dictionaries installed on the system. A language found in several
+/// directories is listed once, from the first of them.
+pub fn list_dictionaries() -> Vec<SpellingDictionary> {
+  let mut dictionaries = BTreeMap::new();
+  for dir in dictionary_dirs() {
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+      continue;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+      if path.extension().is_none_or(|extension| extension != "dic")
+        || !path.with_extension("aff").is_file()
+      {
+        continue;
+      }
+      let Some(language) = path.file_stem().and_then(|stem| stem.to_str()) else {
+        continue;
+      };
+      dictionaries
+        .entry(language.to_string())
+        .or_insert_with(|| path.clone());
+    }
+  }
+
+  dictionaries
+    .into_iter()
+    .map(|(language, path)| SpellingDictionary { language, path })
+    .collect()
+}
+
+fn load_dictionary(path: &Path) -> Result<Dictionary, String> {
+  // Only UTF-8 dictionaries can be parsed, older ones may still use legacy encodings
+  let read = |path: &Path| {
+    std::fs::read(path)
+      .map_err(|err| err.to_string())
+      .and_then(|bytes| String::from_utf8(bytes).map_err(|_| "Not UTF-8".to_string()))
+  };
+  let aff = read(&path.with_extension("aff"))?;
+  let dic = read(path)?;
+  Dictionary::new(&aff, &dic).map_err(|err| err.to_string())
+}
+
+fn load(settings: &SpellingSettings) {
+  let available = list_dictionaries();
+  let dictionaries = settings
+    .languages
+    .iter()
+    .filter_map(|language| {
+      let Some(dictionary) = available
+        .iter()
+        .find(|dictionary| &dictionary.language == language)
+      else {
+        log::warn!("No dictionary installed for {}", language);
+        return None;
+      };
+      load_dictionary(&dictionary.path)
+        .inspect_err(|err| log::warn!("Failed to load dictionary {:?}: {}", dictionary.path, err))
+        .ok()
+    })
+    .collect();
+
+  if let Ok(mut loaded) = LOADED_DICTIONARIES.lock() {
+    *loaded = dictionaries;
+  }
+}
+
+/// Loads the dictionaries of the stored languages, off the main thread as parsing takes a while.
+pub fn init() {
+  let settings = crate::settings::get().spelling;
+  std::thread::spawn(move || load(&settings));
+}
+
+/// Sets the active languages, which must have a dictionary installed.
+pub fn set_languages(app: &AppHandle, languages: Vec<String>) -> Result<(), String> {
+  let available = list_dictionaries();
+  if let Some(language) = languages.iter().find(|language| {
+    !available
+      .iter()
+      .any(|dictionary| &dictionary.language == *language)
+  }) {
+    return Err(format!("No dictionary installed for {}", language));
+  }
+
+  let settings = SpellingSettings { languages };
+  settings.validate()?;
+  crate::settings::modify(app, |current| current.spelling = settings);
+  Ok(())
+}
+
+/// Suggestions for a word none of the active dictionaries knows, `None` when it is spelled right
+/// or no dictionary is active.
+pub fn suggestions(word: &str) -> Option<Vec<String>> {
+  let dictionaries = LOADED_DICTIONARIES.lock().ok()?;
+  if dictionaries.is_empty() || dictionaries.iter().any(|dictionary| dictionary.check(word)) {
+    return None;
+  }
+
+  let mut suggestions = Vec::new();
+  for dictionary in dictionaries.iter() {
+    let mut dictionary_suggestions = Vec::new();
+    dictionary.suggest(word, &mut dictionary_suggestions);
+    for suggestion in dictionary_suggestions {
+      if !suggestions.contains(&suggestion) {
+        suggestions.push(suggestion);
+      }
+    }
+  }
+  suggestions.truncate(MAX_SUGGESTIONS);
+  Some(suggestions)
+}
+
+/// Turns on the spell checker of WebKitGTK for the active languages.
+#[cfg(target_os = "linux")]
+pub fn apply_to_webview(window: &tauri::WebviewWindow, settings: &SpellingSettings) {
+  let languages = settings.languages.clone();
+  let result = window.with_webview(move |webview| {
+    use webkit2gtk::{WebContextExt, WebViewExt};
+
+    let Some(context) = webview.inner().context() else {
+      return;
+    };
+    let languages: Vec<&str> = languages.iter().map(String::as_str).collect();
+    context.set_spell_checking_languages(&languages);
+    context.set_spell_checking_enabled(!languages.is_empty());
+  });
+
+  if let Err(err) = result {
+    log::error!("Failed to apply spelling settings: {:?}", err);
+  }
+}
+
+#[cfg(target_os = "linux")]
+fn apply_to_windows(app: &AppHandle, settings: &SpellingSettings) {
+  use tauri::Manager;
+
+  for window in app.webview_windows().values() {
+    apply_to_webview(window, settings);
+  }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn apply_to_windows(_app: &AppHandle, _settings: &SpellingSettings) {}
+
+/// Reloads the dictionaries and applies the languages to the open windows after a change.
+pub fn apply(app: &AppHandle, settings: &SpellingSettings) {
+  let loaded_settings = settings.clone();
+  std::thread::spawn(move || load(&loaded_settings));
+
+  apply_to_windows(app, settings);
+}
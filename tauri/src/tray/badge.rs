@@ -6,11 +6,10 @@ use imageproc::rect::Rect;
 use std::io::Cursor;
 use tauri::image::Image;
 
-static FONT: &[u8] = include_bytes!("../../fonts/Roboto-Bold.ttf");
-
-const BADGE_BACKGROUND_COLOR: Rgba<u8> = Rgba([0xF2, 0x3C, 0x34, 0xFF]);
-const BADGE_BACKGROUND_COLOR_MUTED: Rgba<u8> = Rgba([0x88, 0x88, 0x88, 0xFF]);
-const BADGE_TEXT_COLOR: Rgba<u8> = Rgba([0xFF, 0xFF, 0xFF, 0xFF]);
+// Settings are validated on write, so this only covers hand-edited files
+fn badge_color(color: &str) -> Rgba<u8> {
+  Rgba(crate::settings::parse_color(color).unwrap_or([0xFF; 4]))
+}
 
 pub fn set_badge_count_icon(window: &tauri::WebviewWindow, amount: i32, is_muted: bool) {
   if amount == 0 {
@@ -47,11 +46,13 @@ pub fn set_badge_count_icon(window: &tauri::WebviewWindow, amount: i32, is_muted
 }
 
 pub fn generate_counter_png(size: u32, count: i32, is_muted: bool) -> Vec<u8> {
+  let badge = crate::settings::get().badge;
   let background_color = if is_muted {
-    BADGE_BACKGROUND_COLOR_MUTED
+    badge_color(&badge.muted_background_color)
   } else {
-    BADGE_BACKGROUND_COLOR
+    badge_color(&badge.background_color)
   };
+  let text_color = badge_color(&badge.text_color);
 
   // Prepare text properties
   let (text, font, scale, text_width, text_height) = if count >= 0 {
@@ -61,7 +62,7 @@ pub fn generate_counter_png(size: u32, count: i32, is_muted: bool) -> Vec<u8> {
       format!("..{:02}", count % 100)
     };
 
-    let font = FontRef::try_from_slice(FONT).expect("Invalid font");
+    let font = FontRef::try_from_slice(crate::ROBOTO_BOLD_FONT).expect("Invalid font");
     let scale = {
       let base = if text.len() < 3 { 0.9 } else { 0.75 };
       let calculated_scale = (base * size as f32).ceil();
@@ -124,13 +125,7 @@ pub fn generate_counter_png(size: u32, count: i32, is_muted: bool) -> Vec<u8> {
     let y = edge_space as f32 + ((badge_height as f32 - text_height as f32) / 2.0).ceil();
 
     draw_text_mut(
-      &mut img,
-      BADGE_TEXT_COLOR,
-      x as i32,
-      y as i32,
-      scale,
-      &font,
-      &text,
+      &mut img, text_color, x as i32, y as i32, scale, &font, &text,
     );
   }
 
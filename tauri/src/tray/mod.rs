@@ -1,10 +1,9 @@
-use std::collections::HashMap;
 use std::sync::{LazyLock, Mutex};
 
 use tauri::{
   AppHandle, Manager, WebviewWindow,
   image::Image,
-  menu::{Menu, MenuItem},
+  menu::{Menu, MenuItem, MenuItemKind, PredefinedMenuItem, Submenu},
   tray::{MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent},
 };
 
@@ -28,27 +27,78 @@ pub const MENU_ITEM_QUIT_ID: &str = "quit";
 pub const MENU_ITEM_QUIT_LABEL: &str = "Quit Telegram";
 pub const MENU_ITEM_OPEN_ID: &str = "open";
 pub const MENU_ITEM_OPEN_LABEL: &str = "Open Telegram";
-
-static MENU_TRANSLATIONS: LazyLock<std::sync::Mutex<HashMap<String, String>>> =
-  LazyLock::new(|| std::sync::Mutex::new(HashMap::new()));
+pub const MENU_ITEM_DND_ID: &str = "dnd";
+pub const MENU_ITEM_DND_LABEL: &str = "Do Not Disturb";
+pub const MENU_ITEM_DND_1H_ID: &str = "dnd_mute_1h";
+pub const MENU_ITEM_DND_1H_LABEL: &str = "Mute for 1 Hour";
+pub const MENU_ITEM_DND_8H_ID: &str = "dnd_mute_8h";
+pub const MENU_ITEM_DND_8H_LABEL: &str = "Mute for 8 Hours";
+pub const MENU_ITEM_DND_TOMORROW_ID: &str = "dnd_mute_tomorrow";
+pub const MENU_ITEM_DND_TOMORROW_LABEL: &str = "Mute Until Tomorrow";
+pub const MENU_ITEM_DND_UNMUTE_ID: &str = "dnd_unmute";
+pub const MENU_ITEM_DND_UNMUTE_LABEL: &str = "Unmute";
+pub const MENU_ITEM_UPDATE_ID: &str = "update_restart";
+pub const MENU_ITEM_UPDATE_LABEL: &str = "Restart to Update";
+pub const MENU_ITEM_SHOW_MENU_BAR_ID: &str = "show_menu_bar";
+pub const MENU_ITEM_SHOW_MENU_BAR_LABEL: &str = "Show Menu Bar";
+
+const MENU_ITEM_LABELS: &[(&str, &str)] = &[
+  (MENU_ITEM_QUIT_ID, MENU_ITEM_QUIT_LABEL),
+  (MENU_ITEM_OPEN_ID, MENU_ITEM_OPEN_LABEL),
+  (MENU_ITEM_DND_ID, MENU_ITEM_DND_LABEL),
+  (MENU_ITEM_DND_1H_ID, MENU_ITEM_DND_1H_LABEL),
+  (MENU_ITEM_DND_8H_ID, MENU_ITEM_DND_8H_LABEL),
+  (MENU_ITEM_DND_TOMORROW_ID, MENU_ITEM_DND_TOMORROW_LABEL),
+  (MENU_ITEM_DND_UNMUTE_ID, MENU_ITEM_DND_UNMUTE_LABEL),
+  (MENU_ITEM_UPDATE_ID, MENU_ITEM_UPDATE_LABEL),
+  (MENU_ITEM_SHOW_MENU_BAR_ID, MENU_ITEM_SHOW_MENU_BAR_LABEL),
+];
 
 pub(super) static TRAY_HANDLE: LazyLock<Mutex<Option<TrayIcon>>> =
   LazyLock::new(|| Mutex::new(None));
 
-pub fn set_menu_translations(new_labels: HashMap<String, String>) {
-  if let Ok(mut labels) = MENU_TRANSLATIONS.lock() {
-    *labels = new_labels;
-  }
+static TRAY_MENU: LazyLock<Mutex<Option<Menu<tauri::Wry>>>> = LazyLock::new(|| Mutex::new(None));
+
+pub(crate) fn translated_label(id: &str, default: &str) -> String {
+  crate::settings::get()
+    .translations
+    .labels
+    .get(id)
+    .cloned()
+    .unwrap_or_else(|| default.to_string())
 }
 
-fn translated_label(id: &str, default: &str) -> String {
-  if let Ok(labels) = MENU_TRANSLATIONS.lock() {
-    labels
-      .get(id)
-      .cloned()
-      .unwrap_or_else(|| default.to_string())
-  } else {
-    default.to_string()
+/// Re-applies translated labels to the tray menu, e.g. after the frontend language changes.
+pub fn refresh_labels() {
+  let Ok(menu_lock) = TRAY_MENU.lock() else {
+    return;
+  };
+  let Some(menu) = menu_lock.as_ref() else {
+    return;
+  };
+
+  let mut items = menu.items().unwrap_or_default();
+  while let Some(item) = items.pop() {
+    let id = item.id().as_ref();
+    let label = MENU_ITEM_LABELS
+      .iter()
+      .find(|(item_id, _)| *item_id == id)
+      .map(|(_, default)| translated_label(id, default));
+
+    match &item {
+      MenuItemKind::MenuItem(menu_item) => {
+        if let Some(label) = label {
+          menu_item.set_text(label).unwrap_or_default();
+        }
+      }
+      MenuItemKind::Submenu(submenu) => {
+        if let Some(label) = label {
+          submenu.set_text(label).unwrap_or_default();
+        }
+        items.extend(submenu.items().unwrap_or_default());
+      }
+      _ => {}
+    }
   }
 }
 
@@ -63,7 +113,41 @@ impl TrayManager {
     let open_label = translated_label(MENU_ITEM_OPEN_ID, MENU_ITEM_OPEN_LABEL);
     let open_i = MenuItem::with_id(&app, MENU_ITEM_OPEN_ID, &open_label, true, None::<&str>)?;
 
-    let menu = Menu::with_items(&app, &[&open_i, &quit_i])?;
+    let dnd_items = [
+      (MENU_ITEM_DND_1H_ID, MENU_ITEM_DND_1H_LABEL),
+      (MENU_ITEM_DND_8H_ID, MENU_ITEM_DND_8H_LABEL),
+      (MENU_ITEM_DND_TOMORROW_ID, MENU_ITEM_DND_TOMORROW_LABEL),
+      (MENU_ITEM_DND_UNMUTE_ID, MENU_ITEM_DND_UNMUTE_LABEL),
+    ]
+    .into_iter()
+    .map(|(id, label)| {
+      MenuItem::with_id(&app, id, translated_label(id, label), true, None::<&str>)
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+    let dnd_label = translated_label(MENU_ITEM_DND_ID, MENU_ITEM_DND_LABEL);
+    let dnd_i = Submenu::with_id(&app, MENU_ITEM_DND_ID, &dnd_label, true)?;
+    for item in &dnd_items {
+      dnd_i.append(item)?;
+    }
+
+    let separator = PredefinedMenuItem::separator(&app)?;
+    let menu = Menu::with_items(&app, &[&open_i, &dnd_i, &separator, &quit_i])?;
+
+    // The hidden menu bar cannot bring itself back, so the tray offers it while hidden
+    #[cfg(target_os = "linux")]
+    {
+      let show_menu_bar_label =
+        translated_label(MENU_ITEM_SHOW_MENU_BAR_ID, MENU_ITEM_SHOW_MENU_BAR_LABEL);
+      let is_menu_bar_hidden = !crate::settings::get().menu_bar.is_visible;
+      let show_menu_bar_i = MenuItem::with_id(
+        &app,
+        MENU_ITEM_SHOW_MENU_BAR_ID,
+        &show_menu_bar_label,
+        is_menu_bar_hidden,
+        None::<&str>,
+      )?;
+      menu.insert(&show_menu_bar_i, 2)?;
+    }
 
     let icon = TRAY_BASE_ICON.clone();
 
@@ -75,6 +159,17 @@ impl TrayManager {
       .on_menu_event(|app, event| match event.id.as_ref() {
         MENU_ITEM_OPEN_ID => handle_icon_click(app, true),
         MENU_ITEM_QUIT_ID => app.exit(0),
+        MENU_ITEM_DND_1H_ID => crate::dnd::mute(app, crate::dnd::MuteDuration::OneHour),
+        MENU_ITEM_DND_8H_ID => crate::dnd::mute(app, crate::dnd::MuteDuration::EightHours),
+        MENU_ITEM_DND_TOMORROW_ID => {
+          crate::dnd::mute(app, crate::dnd::MuteDuration::UntilTomorrow)
+        }
+        MENU_ITEM_DND_UNMUTE_ID => crate::dnd::unmute(app),
+        MENU_ITEM_UPDATE_ID => crate::updater::restart_now(app),
+        #[cfg(target_os = "linux")]
+        MENU_ITEM_SHOW_MENU_BAR_ID => {
+          crate::settings::modify(app, |settings| settings.menu_bar.is_visible = true);
+        }
         _ => {}
       })
       .on_tray_icon_event(|tray, event| tray_click_handler(tray, event));
@@ -89,11 +184,47 @@ impl TrayManager {
     if let Ok(mut tray_lock) = TRAY_HANDLE.lock() {
       *tray_lock = Some(tray_icon.clone());
     }
+    if let Ok(mut menu_lock) = TRAY_MENU.lock() {
+      *menu_lock = Some(menu);
+    }
 
     Ok(Self)
   }
 }
 
+/// Enables "Show Menu Bar" only while the menu bar is hidden.
+#[cfg(target_os = "linux")]
+pub fn set_show_menu_bar_enabled(is_enabled: bool) {
+  let Ok(menu_lock) = TRAY_MENU.lock() else {
+    return;
+  };
+  let Some(menu) = menu_lock.as_ref() else {
+    return;
+  };
+
+  if let Some(MenuItemKind::MenuItem(menu_item)) = menu.get(MENU_ITEM_SHOW_MENU_BAR_ID) {
+    menu_item.set_enabled(is_enabled).unwrap_or_default();
+  }
+}
+
+/// Puts "Restart to Update" on top of the tray menu once an update is downloaded.
+pub fn show_update_item(app: &AppHandle) -> Result<(), tauri::Error> {
+  let Ok(menu_lock) = TRAY_MENU.lock() else {
+    return Ok(());
+  };
+  let Some(menu) = menu_lock.as_ref() else {
+    return Ok(());
+  };
+  if menu.get(MENU_ITEM_UPDATE_ID).is_some() {
+    return Ok(());
+  }
+
+  let update_label = translated_label(MENU_ITEM_UPDATE_ID, MENU_ITEM_UPDATE_LABEL);
+  let update_i = MenuItem::with_id(app, MENU_ITEM_UPDATE_ID, &update_label, true, None::<&str>)?;
+  let separator = PredefinedMenuItem::separator(app)?;
+  menu.insert_items(&[&update_i, &separator], 0)
+}
+
 fn tray_click_handler(tray: &TrayIcon, event: TrayIconEvent) {
   let TrayIconEvent::Click {
     button: MouseButton::Left,
@@ -107,7 +238,7 @@ fn tray_click_handler(tray: &TrayIcon, event: TrayIconEvent) {
   handle_icon_click(tray.app_handle(), false);
 }
 
-fn handle_icon_click(app: &AppHandle, only_open: bool) {
+pub(crate) fn handle_icon_click(app: &AppHandle, only_open: bool) {
   let active_windows = app.windows();
 
   if active_windows.is_empty() {
@@ -196,4 +327,21 @@ mod platform {
   }
 }
 
-pub use platform::set_notifications_count;
+/// Renders the badge, showing it as muted while do-not-disturb is active.
+pub fn set_notifications_count(window: &WebviewWindow, amount: i32, is_muted: bool) {
+  platform::set_notifications_count(window, amount, is_muted || crate::dnd::is_active());
+}
+
+/// Re-renders every badge from the stored count, e.g. after do-not-disturb toggles.
+pub fn refresh_notifications_count(app: &AppHandle) {
+  let Some(state) = app.try_state::<AppState>() else {
+    return;
+  };
+  let Ok(app_state) = state.lock() else {
+    return;
+  };
+
+  for window in app.webview_windows().values() {
+    set_notifications_count(window, app_state.notification_count, app_state.is_muted);
+  }
+}
@@ -0,0 +1,483 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+use tauri_plugin_updater::{Update, Updater, UpdaterExt};
+use url::Url;
+
+use crate::tray::translated_label;
+
+mod rollback;
+mod schedule;
+
+const UPDATER_PLUGIN_NAME: &str = "updater";
+// Replaced in configured endpoints, e.g. `https://example.com/{{channel}}/latest.json`
+const CHANNEL_PLACEHOLDER: &str = "{{channel}}";
+// How often a deferred restart checks whether the call and downloads are over
+const RESTART_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// Label constants, translatable through `set_menu_translations`
+const UPDATE_CHECK_LATEST_ID: &str = "update_check_latest";
+const UPDATE_CHECK_LATEST_LABEL: &str = "You are using the latest version of Telegram.";
+const UPDATE_CHECK_DISABLED_ID: &str = "update_check_disabled";
+const UPDATE_CHECK_DISABLED_LABEL: &str = "Updates are turned off for this version of Telegram.";
+const UPDATE_CHECK_FAILED_ID: &str = "update_check_failed";
+const UPDATE_CHECK_FAILED_LABEL: &str = "Failed to check for updates.";
+const UPDATE_CHECK_READY_ID: &str = "update_check_ready";
+const UPDATE_CHECK_READY_LABEL: &str = "Telegram {version} is ready to install.";
+const UPDATE_CHECK_LATER_ID: &str = "update_check_later";
+const UPDATE_CHECK_LATER_LABEL: &str = "Later";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UpdateChannel {
+  #[default]
+  Stable,
+  Beta,
+}
+
+impl UpdateChannel {
+  fn as_str(self) -> &'static str {
+    match self {
+      UpdateChannel::Stable => "stable",
+      UpdateChannel::Beta => "beta",
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct UpdaterConfig {
+  pub is_enabled: bool,
+  pub channel: UpdateChannel,
+  // Replaces the bundled endpoints, e.g. to test against a local update server
+  pub endpoint: Option<String>,
+}
+
+impl Default for UpdaterConfig {
+  fn default() -> Self {
+    Self {
+      is_enabled: true,
+      channel: UpdateChannel::default(),
+      endpoint: None,
+    }
+  }
+}
+
+impl UpdaterConfig {
+  pub fn validate(&self) -> Result<(), String> {
+    if let Some(endpoint) = &self.endpoint {
+      Url::parse(&endpoint.replace(CHANNEL_PLACEHOLDER, self.channel.as_str()))
+        .map_err(|err| format!("Invalid update endpoint: {}", err))?;
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+  pub version: String,
+  pub current_version: String,
+  pub body: Option<String>,
+  pub date: Option<String>,
+}
+
+impl From<&Update> for UpdateInfo {
+  fn from(update: &Update) -> Self {
+    Self {
+      version: update.version.clone(),
+      current_version: update.current_version.clone(),
+      body: update.body.clone(),
+      date: update.date.map(|date| date.to_string()),
+    }
+  }
+}
+
+struct UpdaterState {
+  update: Option<Update>,
+  // Verified package of `update`, ready to install
+  bytes: Option<Vec<u8>>,
+}
+
+static UPDATER_STATE: LazyLock<Mutex<UpdaterState>> = LazyLock::new(|| {
+  Mutex::new(UpdaterState {
+    update: None,
+    bytes: None,
+  })
+});
+
+// Set by the frontend; installing would end the call
+static IS_CALL_ACTIVE: AtomicBool = AtomicBool::new(false);
+// A restart waits for the call and downloads to end, one at a time
+static IS_RESTART_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Builds without an updater section in their config (no signing key) cannot update themselves.
+pub fn is_supported(app: &AppHandle) -> bool {
+  app.config().plugins.0.contains_key(UPDATER_PLUGIN_NAME)
+}
+
+pub fn init(app: &AppHandle) -> tauri::Result<()> {
+  rollback::init(app);
+
+  if is_supported(app) {
+    app.plugin(tauri_plugin_updater::Builder::new().build())?;
+    schedule::start(app.clone());
+  }
+
+  Ok(())
+}
+
+fn get_config() -> UpdaterConfig {
+  crate::settings::get().updater
+}
+
+pub fn set_config(app: &AppHandle, config: UpdaterConfig) -> Result<(), String> {
+  config.validate()?;
+  crate::settings::modify(app, |settings| settings.updater = config);
+  Ok(())
+}
+
+/// Applies changed settings.
+pub fn apply(previous: &UpdaterConfig, config: &UpdaterConfig) {
+  if previous.channel == config.channel && previous.endpoint == config.endpoint {
+    return;
+  }
+
+  // A pending update from another source must not be installed
+  if let Ok(mut state) = UPDATER_STATE.lock() {
+    state.update = None;
+    state.bytes = None;
+  }
+}
+
+/// Version of the downloaded update waiting to be installed.
+fn ready_version() -> Option<String> {
+  UPDATER_STATE.lock().ok().and_then(|state| {
+    state
+      .bytes
+      .as_ref()
+      .and(state.update.as_ref())
+      .map(|update| update.version.clone())
+  })
+}
+
+pub fn get_status(app: &AppHandle) -> serde_json::Value {
+  let config = get_config();
+  let ready_version = ready_version();
+
+  json!({
+    "isSupported": is_supported(app),
+    "isEnabled": config.is_enabled,
+    "channel": config.channel,
+    "endpoint": config.endpoint,
+    "readyVersion": ready_version,
+  })
+}
+
+/// Endpoints of the selected channel. Endpoints without a placeholder get the channel as a query
+/// parameter, so a server can still tell the channels apart.
+fn channel_endpoints<R: Runtime>(
+  app: &AppHandle<R>,
+  config: &UpdaterConfig,
+) -> Result<Vec<Url>, String> {
+  let endpoints: Vec<String> = match &config.endpoint {
+    Some(endpoint) => vec![endpoint.clone()],
+    None => app
+      .config()
+      .plugins
+      .0
+      .get(UPDATER_PLUGIN_NAME)
+      .and_then(|updater| updater.get("endpoints"))
+      .and_then(|endpoints| endpoints.as_array())
+      .map(|endpoints| {
+        endpoints
+          .iter()
+          .filter_map(|endpoint| endpoint.as_str().map(str::to_string))
+          .collect()
+      })
+      .unwrap_or_default(),
+  };
+
+  endpoints
+    .iter()
+    .map(|endpoint| {
+      let has_placeholder = endpoint.contains(CHANNEL_PLACEHOLDER);
+      let mut url = Url::parse(&endpoint.replace(CHANNEL_PLACEHOLDER, config.channel.as_str()))
+        .map_err(|err| format!("Invalid update endpoint {}: {}", endpoint, err))?;
+      if !has_placeholder && config.channel != UpdateChannel::Stable {
+        url
+          .query_pairs_mut()
+          .append_pair("channel", config.channel.as_str());
+      }
+      Ok(url)
+    })
+    .collect()
+}
+
+fn build_updater<R: Runtime>(
+  app: &AppHandle<R>,
+  config: &UpdaterConfig,
+  rolled_back_version: Option<String>,
+) -> Result<Updater, String> {
+  let channel = config.channel;
+  app
+    .updater_builder()
+    .endpoints(channel_endpoints(app, config)?)
+    .map_err(|err| err.to_string())?
+    // Pre-releases are only offered on the beta channel, and a version that was rolled back
+    // is never offered again
+    .version_comparator(move |current, release| {
+      release.version > current
+        && (channel == UpdateChannel::Beta || release.version.pre.is_empty())
+        && rolled_back_version.as_deref() != Some(release.version.to_string().as_str())
+    })
+    .build()
+    .map_err(|err| err.to_string())
+}
+
+pub async fn check_for_update(app: &AppHandle) -> Result<Option<UpdateInfo>, String> {
+  // `get_updater_status` tells these cases apart, checking just finds nothing
+  let config = get_config();
+  if !is_supported(app) || !config.is_enabled {
+    return Ok(None);
+  }
+
+  let updater = build_updater(app, &config, rollback::rolled_back_version())?;
+  let update = updater.check().await.map_err(|err| err.to_string())?;
+  let info = update.as_ref().map(UpdateInfo::from);
+
+  if let Ok(mut state) = UPDATER_STATE.lock() {
+    let is_same_version = match (&state.update, &update) {
+      (Some(current), Some(new)) => current.version == new.version,
+      _ => false,
+    };
+    if !is_same_version {
+      state.bytes = None;
+      state.update = update;
+    }
+  }
+
+  if let Some(info) = &info {
+    app.emit("update-available", info).unwrap_or_default();
+  }
+
+  Ok(info)
+}
+
+fn show_check_result(app: &AppHandle, message: String, kind: MessageDialogKind) {
+  app
+    .dialog()
+    .message(message)
+    .title(crate::DEFAULT_WINDOW_TITLE)
+    .kind(kind)
+    .show(|_| {});
+}
+
+/// "Check for Updates" from the app menu: checks right away and tells the outcome natively.
+pub fn check_now(app: &AppHandle) {
+  let app = app.clone();
+  tauri::async_runtime::spawn(async move {
+    if !is_supported(&app) || !get_config().is_enabled {
+      let message = translated_label(UPDATE_CHECK_DISABLED_ID, UPDATE_CHECK_DISABLED_LABEL);
+      show_check_result(&app, message, MessageDialogKind::Info);
+      return;
+    }
+
+    if let Err(err) = schedule::check_and_download(&app).await {
+      log::warn!("Update check from the menu failed: {}", err);
+      let message = format!(
+        "{}\n\n{}",
+        translated_label(UPDATE_CHECK_FAILED_ID, UPDATE_CHECK_FAILED_LABEL),
+        err
+      );
+      show_check_result(&app, message, MessageDialogKind::Error);
+      return;
+    }
+
+    let Some(version) = ready_version() else {
+      let message = translated_label(UPDATE_CHECK_LATEST_ID, UPDATE_CHECK_LATEST_LABEL);
+      show_check_result(&app, message, MessageDialogKind::Info);
+      return;
+    };
+
+    let app_handle = app.clone();
+    app
+      .dialog()
+      .message(
+        translated_label(UPDATE_CHECK_READY_ID, UPDATE_CHECK_READY_LABEL)
+          .replace("{version}", &version),
+      )
+      .title(crate::DEFAULT_WINDOW_TITLE)
+      .kind(MessageDialogKind::Info)
+      .buttons(MessageDialogButtons::OkCancelCustom(
+        translated_label(
+          crate::tray::MENU_ITEM_UPDATE_ID,
+          crate::tray::MENU_ITEM_UPDATE_LABEL,
+        ),
+        translated_label(UPDATE_CHECK_LATER_ID, UPDATE_CHECK_LATER_LABEL),
+      ))
+      .show(move |should_restart| {
+        if should_restart {
+          restart_now(&app_handle);
+        }
+      });
+  });
+}
+
+/// Downloads and verifies the update found by the last check.
+pub async fn download_update(app: &AppHandle) -> Result<UpdateInfo, String> {
+  let update = UPDATER_STATE
+    .lock()
+    .map_err(|err| err.to_string())?
+    .update
+    .clone()
+    .ok_or("No update to download")?;
+
+  let mut downloaded: usize = 0;
+  let bytes = update
+    .download(
+      |chunk_length, total| {
+        downloaded += chunk_length;
+        app
+          .emit(
+            "update-download-progress",
+            json!({
+              "version": update.version,
+              "downloaded": downloaded,
+              "total": total,
+            }),
+          )
+          .unwrap_or_default();
+      },
+      || {},
+    )
+    .await
+    .map_err(|err| err.to_string())?;
+
+  let info = UpdateInfo::from(&update);
+  if let Ok(mut state) = UPDATER_STATE.lock() {
+    // The channel may have changed meanwhile, only keep bytes that match the pending update
+    if state
+      .update
+      .as_ref()
+      .is_some_and(|pending| pending.version == update.version)
+    {
+      state.bytes = Some(bytes);
+    }
+  }
+
+  if let Err(err) = crate::tray::show_update_item(app) {
+    log::warn!("Failed to show update menu item: {:?}", err);
+  }
+  // Only one window shows the update banner
+  let window = crate::window::primary_window(app);
+  crate::window::emit_when_ready(
+    app,
+    window.as_ref().map(|window| window.label()),
+    "update-ready",
+    serde_json::to_value(&info).unwrap_or_default(),
+  );
+
+  Ok(info)
+}
+
+/// Called once a window's frontend is ready, which confirms that an updated version starts.
+pub fn confirm_launch(app: &AppHandle, label: &str) {
+  rollback::confirm_launch(app, label);
+}
+
+/// Called when the app quits on its own, so an updated version that quits early is not rolled back.
+pub fn record_clean_exit() {
+  rollback::record_clean_exit();
+}
+
+pub fn set_call_active(is_active: bool) {
+  IS_CALL_ACTIVE.store(is_active, Ordering::Relaxed);
+}
+
+/// Why installing or restarting has to wait, if it does.
+fn install_blocker() -> Option<&'static str> {
+  if IS_CALL_ACTIVE.load(Ordering::Relaxed) {
+    Some("A call is in progress")
+  } else if crate::downloads::has_active_downloads() {
+    Some("Downloads are in progress")
+  } else {
+    None
+  }
+}
+
+fn install_ready_update(app: &AppHandle) -> Result<(), String> {
+  if let Some(reason) = install_blocker() {
+    return Err(reason.to_string());
+  }
+
+  let (update, bytes) = {
+    let mut state = UPDATER_STATE.lock().map_err(|err| err.to_string())?;
+    match (state.update.clone(), state.bytes.take()) {
+      (Some(update), Some(bytes)) => (update, bytes),
+      _ => return Err("No downloaded update to install".to_string()),
+    }
+  };
+
+  rollback::stage(app, &update.version);
+  update.install(&bytes).map_err(|err| err.to_string())
+}
+
+pub fn install_and_relaunch(app: &AppHandle) -> Result<(), String> {
+  install_ready_update(app)?;
+  app.restart();
+}
+
+/// Restarts the app once no call or download would be cut off. Runs in the background, so the
+/// command that changed a setting returns first. `reason` is only logged.
+pub fn restart_when_idle(app: &AppHandle, reason: &'static str) {
+  if IS_RESTART_PENDING.swap(true, Ordering::Relaxed) {
+    return;
+  }
+
+  let app = app.clone();
+  std::thread::spawn(move || {
+    let mut is_reported = false;
+    while let Some(blocker) = install_blocker() {
+      if !is_reported {
+        log::info!("Restart to {} deferred: {}", reason, blocker);
+        app
+          .emit("restart-deferred", json!({ "reason": blocker }))
+          .unwrap_or_default();
+        is_reported = true;
+      }
+      std::thread::sleep(RESTART_POLL_INTERVAL);
+    }
+
+    log::info!("Restarting to {}", reason);
+    // Goes through the exit events, so a downloaded update is installed on the way
+    app.request_restart();
+  });
+}
+
+/// "Restart now" from the tray. A blocked install stays ready for the next quit.
+pub fn restart_now(app: &AppHandle) {
+  if let Err(err) = install_and_relaunch(app) {
+    log::warn!("Not installing update now: {}", err);
+    app
+      .emit("update-install-deferred", json!({ "reason": err }))
+      .unwrap_or_default();
+  }
+}
+
+/// Installs a downloaded update while the app quits, so the next launch runs the new version.
+pub fn install_on_exit(app: &AppHandle) {
+  if ready_version().is_none() {
+    return;
+  }
+
+  if let Err(err) = install_ready_update(app) {
+    log::warn!("Not installing update on quit: {}", err);
+  }
+}
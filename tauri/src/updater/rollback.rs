@@ -0,0 +1,676 @@
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Emitter, Manager};
+
+const ROLLBACK_STATE_FILE: &str = "update-rollback.json";
+const BACKUP_DIR: &str = "previous-version";
+// Launches of a new version that end abnormally before `frontend_ready`, before it is rolled back
+const MAX_FAILED_LAUNCHES: u32 = 3;
+
+/// Written right before an update is installed, and doubles as the startup sentinel of the new
+/// version until its frontend reports readiness.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RollbackState {
+  pub previous_version: String,
+  pub new_version: String,
+  // Installed artifact replaced by the update: AppImage, app bundle or install directory
+  pub target: PathBuf,
+  pub backup: PathBuf,
+  // Launches of the new version that ended abnormally before `frontend_ready`
+  #[serde(default)]
+  pub failed_launches: u32,
+  // The last launch has neither reached `frontend_ready` nor exited cleanly yet. Quick quits and
+  // launches that never get ready while offline exit cleanly, so only crashes and hangs count.
+  #[serde(default)]
+  pub is_running: bool,
+  #[serde(default)]
+  pub is_confirmed: bool,
+  #[serde(default)]
+  pub is_rolled_back: bool,
+  #[serde(default)]
+  pub is_reported: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LaunchAction {
+  Ignore,
+  // Watch the launch until `frontend_ready` or a clean exit
+  Track,
+  RollBack,
+}
+
+impl RollbackState {
+  /// Whether `version` is the new version and has not started successfully yet.
+  fn is_pending(&self, version: &str) -> bool {
+    !self.is_confirmed && !self.is_rolled_back && self.new_version == version
+  }
+
+  /// Records a launch of `version`, counting the previous one as failed if it never finished.
+  pub fn record_launch(&mut self, version: &str) -> LaunchAction {
+    if !self.is_pending(version) {
+      return LaunchAction::Ignore;
+    }
+
+    if self.is_running {
+      self.failed_launches += 1;
+    }
+    if self.failed_launches >= MAX_FAILED_LAUNCHES {
+      self.is_running = false;
+      LaunchAction::RollBack
+    } else {
+      self.is_running = true;
+      LaunchAction::Track
+    }
+  }
+
+  /// Records that the frontend of `version` got ready. Returns whether anything changed.
+  pub fn record_ready(&mut self, version: &str) -> bool {
+    if !self.is_pending(version) {
+      return false;
+    }
+
+    self.is_confirmed = true;
+    self.is_running = false;
+    self.failed_launches = 0;
+    true
+  }
+
+  /// Records that the app quit on its own. Returns whether anything changed.
+  pub fn record_clean_exit(&mut self) -> bool {
+    std::mem::replace(&mut self.is_running, false)
+  }
+
+  /// Version that was rolled back, never to be offered again.
+  pub fn rolled_back_version(&self) -> Option<String> {
+    self.is_rolled_back.then(|| self.new_version.clone())
+  }
+}
+
+struct RollbackStore {
+  path: Option<PathBuf>,
+  state: Option<RollbackState>,
+}
+
+static ROLLBACK_STORE: LazyLock<Mutex<RollbackStore>> = LazyLock::new(|| {
+  Mutex::new(RollbackStore {
+    path: None,
+    state: None,
+  })
+});
+
+fn load_state(path: &Path) -> Option<RollbackState> {
+  let contents = std::fs::read_to_string(path).ok()?;
+  serde_json::from_str(&contents)
+    .inspect_err(|err| log::warn!("Ignoring broken rollback state: {:?}", err))
+    .ok()
+}
+
+fn save_state(path: &Path, state: &RollbackState) {
+  let result = serde_json::to_string_pretty(state)
+    .map_err(std::io::Error::other)
+    .and_then(|contents| crate::settings::write_atomically(path, &contents));
+
+  if let Err(err) = result {
+    log::error!("Failed to save rollback state: {:?}", err);
+  }
+}
+
+/// Tracks the launch of a freshly installed version, rolling it back once it has failed to start
+/// too many times in a row. Runs before any window is created, so crashes while opening one count.
+pub fn init(app: &AppHandle) {
+  let path = app
+    .path()
+    .app_data_dir()
+    .map(|dir| dir.join(ROLLBACK_STATE_FILE))
+    .ok();
+  let mut state = path.as_deref().and_then(load_state);
+  let version = app.package_info().version.to_string();
+
+  if let (Some(path), Some(state)) = (&path, &mut state)
+    && handle_launch(path, state, &version)
+  {
+    app.restart();
+  }
+
+  if let Ok(mut store) = ROLLBACK_STORE.lock() {
+    store.path = path;
+    store.state = state;
+  }
+}
+
+/// Records the launch and restores the backup once too many launches failed. Returns whether the
+/// previous version is back in place, so the app has to restart into it.
+fn handle_launch(path: &Path, state: &mut RollbackState, version: &str) -> bool {
+  match state.record_launch(version) {
+    LaunchAction::Ignore => false,
+    LaunchAction::Track => {
+      // Saved before anything else can crash
+      save_state(path, state);
+      false
+    }
+    LaunchAction::RollBack => {
+      log::error!(
+        "Version {} failed to start {} times, rolling back to {}",
+        state.new_version,
+        state.failed_launches,
+        state.previous_version
+      );
+      match restore(&state.backup, &state.target) {
+        Ok(()) => {
+          state.is_rolled_back = true;
+          save_state(path, state);
+          true
+        }
+        Err(err) => {
+          log::error!("Failed to roll back update: {}", err);
+          save_state(path, state);
+          false
+        }
+      }
+    }
+  }
+}
+
+/// Confirms that the running version starts, and reports a rollback from a previous launch to the
+/// first ready window.
+pub fn confirm_launch(app: &AppHandle, label: &str) {
+  let Ok(mut store) = ROLLBACK_STORE.lock() else {
+    return;
+  };
+  let RollbackStore {
+    path: Some(path),
+    state: Some(state),
+  } = &mut *store
+  else {
+    return;
+  };
+
+  let version = app.package_info().version.to_string();
+  if state.record_ready(&version) {
+    save_state(path, state);
+  } else if state.is_rolled_back && !state.is_reported {
+    app
+      .emit_to(
+        label,
+        "update-rolled-back",
+        json!({
+          "version": state.previous_version,
+          "failedVersion": state.new_version,
+        }),
+      )
+      .unwrap_or_default();
+    state.is_reported = true;
+    save_state(path, state);
+  }
+}
+
+/// Marks the running launch as finished, so a quit before `frontend_ready` is not a failure.
+pub fn record_clean_exit() {
+  let Ok(mut store) = ROLLBACK_STORE.lock() else {
+    return;
+  };
+  if let RollbackStore {
+    path: Some(path),
+    state: Some(state),
+  } = &mut *store
+    && state.record_clean_exit()
+  {
+    save_state(path, state);
+  }
+}
+
+/// Version that was rolled back, never to be offered again.
+pub fn rolled_back_version() -> Option<String> {
+  let store = ROLLBACK_STORE.lock().ok()?;
+  store.state.as_ref()?.rolled_back_version()
+}
+
+/// Keeps a copy of the installed artifact, so the update can be rolled back. Without one, the
+/// update is installed unprotected.
+pub fn stage(app: &AppHandle, new_version: &str) {
+  let Some(target) = installed_artifact() else {
+    log::warn!(
+      "Installed artifact not found, update {} cannot be rolled back",
+      new_version
+    );
+    return;
+  };
+  let Ok(backup_dir) = app.path().app_data_dir().map(|dir| dir.join(BACKUP_DIR)) else {
+    return;
+  };
+  let previous_version = app.package_info().version.to_string();
+  let state = match back_up(&target, &backup_dir, &previous_version, new_version) {
+    Ok(state) => state,
+    Err(err) => {
+      log::error!(
+        "Failed to back up {:?}, update cannot be rolled back: {:?}",
+        target,
+        err
+      );
+      return;
+    }
+  };
+
+  if let Ok(mut store) = ROLLBACK_STORE.lock() {
+    if let Some(path) = &store.path {
+      save_state(path, &state);
+    }
+    store.state = Some(state);
+  }
+}
+
+/// Replaces the backup in `backup_dir` with a copy of the target.
+fn back_up(
+  target: &Path,
+  backup_dir: &Path,
+  previous_version: &str,
+  new_version: &str,
+) -> std::io::Result<RollbackState> {
+  let file_name = target
+    .file_name()
+    .ok_or_else(|| std::io::Error::other("Installed artifact has no file name"))?;
+  let backup = backup_dir.join(file_name);
+
+  remove_path(backup_dir).or_else(|err| match err.kind() {
+    std::io::ErrorKind::NotFound => Ok(()),
+    _ => Err(err),
+  })?;
+  std::fs::create_dir_all(backup_dir)?;
+  copy_path(target, &backup)?;
+
+  Ok(RollbackState {
+    previous_version: previous_version.to_string(),
+    new_version: new_version.to_string(),
+    target: target.to_path_buf(),
+    backup,
+    failed_launches: 0,
+    is_running: false,
+    is_confirmed: false,
+    is_rolled_back: false,
+    is_reported: false,
+  })
+}
+
+/// The file or bundle the updater replaces. Package manager installs are not updated in place.
+#[cfg(target_os = "linux")]
+fn installed_artifact() -> Option<PathBuf> {
+  std::env::var_os("APPIMAGE").map(PathBuf::from)
+}
+
+#[cfg(target_os = "macos")]
+fn installed_artifact() -> Option<PathBuf> {
+  let exe = std::env::current_exe().ok()?;
+  exe
+    .ancestors()
+    .find(|path| path.extension().is_some_and(|extension| extension == "app"))
+    .map(Path::to_path_buf)
+}
+
+// The whole install directory, as updates replace resources and the uninstaller too
+#[cfg(windows)]
+fn installed_artifact() -> Option<PathBuf> {
+  std::env::current_exe()
+    .ok()?
+    .parent()
+    .map(Path::to_path_buf)
+}
+
+/// Puts the backup in place of the target. The target is moved aside first, as running
+/// executables can be renamed but not overwritten on every platform.
+fn restore(backup: &Path, target: &Path) -> Result<(), String> {
+  if !backup.exists() {
+    return Err(format!("Backup {:?} is missing", backup));
+  }
+
+  // Windows does not rename directories holding a running executable, so their files are
+  // replaced one by one instead
+  if cfg!(windows) && backup.is_dir() {
+    return restore_files(backup, target).map_err(|err| err.to_string());
+  }
+
+  let failed = failed_path(target);
+  remove_path(&failed).unwrap_or_default();
+  std::fs::rename(target, &failed).map_err(|err| err.to_string())?;
+
+  if let Err(err) = copy_path(backup, target) {
+    remove_path(target).unwrap_or_default();
+    std::fs::rename(&failed, target).unwrap_or_default();
+    return Err(err.to_string());
+  }
+
+  // Fails on Windows while the old executable is still running, it is replaced next time
+  remove_path(&failed).unwrap_or_default();
+  Ok(())
+}
+
+fn failed_path(target: &Path) -> PathBuf {
+  let mut failed_name = target.file_name().unwrap_or_default().to_os_string();
+  failed_name.push(".failed");
+  target.with_file_name(failed_name)
+}
+
+/// Copies every file of the backup over the one in the target directory, moving the existing one
+/// aside first. Files added by the failed version stay, as nothing of the previous one uses them.
+fn restore_files(backup: &Path, target: &Path) -> std::io::Result<()> {
+  std::fs::create_dir_all(target)?;
+  for entry in std::fs::read_dir(backup)? {
+    let entry = entry?;
+    let source = entry.path();
+    let destination = target.join(entry.file_name());
+
+    if entry.file_type()?.is_dir() {
+      restore_files(&source, &destination)?;
+      continue;
+    }
+
+    let failed = failed_path(&destination);
+    if destination.exists() {
+      remove_path(&failed).unwrap_or_default();
+      std::fs::rename(&destination, &failed)?;
+    }
+    if let Err(err) = std::fs::copy(&source, &destination) {
+      std::fs::rename(&failed, &destination).unwrap_or_default();
+      return Err(err);
+    }
+    // Fails while the old executable is still running, it is replaced next time
+    remove_path(&failed).unwrap_or_default();
+  }
+
+  Ok(())
+}
+
+fn remove_path(path: &Path) -> std::io::Result<()> {
+  if path.is_dir() && !path.is_symlink() {
+    std::fs::remove_dir_all(path)
+  } else {
+    std::fs::remove_file(path)
+  }
+}
+
+/// Copies a file or a directory tree, keeping symlinks as they are, which app bundles rely on.
+fn copy_path(source: &Path, destination: &Path) -> std::io::Result<()> {
+  let metadata = std::fs::symlink_metadata(source)?;
+
+  #[cfg(unix)]
+  if metadata.file_type().is_symlink() {
+    return std::os::unix::fs::symlink(std::fs::read_link(source)?, destination);
+  }
+
+  if metadata.is_dir() {
+    std::fs::create_dir_all(destination)?;
+    for entry in std::fs::read_dir(source)? {
+      let entry = entry?;
+      copy_path(&entry.path(), &destination.join(entry.file_name()))?;
+    }
+    std::fs::set_permissions(destination, metadata.permissions())
+  } else {
+    std::fs::copy(source, destination).map(|_| ())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::{BufRead, BufReader, Write};
+  use std::net::TcpListener;
+
+  use super::*;
+  use crate::updater::{UpdaterConfig, build_updater};
+
+  const VERSION: &str = "2.0.0";
+  const PREVIOUS_PACKAGE: &[u8] = b"Telegram 1.0.0";
+  const PACKAGE: &[u8] = b"Telegram 2.0.0";
+  // Minisign key and signature of `PACKAGE`, both base64 encoded as in the updater config
+  const PUBKEY: &str = "dW50cnVzdGVkIGNvbW1lbnQ6IG1pbmlzaWduIHB1YmxpYyBrZXkgRTJGOTczOTZBMTE1NkFCNgpSV1MyYWhXaGxuUDU0aVZtOHFwUG9PWDl3N3loWEU5QjhHQ3BmMSs3OW83QmNwVnA0TjM5eTZGdQo=";
+  const SIGNATURE: &str = "dW50cnVzdGVkIGNvbW1lbnQ6IHNpZ25hdHVyZSBmcm9tIHRhdXJpIHNlY3JldCBrZXkKUlVTMmFoV2hsblA1NGpXaThLQ0tpV2dRTFhNdjg2aktNeWh3NnY2NkEzbnN4M3ZZYlhaT2FpLzg2c3hvL2FQUGNmVmJWOU05NXpVVVhVN3RpUzlpZFhDOEtKdjk1ZGEwcVE4PQp0cnVzdGVkIGNvbW1lbnQ6IHRpbWVzdGFtcDoxNzYwMDAwMDAwCWZpbGU6VGVsZWdyYW0uQXBwSW1hZ2UKR2RKcHgwem9kOGhmZWIydDhhcmx3bkR5a0dkazlJNEdIS1JZdUtGRmJiTTNTUjljV1ROM3ZJMkZjU25HcFZvbFhWd2I1bG9kemN0Zy9GUnZNNVJNRFE9PQo=";
+
+  struct TempDir(PathBuf);
+
+  impl TempDir {
+    fn new() -> Self {
+      let path = std::env::temp_dir().join(format!("rollback-test-{}", uuid::Uuid::new_v4()));
+      std::fs::create_dir_all(&path).unwrap();
+      TempDir(path)
+    }
+  }
+
+  impl Drop for TempDir {
+    fn drop(&mut self) {
+      std::fs::remove_dir_all(&self.0).unwrap_or_default();
+    }
+  }
+
+  /// An installed new version with a backup of the previous one, as `stage` leaves them.
+  fn staged(dir: &Path) -> (PathBuf, RollbackState) {
+    let target = dir.join("Telegram.AppImage");
+    let backup = dir.join(BACKUP_DIR).join("Telegram.AppImage");
+    std::fs::create_dir_all(backup.parent().unwrap()).unwrap();
+    std::fs::write(&target, "new").unwrap();
+    std::fs::write(&backup, "previous").unwrap();
+
+    let state = RollbackState {
+      previous_version: "1.0.0".to_string(),
+      new_version: VERSION.to_string(),
+      target,
+      backup,
+      failed_launches: 0,
+      is_running: false,
+      is_confirmed: false,
+      is_rolled_back: false,
+      is_reported: false,
+    };
+    let path = dir.join(ROLLBACK_STATE_FILE);
+    save_state(&path, &state);
+    (path, state)
+  }
+
+  /// Launches the app the way `init` does after a restart, reading the state from disk.
+  fn launch(path: &Path) -> (RollbackState, bool) {
+    let mut state = load_state(path).unwrap();
+    let is_restored = handle_launch(path, &mut state, VERSION);
+    (state, is_restored)
+  }
+
+  #[test]
+  fn rolls_back_after_repeated_crashes() {
+    let dir = TempDir::new();
+    let (path, staged) = staged(&dir.0);
+
+    // Every launch crashes, leaving the launch unfinished on disk
+    for failed_launches in 0..MAX_FAILED_LAUNCHES {
+      let (state, is_restored) = launch(&path);
+      assert!(!is_restored);
+      assert!(state.is_running);
+      assert_eq!(state.failed_launches, failed_launches);
+    }
+
+    let (state, is_restored) = launch(&path);
+    assert!(is_restored);
+    assert!(state.is_rolled_back);
+    assert_eq!(load_state(&path).unwrap(), state);
+    assert_eq!(std::fs::read_to_string(&staged.target).unwrap(), "previous");
+    assert!(!failed_path(&staged.target).exists());
+
+    // The restored previous version is not tracked
+    let mut state = load_state(&path).unwrap();
+    assert_eq!(state.record_launch("1.0.0"), LaunchAction::Ignore);
+    assert_eq!(state.record_launch(VERSION), LaunchAction::Ignore);
+  }
+
+  #[test]
+  fn clean_exits_do_not_count() {
+    let dir = TempDir::new();
+    let (path, staged) = staged(&dir.0);
+
+    for _ in 0..MAX_FAILED_LAUNCHES * 2 {
+      let (mut state, is_restored) = launch(&path);
+      assert!(!is_restored);
+      assert!(state.record_clean_exit());
+      save_state(&path, &state);
+    }
+
+    let state = load_state(&path).unwrap();
+    assert_eq!(state.failed_launches, 0);
+    assert_eq!(std::fs::read_to_string(&staged.target).unwrap(), "new");
+  }
+
+  #[test]
+  fn ready_launch_confirms_the_version() {
+    let dir = TempDir::new();
+    let (path, _) = staged(&dir.0);
+
+    launch(&path);
+    let (mut state, _) = launch(&path);
+    assert_eq!(state.failed_launches, 1);
+
+    assert!(state.record_ready(VERSION));
+    assert_eq!(state.failed_launches, 0);
+    assert!(!state.record_ready(VERSION));
+    assert!(!state.record_clean_exit());
+    assert_eq!(state.record_launch(VERSION), LaunchAction::Ignore);
+  }
+
+  #[test]
+  fn restores_install_directories_file_by_file() {
+    let dir = TempDir::new();
+    let target = dir.0.join("install");
+    let backup = dir.0.join(BACKUP_DIR).join("install");
+    std::fs::create_dir_all(target.join("resources")).unwrap();
+    std::fs::create_dir_all(backup.join("resources")).unwrap();
+    std::fs::write(target.join("app.exe"), "new").unwrap();
+    std::fs::write(target.join("resources").join("added.dat"), "new").unwrap();
+    std::fs::write(backup.join("app.exe"), "previous").unwrap();
+    std::fs::write(backup.join("resources").join("icon.ico"), "previous").unwrap();
+
+    restore_files(&backup, &target).unwrap();
+
+    assert_eq!(
+      std::fs::read_to_string(target.join("app.exe")).unwrap(),
+      "previous"
+    );
+    assert_eq!(
+      std::fs::read_to_string(target.join("resources").join("icon.ico")).unwrap(),
+      "previous"
+    );
+    assert!(target.join("resources").join("added.dat").exists());
+    assert!(!failed_path(&target.join("app.exe")).exists());
+  }
+
+  /// Serves a release manifest of `VERSION` and its package, like the publish repository does.
+  /// Returns the endpoint of the manifest.
+  fn serve_update(package: &'static [u8]) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let manifest = json!({
+      "version": VERSION,
+      "notes": "Fake release",
+      "url": format!("http://{}/Telegram.AppImage", address),
+      "signature": SIGNATURE,
+    })
+    .to_string();
+
+    std::thread::spawn(move || {
+      for mut stream in listener.incoming().flatten() {
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap_or_default();
+        // Headers end with an empty line
+        let mut header = String::new();
+        while reader.read_line(&mut header).is_ok_and(|length| length > 2) {
+          header.clear();
+        }
+
+        let body = if request_line.starts_with("GET /latest.json ") {
+          manifest.as_bytes()
+        } else {
+          package
+        };
+        let head = format!(
+          "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+          body.len()
+        );
+        stream.write_all(head.as_bytes()).unwrap_or_default();
+        stream.write_all(body).unwrap_or_default();
+      }
+    });
+
+    format!("http://{}/latest.json", address)
+  }
+
+  fn mock_app() -> tauri::App<tauri::test::MockRuntime> {
+    let mut context = tauri::test::mock_context(tauri::test::noop_assets());
+    context.package_info_mut().version = "1.0.0".parse().unwrap();
+    context
+      .config_mut()
+      .plugins
+      .0
+      .insert("updater".to_string(), json!({ "pubkey": PUBKEY }));
+
+    tauri::test::mock_builder()
+      .plugin(tauri_plugin_updater::Builder::new().build())
+      .build(context)
+      .unwrap()
+  }
+
+  fn updater_config(endpoint: String) -> UpdaterConfig {
+    UpdaterConfig {
+      endpoint: Some(endpoint),
+      ..UpdaterConfig::default()
+    }
+  }
+
+  #[test]
+  fn rolls_back_a_downloaded_update_that_keeps_crashing() {
+    let dir = TempDir::new();
+    let target = dir.0.join("Telegram.AppImage");
+    std::fs::write(&target, PREVIOUS_PACKAGE).unwrap();
+    let app = mock_app();
+    let config = updater_config(serve_update(PACKAGE));
+
+    let updater = build_updater(app.handle(), &config, None).unwrap();
+    let update = tauri::async_runtime::block_on(updater.check())
+      .unwrap()
+      .expect("the fake release is newer");
+    assert_eq!(update.version, VERSION);
+    assert_eq!(update.current_version, "1.0.0");
+    let bytes = tauri::async_runtime::block_on(update.download(|_, _| {}, || {})).unwrap();
+    assert_eq!(bytes, PACKAGE);
+
+    // Staged and installed the way `install_ready_update` does, minus the restart
+    let path = dir.0.join(ROLLBACK_STATE_FILE);
+    let state = back_up(&target, &dir.0.join(BACKUP_DIR), "1.0.0", VERSION).unwrap();
+    save_state(&path, &state);
+    std::fs::write(&target, &bytes).unwrap();
+
+    for _ in 0..MAX_FAILED_LAUNCHES {
+      let (_, is_restored) = launch(&path);
+      assert!(!is_restored);
+    }
+    let (state, is_restored) = launch(&path);
+    assert!(is_restored);
+    assert_eq!(std::fs::read(&target).unwrap(), PREVIOUS_PACKAGE);
+
+    // The failed version is never offered again
+    let updater = build_updater(app.handle(), &config, state.rolled_back_version()).unwrap();
+    assert!(
+      tauri::async_runtime::block_on(updater.check())
+        .unwrap()
+        .is_none()
+    );
+  }
+
+  #[test]
+  fn rejects_packages_that_do_not_match_the_signature() {
+    let app = mock_app();
+    let config = updater_config(serve_update(b"Telegram 2.0.0 with a payload"));
+
+    let updater = build_updater(app.handle(), &config, None).unwrap();
+    let update = tauri::async_runtime::block_on(updater.check())
+      .unwrap()
+      .expect("the fake release is newer");
+    assert!(tauri::async_runtime::block_on(update.download(|_, _| {}, || {})).is_err());
+  }
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use tauri::AppHandle;
+
+// Leaves startup to the app itself
+const INITIAL_DELAY: Duration = Duration::from_secs(5 * 60);
+const CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+const RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+// Spreads checks of many clients over ±20% of each delay
+const JITTER_RATIO: f64 = 0.2;
+
+/// Delay before the next check. Failures back off exponentially from `RETRY_DELAY` up to the
+/// regular interval. `jitter` is a random number in `-1.0..=1.0`.
+pub fn next_delay(failures: u32, jitter: f64) -> Duration {
+  let base = if failures == 0 {
+    CHECK_INTERVAL
+  } else {
+    RETRY_DELAY
+      .saturating_mul(2u32.saturating_pow(failures - 1))
+      .min(CHECK_INTERVAL)
+  };
+
+  base.mul_f64(1.0 + JITTER_RATIO * jitter.clamp(-1.0, 1.0))
+}
+
+fn random_jitter() -> f64 {
+  rand::random_range(-1.0..=1.0)
+}
+
+/// Checks for updates in the background for the lifetime of the app, downloading new versions so
+/// they are ready to install on the next quit.
+pub fn start(app: AppHandle) {
+  std::thread::spawn(move || {
+    let mut delay = INITIAL_DELAY.mul_f64(1.0 + JITTER_RATIO * random_jitter());
+    let mut failures: u32 = 0;
+
+    loop {
+      std::thread::sleep(delay);
+
+      match tauri::async_runtime::block_on(check_and_download(&app)) {
+        Ok(()) => failures = 0,
+        Err(err) => {
+          failures = failures.saturating_add(1);
+          log::warn!(
+            "Background update check failed ({} in a row): {}",
+            failures,
+            err
+          );
+        }
+      }
+
+      delay = next_delay(failures, random_jitter());
+    }
+  });
+}
+
+pub(super) async fn check_and_download(app: &AppHandle) -> Result<(), String> {
+  let Some(info) = super::check_for_update(app).await? else {
+    return Ok(());
+  };
+
+  if super::ready_version().as_deref() == Some(info.version.as_str()) {
+    return Ok(());
+  }
+
+  super::download_update(app).await.map(|_| ())
+}
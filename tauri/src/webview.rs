@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AutoplayPolicy {
+  // Media may start without user interaction, e.g. ringtones of incoming calls
+  #[default]
+  NoUserGestureRequired,
+  UserGestureRequired,
+  DocumentUserActivationRequired,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WebviewSettings {
+  pub is_hardware_acceleration_enabled: bool,
+  pub autoplay_policy: AutoplayPolicy,
+  pub is_smooth_scrolling_enabled: bool,
+  // Passed to the engine as is: Chromium switches for WebView2, `NAME=value` environment variables
+  // for WebKitGTK
+  pub raw_args: String,
+}
+
+impl Default for WebviewSettings {
+  fn default() -> Self {
+    Self {
+      is_hardware_acceleration_enabled: true,
+      autoplay_policy: AutoplayPolicy::default(),
+      is_smooth_scrolling_enabled: true,
+      raw_args: String::new(),
+    }
+  }
+}
+
+// Switches that would hand control of the browser process to whoever can change the settings
+#[cfg(windows)]
+const FORBIDDEN_SWITCHES: &[&str] = &[
+  "--remote-debugging",
+  "--remote-allow-origins",
+  "--load-extension",
+  "--disable-web-security",
+  "--user-data-dir",
+  "--browser-subprocess-path",
+  "--renderer-cmd-prefix",
+  "--utility-cmd-prefix",
+  "--gpu-launcher",
+  "--proxy-",
+];
+
+// Only variables read by WebKitGTK and the graphics stack, not ones like `LD_PRELOAD`
+#[cfg(target_os = "linux")]
+const ALLOWED_VARIABLE_PREFIXES: &[&str] = &["WEBKIT_", "GDK_", "LIBGL_", "MESA_", "__GL_"];
+
+#[cfg(windows)]
+fn parse_raw_args(raw_args: &str) -> Result<Vec<&str>, String> {
+  raw_args
+    .split_whitespace()
+    .map(|arg| {
+      if !arg.starts_with("--") || arg.contains('"') {
+        return Err(format!("Invalid browser switch {:?}", arg));
+      }
+      if FORBIDDEN_SWITCHES
+        .iter()
+        .any(|switch| arg.starts_with(switch))
+      {
+        return Err(format!("Browser switch {:?} is not allowed", arg));
+      }
+      Ok(arg)
+    })
+    .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn parse_raw_args(raw_args: &str) -> Result<Vec<(&str, &str)>, String> {
+  raw_args
+    .split_whitespace()
+    .map(|arg| {
+      let (name, value) = arg
+        .split_once('=')
+        .filter(|(name, _)| {
+          !name.is_empty()
+            && name
+              .chars()
+              .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        })
+        .ok_or_else(|| format!("Invalid environment variable {:?}", arg))?;
+      if !ALLOWED_VARIABLE_PREFIXES
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+      {
+        return Err(format!("Environment variable {} is not allowed", name));
+      }
+      Ok((name, value))
+    })
+    .collect()
+}
+
+/// Rejects what the webview engine of this platform cannot apply.
+#[cfg(not(target_os = "macos"))]
+pub fn validate_for_engine(settings: &WebviewSettings) -> Result<(), String> {
+  parse_raw_args(&settings.raw_args).map(|_| ())
+}
+
+// WKWebView takes no arguments, and other flags have no equivalent there
+#[cfg(target_os = "macos")]
+pub fn validate_for_engine(settings: &WebviewSettings) -> Result<(), String> {
+  if !settings.raw_args.trim().is_empty() {
+    return Err("Raw webview arguments are not supported on macOS".to_string());
+  }
+  Ok(())
+}
+
+/// Sets the environment variables WebKitGTK reads once, when the first webview is created.
+/// Variables already set when the app was launched take precedence.
+#[cfg(target_os = "linux")]
+pub fn init(settings: &WebviewSettings) {
+  let mut variables = Vec::new();
+  if !settings.is_hardware_acceleration_enabled {
+    // Broken GPU drivers usually fail in DMA-BUF rendering or in the compositor
+    variables.push(("WEBKIT_DISABLE_DMABUF_RENDERER", "1"));
+    variables.push(("WEBKIT_DISABLE_COMPOSITING_MODE", "1"));
+  }
+  match parse_raw_args(&settings.raw_args) {
+    Ok(raw_variables) => variables.extend(raw_variables),
+    Err(err) => log::warn!("Ignoring raw webview arguments: {}", err),
+  }
+
+  for (name, value) in variables {
+    if std::env::var_os(name).is_some() {
+      continue;
+    }
+    // SAFETY: runs on the main thread during setup, before the first webview, while nothing else
+    // in the app reads the environment
+    unsafe { std::env::set_var(name, value) };
+  }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn init(_settings: &WebviewSettings) {}
+
+/// Chromium switches for WebView2, which apply to the whole browser process.
+#[cfg(windows)]
+pub fn browser_args(settings: &WebviewSettings) -> String {
+  let mut args = vec![match settings.autoplay_policy {
+    AutoplayPolicy::NoUserGestureRequired => "--autoplay-policy=no-user-gesture-required",
+    AutoplayPolicy::UserGestureRequired => "--autoplay-policy=user-gesture-required",
+    AutoplayPolicy::DocumentUserActivationRequired => {
+      "--autoplay-policy=document-user-activation-required"
+    }
+  }];
+  if !settings.is_hardware_acceleration_enabled {
+    args.push("--disable-gpu");
+  }
+  if !settings.is_smooth_scrolling_enabled {
+    args.push("--disable-smooth-scrolling");
+  }
+  match parse_raw_args(&settings.raw_args) {
+    Ok(raw_args) => args.extend(raw_args),
+    Err(err) => log::warn!("Ignoring raw webview arguments: {}", err),
+  }
+
+  args.join(" ")
+}
+
+/// Applies the flags WebKitGTK takes per webview. They can change while the webview is running.
+#[cfg(target_os = "linux")]
+pub fn apply_to_webview(window: &tauri::WebviewWindow, settings: &WebviewSettings) {
+  let settings = settings.clone();
+  let result = window.with_webview(move |webview| {
+    use webkit2gtk::{HardwareAccelerationPolicy, SettingsExt, WebViewExt};
+
+    let Some(webkit_settings) = webview.inner().settings() else {
+      return;
+    };
+    webkit_settings.set_media_playback_requires_user_gesture(
+      settings.autoplay_policy != AutoplayPolicy::NoUserGestureRequired,
+    );
+    webkit_settings.set_enable_smooth_scrolling(settings.is_smooth_scrolling_enabled);
+    if !settings.is_hardware_acceleration_enabled {
+      webkit_settings.set_hardware_acceleration_policy(HardwareAccelerationPolicy::Never);
+    }
+  });
+
+  if let Err(err) = result {
+    log::error!("Failed to apply webview settings: {:?}", err);
+  }
+}
+
+/// Whether the change only applies to a new browser process, as WebView2 switches and WebKitGTK
+/// environment variables are read once.
+fn needs_restart(previous: &WebviewSettings, settings: &WebviewSettings) -> bool {
+  if cfg!(windows) {
+    previous != settings
+  } else if cfg!(target_os = "linux") {
+    previous.is_hardware_acceleration_enabled != settings.is_hardware_acceleration_enabled
+      || previous.raw_args != settings.raw_args
+  } else {
+    false
+  }
+}
+
+/// Applies changed settings to the open windows, restarting the app if the engine needs it. The
+/// restart waits until no call or download is in progress.
+pub fn apply(app: &AppHandle, previous: &WebviewSettings, settings: &WebviewSettings) {
+  if needs_restart(previous, settings) {
+    crate::updater::restart_when_idle(app, "apply webview settings");
+  }
+
+  #[cfg(target_os = "linux")]
+  {
+    use tauri::Manager;
+
+    for window in app.webview_windows().values() {
+      apply_to_webview(window, settings);
+    }
+  }
+}
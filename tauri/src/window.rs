@@ -1,12 +1,313 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::LazyLock;
 
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager};
+use url::Url;
+
+use crate::{BASE_URL, LAST_URL};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WindowRole {
+  // Opened while no other window exists
+  Main,
+  Secondary,
+}
+
 #[derive(Clone)]
 pub struct WindowState {
   pub title: String,
   pub is_overlay: bool,
   pub is_mobile: bool,
+  pub is_ready: bool,
+  pub is_focused: bool,
+  pub active_chat_id: Option<String>,
+  pub role: WindowRole,
+  pub zoom: f64,
+  // Last page of the app the window showed, to go back to when it leaves the app
+  pub app_url: Url,
+  // Late events the current page listens to
+  pub subscribed_events: HashSet<String>,
 }
 
 pub static WINDOW_STATES: LazyLock<std::sync::Mutex<HashMap<String, WindowState>>> =
   LazyLock::new(|| std::sync::Mutex::new(HashMap::new()));
+
+/// Updates the focus flag and returns the chat that became visible to the user, if any.
+pub fn set_window_focused(label: &str, is_focused: bool) -> Option<String> {
+  let mut states = WINDOW_STATES.lock().ok()?;
+  let state = states.get_mut(label)?;
+  state.is_focused = is_focused;
+
+  if is_focused {
+    state.active_chat_id.clone()
+  } else {
+    None
+  }
+}
+
+/// Updates the open chat and returns it if the window is focused, so the chat is visible.
+pub fn set_active_chat(label: &str, chat_id: Option<String>) -> Option<String> {
+  let mut states = WINDOW_STATES.lock().ok()?;
+  let state = states.get_mut(label)?;
+  state.active_chat_id = chat_id;
+
+  if state.is_focused {
+    state.active_chat_id.clone()
+  } else {
+    None
+  }
+}
+
+pub fn is_chat_active_in_focused_window(chat_id: &str) -> bool {
+  WINDOW_STATES
+    .lock()
+    .map(|states| {
+      states
+        .values()
+        .any(|state| state.is_focused && state.active_chat_id.as_deref() == Some(chat_id))
+    })
+    .unwrap_or(false)
+}
+
+/// Window that app-wide actions go to: the focused one, then the main one, then any.
+pub fn primary_window(app: &AppHandle) -> Option<tauri::WebviewWindow> {
+  let windows = app.webview_windows();
+  let label = WINDOW_STATES.lock().ok().and_then(|states| {
+    states
+      .iter()
+      .filter(|(label, _)| windows.contains_key(*label))
+      .max_by_key(|(_, state)| (state.is_focused, state.role == WindowRole::Main))
+      .map(|(label, _)| label.clone())
+  });
+
+  label
+    .and_then(|label| windows.get(&label).cloned())
+    .or_else(|| windows.into_values().next())
+}
+
+struct PendingEvent {
+  // `None` means any window that becomes ready may receive the event
+  target: Option<String>,
+  event: String,
+  payload: Value,
+}
+
+static PENDING_EVENTS: LazyLock<std::sync::Mutex<Vec<PendingEvent>>> =
+  LazyLock::new(|| std::sync::Mutex::new(Vec::new()));
+
+// Listened to by components that mount after `frontend_ready`, so they wait for `subscribe_event`
+const LATE_EVENTS: &[&str] = &["deeplink"];
+
+fn is_late_event(event: &str) -> bool {
+  LATE_EVENTS.contains(&event)
+}
+
+fn is_window_ready(label: &str, event: &str) -> bool {
+  WINDOW_STATES
+    .lock()
+    .map(|states| {
+      states.get(label).is_some_and(|state| {
+        state.is_ready && (!is_late_event(event) || state.subscribed_events.contains(event))
+      })
+    })
+    .unwrap_or(false)
+}
+
+fn queue_event(target: Option<String>, event: &str, payload: Value) {
+  if let Ok(mut pending) = PENDING_EVENTS.lock() {
+    pending.push(PendingEvent {
+      target,
+      event: event.to_string(),
+      payload,
+    });
+  }
+}
+
+/// Emits an event to the given window once its frontend has reported readiness, or subscribed to
+/// it for late events.
+/// Hidden windows keep their webview running, so they receive events directly. If the window
+/// is gone, the event goes to any other ready window, or waits for a freshly opened one.
+pub fn emit_when_ready(app: &AppHandle, label: Option<&str>, event: &str, payload: Value) {
+  if let Some(label) = label {
+    if let Some(window) = app.get_webview_window(label) {
+      if is_window_ready(label, event) {
+        window.emit_to(label, event, payload).unwrap_or_default();
+      } else {
+        queue_event(Some(label.to_string()), event, payload);
+      }
+      return;
+    }
+  }
+
+  let ready_window = app
+    .webview_windows()
+    .into_keys()
+    .find(|label| is_window_ready(label, event));
+
+  if let Some(ready_label) = ready_window {
+    app
+      .emit_to(&ready_label, event, payload)
+      .unwrap_or_default();
+    return;
+  }
+
+  queue_event(None, event, payload);
+
+  if app.webview_windows().is_empty() {
+    let url = if let Ok(last_url) = LAST_URL.lock() {
+      last_url.clone()
+    } else {
+      BASE_URL.to_string()
+    };
+
+    if let Err(err) = crate::open_new_window(app.clone(), url) {
+      log::error!("Failed to open window for pending event: {:?}", err);
+    }
+  }
+}
+
+/// Takes the queued events for the window or for any window whose name passes `is_wanted`.
+fn take_pending_events(label: &str, is_wanted: impl Fn(&str) -> bool) -> Vec<PendingEvent> {
+  let Ok(mut pending) = PENDING_EVENTS.lock() else {
+    return Vec::new();
+  };
+  let (matching, rest) = pending.drain(..).partition(|event| {
+    event.target.as_deref().is_none_or(|target| target == label) && is_wanted(&event.event)
+  });
+  *pending = rest;
+  matching
+}
+
+fn deliver(window: &tauri::WebviewWindow, events: Vec<PendingEvent>) {
+  for pending_event in events {
+    window
+      .emit_to(window.label(), &pending_event.event, pending_event.payload)
+      .unwrap_or_default();
+  }
+}
+
+/// Forgets the readiness and the listeners of the previous page, as a new one starts loading.
+pub fn reset_window_page(label: &str) {
+  if let Ok(mut states) = WINDOW_STATES.lock() {
+    if let Some(state) = states.get_mut(label) {
+      state.is_ready = false;
+      state.subscribed_events.clear();
+    }
+  }
+}
+
+pub fn set_app_url(label: &str, url: Url) {
+  if let Ok(mut states) = WINDOW_STATES.lock()
+    && let Some(state) = states.get_mut(label)
+  {
+    state.app_url = url;
+  }
+}
+
+pub fn app_url(label: &str) -> Option<Url> {
+  let states = WINDOW_STATES.lock().ok()?;
+  states.get(label).map(|state| state.app_url.clone())
+}
+
+/// Marks the window as ready and delivers every event that was waiting for it, except late ones.
+pub fn mark_window_ready(window: &tauri::WebviewWindow) {
+  let label = window.label();
+
+  if let Ok(mut states) = WINDOW_STATES.lock() {
+    if let Some(state) = states.get_mut(label) {
+      state.is_ready = true;
+    }
+  }
+
+  deliver(
+    window,
+    take_pending_events(label, |event| !is_late_event(event)),
+  );
+}
+
+/// Records that the frontend of the window listens to `event`, and delivers what waited for it.
+pub fn subscribe_event(window: &tauri::WebviewWindow, event: &str) {
+  let label = window.label();
+
+  if let Ok(mut states) = WINDOW_STATES.lock() {
+    if let Some(state) = states.get_mut(label) {
+      state.subscribed_events.insert(event.to_string());
+    }
+  }
+
+  if is_window_ready(label, event) {
+    deliver(
+      window,
+      take_pending_events(label, |pending| pending == event),
+    );
+  }
+}
+
+pub fn unsubscribe_event(label: &str, event: &str) {
+  if let Ok(mut states) = WINDOW_STATES.lock() {
+    if let Some(state) = states.get_mut(label) {
+      state.subscribed_events.remove(event);
+    }
+  }
+}
+
+/// Re-targets events queued for a destroyed window, so they are not lost with it.
+pub fn release_pending_events(label: &str) {
+  if let Ok(mut pending) = PENDING_EVENTS.lock() {
+    for event in pending.iter_mut() {
+      if event.target.as_deref() == Some(label) {
+        event.target = None;
+      }
+    }
+  }
+}
+
+/// Reopens every window on the same page with the same role, so settings that only apply when a
+/// webview is created take effect. WebView2 shares one browser process, and its switches, between
+/// all windows, so the app is restarted there instead, once that cuts off no call or download.
+#[cfg(windows)]
+pub fn recreate_windows(app: &AppHandle) {
+  crate::updater::restart_when_idle(app, "apply the proxy");
+}
+
+#[cfg(not(windows))]
+pub fn recreate_windows(app: &AppHandle) {
+  let app_handle = app.clone();
+  let result = app.run_on_main_thread(move || {
+    for (label, window) in app_handle.webview_windows() {
+      let role = WINDOW_STATES
+        .lock()
+        .ok()
+        .and_then(|states| states.get(&label).map(|state| state.role))
+        .unwrap_or(WindowRole::Secondary);
+      let url = window
+        .url()
+        .map(|url| url.to_string())
+        .unwrap_or_else(|_| BASE_URL.to_string());
+
+      let new_window = match crate::open_window_with_role(app_handle.clone(), url, role) {
+        Ok(new_window) => new_window,
+        Err(err) => {
+          log::error!("Failed to recreate window: {:?}", err);
+          continue;
+        }
+      };
+
+      if let (Ok(position), Ok(size)) = (window.outer_position(), window.inner_size()) {
+        new_window.set_position(position).unwrap_or_default();
+        new_window.set_size(size).unwrap_or_default();
+      }
+      if !window.is_visible().unwrap_or(true) {
+        new_window.hide().unwrap_or_default();
+      }
+      window.destroy().unwrap_or_default();
+    }
+  });
+
+  if let Err(err) = result {
+    log::error!("Failed to recreate windows: {:?}", err);
+  }
+}
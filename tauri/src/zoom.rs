@@ -0,0 +1,170 @@
+use serde_json::json;
+use tauri::{AppHandle, Emitter, WebviewWindow};
+
+use crate::window::{WINDOW_STATES, WindowRole};
+
+const MIN_ZOOM: f64 = 0.5;
+const MAX_ZOOM: f64 = 3.0;
+// Same steps as in browsers
+const ZOOM_LEVELS: &[f64] = &[
+  0.5, 0.67, 0.75, 0.8, 0.9, 1.0, 1.1, 1.25, 1.5, 1.75, 2.0, 2.5, 3.0,
+];
+const ZOOM_EPSILON: f64 = 0.001;
+
+#[cfg(target_os = "macos")]
+const SHORTCUT_MODIFIER: &str = "metaKey";
+#[cfg(not(target_os = "macos"))]
+const SHORTCUT_MODIFIER: &str = "ctrlKey";
+
+/// Handles zoom shortcuts in every frame. Key events inside iframes never reach the top document,
+/// so frames forward them, as they may be cross-origin and unable to call commands themselves.
+/// Forwarded messages carry a token only this script knows, and the top document accepts them only
+/// from its own frames, so embedded pages cannot zoom the window by posting messages themselves.
+pub fn shortcuts_script() -> String {
+  format!(
+    r#"(function () {{
+  var ACTIONS = {{ '=': 'zoom_in', '+': 'zoom_in', '-': 'zoom_out', '_': 'zoom_out', '0': 'zoom_reset' }};
+  var MESSAGE_KEY = '__tauriZoomAction';
+  var TOKEN = '{token}';
+  var isTop = window.top === window;
+
+  function run(action) {{
+    if (isTop) {{
+      window.__TAURI_INTERNALS__.invoke(action);
+    }} else {{
+      window.top.postMessage({{ [MESSAGE_KEY]: action, token: TOKEN }}, '*');
+    }}
+  }}
+
+  function isFrameOf(source, parent) {{
+    for (var i = 0; i < parent.frames.length; i++) {{
+      if (parent.frames[i] === source || isFrameOf(source, parent.frames[i])) return true;
+    }}
+    return false;
+  }}
+
+  // Opaque origins of sandboxed and `data:` frames say nothing about who sent the message
+  function isAllowedOrigin(origin) {{
+    return origin === window.location.origin || /^https?:\/\//.test(origin);
+  }}
+
+  document.addEventListener('keydown', function (event) {{
+    if (!event.{modifier} || event.altKey) return;
+    var action = event.code === 'NumpadAdd' ? 'zoom_in'
+      : event.code === 'NumpadSubtract' ? 'zoom_out'
+        : event.code === 'Numpad0' ? 'zoom_reset'
+          : ACTIONS[event.key];
+    if (!action) return;
+
+    event.preventDefault();
+    event.stopPropagation();
+    run(action);
+  }}, true);
+
+  if (isTop) {{
+    window.addEventListener('message', function (event) {{
+      if (!event.source || event.source === window || !isFrameOf(event.source, window)) return;
+      if (!isAllowedOrigin(event.origin)) return;
+      if (!event.data || event.data.token !== TOKEN) return;
+
+      var action = event.data[MESSAGE_KEY];
+      if (action === 'zoom_in' || action === 'zoom_out' || action === 'zoom_reset') {{
+        window.__TAURI_INTERNALS__.invoke(action);
+      }}
+    }});
+  }}
+}})();"#,
+    modifier = SHORTCUT_MODIFIER,
+    token = uuid::Uuid::new_v4()
+  )
+}
+
+pub fn validate_factor(factor: f64) -> Result<(), String> {
+  if factor.is_finite() && (MIN_ZOOM..=MAX_ZOOM).contains(&factor) {
+    Ok(())
+  } else {
+    Err(format!(
+      "Zoom must be between {} and {}, got {}",
+      MIN_ZOOM, MAX_ZOOM, factor
+    ))
+  }
+}
+
+/// Next step above `current`, or below it when `is_increase` is false.
+pub fn step(current: f64, is_increase: bool) -> f64 {
+  let level = if is_increase {
+    ZOOM_LEVELS
+      .iter()
+      .find(|level| **level > current + ZOOM_EPSILON)
+  } else {
+    ZOOM_LEVELS
+      .iter()
+      .rev()
+      .find(|level| **level < current - ZOOM_EPSILON)
+  };
+
+  level.copied().unwrap_or(current)
+}
+
+/// Stored zoom of new windows with the role.
+pub fn level_for(role: WindowRole) -> f64 {
+  crate::settings::get()
+    .zoom
+    .levels
+    .get(&role)
+    .copied()
+    .unwrap_or(1.0)
+}
+
+fn current_zoom(window: &WebviewWindow) -> (WindowRole, f64) {
+  WINDOW_STATES
+    .lock()
+    .ok()
+    .and_then(|states| {
+      states
+        .get(window.label())
+        .map(|state| (state.role, state.zoom))
+    })
+    .unwrap_or((WindowRole::Main, 1.0))
+}
+
+/// Zooms the window and remembers the factor for new windows of its role.
+pub fn set_zoom(app: &AppHandle, window: &WebviewWindow, factor: f64) -> Result<f64, String> {
+  validate_factor(factor)?;
+  window.set_zoom(factor).map_err(|err| err.to_string())?;
+
+  let (role, _) = current_zoom(window);
+  if let Ok(mut states) = WINDOW_STATES.lock() {
+    if let Some(state) = states.get_mut(window.label()) {
+      state.zoom = factor;
+    }
+  }
+
+  crate::settings::modify(app, |settings| {
+    if (factor - 1.0).abs() < ZOOM_EPSILON {
+      settings.zoom.levels.remove(&role);
+    } else {
+      settings.zoom.levels.insert(role, factor);
+    }
+  });
+
+  app
+    .emit_to(window.label(), "zoom-changed", json!({ "factor": factor }))
+    .unwrap_or_default();
+
+  Ok(factor)
+}
+
+pub fn zoom_in(app: &AppHandle, window: &WebviewWindow) -> Result<f64, String> {
+  let (_, zoom) = current_zoom(window);
+  set_zoom(app, window, step(zoom, true))
+}
+
+pub fn zoom_out(app: &AppHandle, window: &WebviewWindow) -> Result<f64, String> {
+  let (_, zoom) = current_zoom(window);
+  set_zoom(app, window, step(zoom, false))
+}
+
+pub fn zoom_reset(app: &AppHandle, window: &WebviewWindow) -> Result<f64, String> {
+  set_zoom(app, window, 1.0)
+}
@@ -285,6 +285,8 @@ function setViteEnv(env: Record<string, string>) {
   });
 }
 
+// Sent in the `<meta>` tag of `index.html`, which is also how Tauri windows get it,
+// as they load the app from `BASE_URL`
 function buildCsp(appEnv: string) {
   return `
   default-src 'self';